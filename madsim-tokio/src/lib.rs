@@ -21,10 +21,8 @@ mod sim {
     pub use madsim;
 
     pub mod task {
-        #[cfg(tokio_unstable)]
-        pub use madsim::task::yield_now as consume_budget;
-
         pub use madsim::task::*;
+        // keep the semantics of tokio: values are not inherited by spawned tasks
         #[cfg(feature = "rt")]
         pub use tokio::task::LocalKey;
     }
@@ -26,4 +26,7 @@ pub use _std::*;
 #[doc(hidden)]
 pub mod export {
     pub use futures_util as futures;
+
+    #[cfg(madsim)]
+    pub use crate::sim::{join::join_order, select::select_start};
 }
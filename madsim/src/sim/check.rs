@@ -0,0 +1,209 @@
+//! Run a scenario across a range of seeds.
+
+use crate::{runtime::Runtime, Config};
+use std::{any::Any, fmt::Debug, ops::Range, panic::AssertUnwindSafe};
+
+/// Runs `f` with a fresh [`Runtime`] for each seed in `seeds`, stopping at the first failure.
+///
+/// If `f` panics, the failing seed and its index in `seeds` are reported,
+/// and the panic is propagated.
+///
+/// # Example
+///
+/// ```
+/// use madsim::time::{sleep, Duration};
+///
+/// madsim::check(0..10, |rt| {
+///     rt.block_on(async {
+///         sleep(Duration::from_secs(1)).await;
+///     });
+/// });
+/// ```
+pub fn check(seeds: Range<u64>, f: impl Fn(Runtime)) {
+    if let Some(failure) = run(seeds, true, f).into_iter().next() {
+        failure.report();
+        std::panic::resume_unwind(failure.payload);
+    }
+}
+
+/// Runs `f` with a fresh [`Runtime`] for each seed in `seeds`, continuing after failures.
+///
+/// After all seeds are run, all failing seeds and their indices in `seeds` are reported,
+/// and the panic of the first failure is propagated.
+///
+/// # Example
+///
+/// ```should_panic
+/// madsim::check_all(0..10, |rt| {
+///     let seed = rt.seed();
+///     rt.block_on(async move {
+///         assert!(seed % 3 != 0);
+///     });
+/// });
+/// ```
+pub fn check_all(seeds: Range<u64>, f: impl Fn(Runtime)) {
+    let failures = run(seeds, false, f);
+    for failure in &failures {
+        failure.report();
+    }
+    if let Some(failure) = failures.into_iter().next() {
+        std::panic::resume_unwind(failure.payload);
+    }
+}
+
+/// Runs `f` twice with a fresh [`Runtime`] under the same `seed`, and asserts that
+/// the outputs are equal.
+///
+/// Returns the output on success. Otherwise, panics with a line diff of the outputs.
+/// Unlike [`Runtime::check_determinism`], which compares the random numbers drawn,
+/// this compares only the outputs, so `f` can return whatever state it cares about.
+///
+/// # Example
+///
+/// ```
+/// use madsim::{task, time::{sleep, Duration}};
+/// use std::sync::{Arc, Mutex};
+///
+/// let order = madsim::assert_deterministic(1, |rt| {
+///     rt.block_on(async {
+///         let order = Arc::new(Mutex::new(vec![]));
+///         let tasks = (0..3).map(|i| {
+///             let order = order.clone();
+///             task::spawn(async move { order.lock().unwrap().push(i) })
+///         });
+///         task::join_all(tasks).await;
+///         let order = order.lock().unwrap().clone();
+///         order
+///     })
+/// });
+/// assert_eq!(order.len(), 3);
+/// ```
+pub fn assert_deterministic<T: PartialEq + Debug>(seed: u64, f: impl Fn(Runtime) -> T) -> T {
+    let first = f(Runtime::with_seed_and_config(seed, Config::default()));
+    let second = f(Runtime::with_seed_and_config(seed, Config::default()));
+    if first != second {
+        panic!(
+            "non-deterministic output under seed {seed}:\n{}",
+            diff(&format!("{first:#?}"), &format!("{second:#?}"))
+        );
+    }
+    first
+}
+
+/// Returns a line diff of `a` and `b`, prefixing lines only in `a` with `-` and
+/// lines only in `b` with `+`.
+fn diff(a: &str, b: &str) -> String {
+    let a = a.lines().collect::<Vec<_>>();
+    let b = b.lines().collect::<Vec<_>>();
+    // longest common subsequence
+    let mut lcs = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lcs[i][j] = if a[i] == b[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+    let mut out = String::new();
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() || j < b.len() {
+        if i < a.len() && j < b.len() && a[i] == b[j] {
+            out += &format!("  {}\n", a[i]);
+            i += 1;
+            j += 1;
+        } else if j == b.len() || (i < a.len() && lcs[i + 1][j] >= lcs[i][j + 1]) {
+            out += &format!("- {}\n", a[i]);
+            i += 1;
+        } else {
+            out += &format!("+ {}\n", b[j]);
+            j += 1;
+        }
+    }
+    out
+}
+
+/// A panic under a seed.
+struct Failure {
+    index: usize,
+    seed: u64,
+    payload: Box<dyn Any + Send>,
+}
+
+impl Failure {
+    fn report(&self) {
+        eprintln!(
+            "note: seed {} (#{}) failed, run with `MADSIM_TEST_SEED={}` environment variable to reproduce this error",
+            self.seed, self.index, self.seed
+        );
+    }
+}
+
+fn run(seeds: Range<u64>, fail_fast: bool, f: impl Fn(Runtime)) -> Vec<Failure> {
+    let mut failures = vec![];
+    for (index, seed) in seeds.enumerate() {
+        let rt = Runtime::with_seed_and_config(seed, Config::default());
+        if let Err(payload) = std::panic::catch_unwind(AssertUnwindSafe(|| f(rt))) {
+            failures.push(Failure {
+                index,
+                seed,
+                payload,
+            });
+            if fail_fast {
+                break;
+            }
+        }
+    }
+    failures
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+
+    #[test]
+    fn stop_at_first_failure() {
+        let seeds = RefCell::new(vec![]);
+        let result = std::panic::catch_unwind(AssertUnwindSafe(|| {
+            check(10..20, |rt| {
+                seeds.borrow_mut().push(rt.seed());
+                assert!(rt.seed() < 13);
+            })
+        }));
+        assert!(result.is_err());
+        assert_eq!(*seeds.borrow(), [10, 11, 12, 13]);
+    }
+
+    #[test]
+    fn run_all() {
+        let seeds = RefCell::new(vec![]);
+        let failures = run(0..10, false, |rt| {
+            seeds.borrow_mut().push(rt.seed());
+            assert!(rt.seed() % 3 != 0);
+        });
+        assert_eq!(seeds.borrow().len(), 10);
+        let failed = failures
+            .iter()
+            .map(|f| (f.index, f.seed))
+            .collect::<Vec<_>>();
+        assert_eq!(failed, [(0, 0), (3, 3), (6, 6), (9, 9)]);
+    }
+
+    #[test]
+    fn non_deterministic() {
+        let count = std::cell::Cell::new(0);
+        let result = std::panic::catch_unwind(AssertUnwindSafe(|| {
+            assert_deterministic(0, |_| {
+                count.set(count.get() + 1);
+                vec![1, count.get(), 3]
+            })
+        }));
+        let payload = result.unwrap_err();
+        let msg = payload.downcast_ref::<String>().unwrap();
+        assert!(msg.contains("seed 0"));
+        assert!(msg.contains("-     1,\n+     2,\n"), "{msg}");
+        assert!(msg.contains("      3,"), "{msg}");
+    }
+}
@@ -5,14 +5,29 @@ use std::{
     str::FromStr,
 };
 
-use crate::net::{self, tcp};
+use crate::{
+    net::{self, tcp},
+    rand::RngAlgorithm,
+    task,
+};
 use ahash::AHasher;
 use serde::{Deserialize, Serialize};
 
 /// Simulation configuration.
 #[cfg_attr(docsrs, doc(cfg(madsim)))]
-#[derive(Debug, Default, Serialize, Deserialize, PartialEq, Hash, Clone)]
+#[derive(Debug, Default, Serialize, Deserialize, PartialEq, Clone)]
 pub struct Config {
+    /// The algorithm of the global random number generator.
+    #[serde(default)]
+    pub rng_algorithm: RngAlgorithm,
+
+    /// Log the seed and the simulated time when a task panics.
+    ///
+    /// This installs a panic hook that reports them via `tracing` before unwinding,
+    /// so that the failure can be reproduced with the same seed.
+    #[serde(default)]
+    pub report_seed_on_panic: bool,
+
     /// Network configurations.
     #[serde(default)]
     pub net: net::Config,
@@ -20,10 +35,28 @@ pub struct Config {
     /// Tcp Configurations
     #[serde(default)]
     pub tcp: tcp::TcpConfig,
+
+    /// Task scheduler configurations.
+    #[serde(default)]
+    pub task: task::Config,
+}
+
+impl Hash for Config {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        // `report_seed_on_panic` only affects diagnostics
+        self.rng_algorithm.hash(state);
+        self.net.hash(state);
+        self.tcp.hash(state);
+        self.task.hash(state);
+    }
 }
 
 impl Config {
     /// Returns the hash value of this config.
+    ///
+    /// Options that only affect diagnostics, such as
+    /// [`report_seed_on_panic`](Self::report_seed_on_panic), are not included,
+    /// so they can be changed when reproducing a failure with the same seed.
     pub fn hash(&self) -> u64 {
         let mut hasher = AHasher::new_with_keys(0, 0);
         Hash::hash(self, &mut hasher);
@@ -43,7 +76,9 @@ impl FromStr for Config {
 /// Print the config into TOML.
 impl ToString for Config {
     fn to_string(&self) -> String {
-        toml::to_string_pretty(self).unwrap()
+        // a `toml::Value` puts the tables, such as ranges of durations, after the values
+        let value = toml::Value::try_from(self).unwrap();
+        toml::to_string_pretty(&value).unwrap()
     }
 }
 
@@ -67,12 +102,40 @@ mod tests {
         assert_eq!(
             config,
             Config {
+                rng_algorithm: RngAlgorithm::SmallRng,
+                report_seed_on_panic: false,
                 net: net::Config {
                     packet_loss_rate: 0.1,
                     send_latency: Duration::from_millis(1)..Duration::from_millis(10)
                 },
-                tcp: tcp::TcpConfig {}
+                tcp: tcp::TcpConfig {},
+                task: task::Config::default(),
             }
         );
     }
+
+    #[test]
+    fn hash_ignores_diagnostics() {
+        let config = Config::default();
+        let mut diag = config.clone();
+        diag.report_seed_on_panic = true;
+        diag.task.strict_join_handles = true;
+        diag.task.record_trace = true;
+        assert_eq!(diag.hash(), config.hash());
+
+        let mut other = config.clone();
+        other.task.detect_deadlocks = true;
+        assert_ne!(other.hash(), config.hash());
+        let mut other = config.clone();
+        other.rng_algorithm = RngAlgorithm::ChaCha8;
+        assert_ne!(other.hash(), config.hash());
+    }
+
+    #[test]
+    fn print_and_parse() {
+        let mut config = Config::default();
+        config.task.poll_duration = Duration::from_millis(1)..Duration::from_millis(2);
+        let parsed: Config = config.to_string().parse().unwrap();
+        assert_eq!(parsed, config);
+    }
 }
@@ -0,0 +1,210 @@
+//! Deterministic generation of random test inputs.
+//!
+//! Generators are closures drawing from the [`GlobalRng`], so the generated inputs and the
+//! scheduling of a test are both determined by the seed. A failing seed reproduces the
+//! same workload as well as the same execution.
+//!
+//! # Example
+//!
+//! ```
+//! use madsim::{generate, rand::Rng, runtime::Runtime};
+//!
+//! #[derive(Debug, PartialEq)]
+//! enum Op {
+//!     Put(u32, u64),
+//!     Get(u32),
+//! }
+//!
+//! let ops = |seed| {
+//!     let rt = Runtime::with_seed_and_config(seed, madsim::Config::default());
+//!     rt.block_on(async {
+//!         let mut kind = generate::weighted(&[3, 1]);
+//!         let mut key = generate::range(0..10u32);
+//!         generate::gen_ops(100, |rng| match kind(rng) {
+//!             0 => Op::Put(key(rng), rng.gen()),
+//!             _ => Op::Get(key(rng)),
+//!         })
+//!     })
+//! };
+//! assert_eq!(ops(1).len(), 100);
+//! assert_eq!(ops(1), ops(1));
+//! ```
+
+use crate::rand::{distributions::uniform::SampleUniform, GlobalRng, Rng};
+use std::ops::Range;
+
+/// Generates `count` values by calling the generator with the global random number generator.
+///
+/// # Panics
+///
+/// This function panics if called outside a madsim runtime.
+pub fn gen_ops<T>(count: usize, mut generator: impl FnMut(&mut GlobalRng) -> T) -> Vec<T> {
+    let mut rng = crate::rand::thread_rng();
+    (0..count).map(|_| generator(&mut rng)).collect()
+}
+
+/// Returns a generator of values uniformly drawn from the range.
+///
+/// # Panics
+///
+/// The generator panics if the range is empty.
+pub fn range<T>(range: Range<T>) -> impl FnMut(&mut GlobalRng) -> T
+where
+    T: SampleUniform + PartialOrd + Clone,
+{
+    move |rng| rng.gen_range(range.clone())
+}
+
+/// Returns a generator of values uniformly chosen from `choices`.
+///
+/// # Panics
+///
+/// This function panics if `choices` is empty.
+pub fn choose<T: Clone>(choices: &[T]) -> impl FnMut(&mut GlobalRng) -> T + '_ {
+    assert!(!choices.is_empty(), "no choice to choose from");
+    move |rng| choices[rng.gen_range(0..choices.len())].clone()
+}
+
+/// Returns a generator of indices into `weights`, each drawn with a probability
+/// proportional to its weight.
+///
+/// This is handy to pick the kind of an operation, e.g. 3 writes for each read.
+///
+/// # Panics
+///
+/// This function panics if all weights are zero.
+pub fn weighted(weights: &[u32]) -> impl FnMut(&mut GlobalRng) -> usize + '_ {
+    let total = weights.iter().map(|&w| w as u64).sum::<u64>();
+    assert!(total > 0, "the total weight must be positive");
+    move |rng| {
+        let mut n = rng.gen_range(0..total);
+        (weights.iter())
+            .position(|&w| match n.checked_sub(w as u64) {
+                Some(rest) => {
+                    n = rest;
+                    false
+                }
+                None => true,
+            })
+            .unwrap()
+    }
+}
+
+/// Returns a generator of `true` with probability `p`.
+///
+/// # Panics
+///
+/// This function panics if `p` is not in `[0, 1]`.
+pub fn with_probability(p: f64) -> impl FnMut(&mut GlobalRng) -> bool {
+    assert!((0.0..=1.0).contains(&p), "probability must be in [0, 1]");
+    move |rng| rng.gen_bool(p)
+}
+
+/// Returns a generator of vectors whose length is drawn from `len`, with elements
+/// produced by `element`.
+///
+/// # Panics
+///
+/// The generator panics if `len` is empty.
+pub fn vec_of<T>(
+    len: Range<usize>,
+    mut element: impl FnMut(&mut GlobalRng) -> T,
+) -> impl FnMut(&mut GlobalRng) -> Vec<T> {
+    move |rng| {
+        let len = rng.gen_range(len.clone());
+        (0..len).map(|_| element(rng)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runtime::Runtime;
+    use std::collections::{BTreeMap, HashSet};
+
+    #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+    enum Op {
+        Put(u32, Vec<u8>),
+        Get(u32),
+        Delete(u32),
+    }
+
+    fn ops(seed: u64) -> Vec<Op> {
+        let runtime = Runtime::with_seed_and_config(seed, crate::Config::default());
+        runtime.block_on(async {
+            let mut kind = weighted(&[5, 3, 1]);
+            let mut key = range(0..16u32);
+            let mut value = vec_of(0..8, |rng| rng.gen());
+            gen_ops(100, |rng| match kind(rng) {
+                0 => Op::Put(key(rng), value(rng)),
+                1 => Op::Get(key(rng)),
+                _ => Op::Delete(key(rng)),
+            })
+        })
+    }
+
+    fn replay(ops: &[Op]) -> Vec<Option<Vec<u8>>> {
+        let mut map = BTreeMap::new();
+        (ops.iter())
+            .map(|op| match op {
+                Op::Put(k, v) => map.insert(*k, v.clone()),
+                Op::Get(k) => map.get(k).cloned(),
+                Op::Delete(k) => map.remove(k),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn deterministic_ops() {
+        let mut workloads = HashSet::new();
+        for seed in 0..10 {
+            let ops1 = ops(seed);
+            assert_eq!(ops1.len(), 100);
+            assert_eq!(ops1, ops(seed));
+            let mut counts = [0; 3];
+            for op in &ops1 {
+                let (kind, key) = match op {
+                    Op::Put(k, v) => {
+                        assert!(v.len() < 8);
+                        (0, k)
+                    }
+                    Op::Get(k) => (1, k),
+                    Op::Delete(k) => (2, k),
+                };
+                assert!(*key < 16);
+                counts[kind] += 1;
+            }
+            // the kinds follow the weights
+            assert!(counts[0] > counts[1] && counts[1] > counts[2] && counts[2] > 0);
+            // the keys are few enough for reads and deletes to hit earlier writes
+            assert!(replay(&ops1).iter().any(Option::is_some));
+            workloads.insert(ops1);
+        }
+        assert_eq!(workloads.len(), 10);
+    }
+
+    #[test]
+    fn combinators() {
+        let runtime = Runtime::new();
+        runtime.block_on(async {
+            let mut rng = crate::rand::thread_rng();
+            let counts =
+                gen_ops(1000, weighted(&[1, 0, 3]))
+                    .into_iter()
+                    .fold([0; 3], |mut counts, i| {
+                        counts[i] += 1;
+                        counts
+                    });
+            assert_eq!(counts[1], 0);
+            assert!(counts[2] > counts[0]);
+
+            assert!(gen_ops(100, choose(&["a", "b"])).contains(&"b"));
+            assert!(gen_ops(100, with_probability(0.0)).iter().all(|b| !b));
+            assert!(gen_ops(100, with_probability(1.0)).iter().all(|&b| b));
+
+            let v = vec_of(2..4, range(0..10))(&mut rng);
+            assert!((2..4).contains(&v.len()));
+            assert!(v.iter().all(|x| (0..10).contains(x)));
+        });
+    }
+}
@@ -0,0 +1,139 @@
+/// Waits on multiple concurrent branches, returning when all branches complete.
+///
+/// This is similar to [`futures::join!`], but on each poll, the pending branches are polled
+/// in a random order chosen by the deterministic random number generator, instead of left
+/// to right. So a branch written first does not always get to run before the others when
+/// they all make progress on the same poll.
+///
+/// The outputs are returned in a tuple in the same order as the branches given.
+/// This macro must be used inside of async functions, closures, and blocks.
+///
+/// [`futures::join!`]: https://docs.rs/futures/0.3/futures/macro.join.html
+///
+/// # Example
+///
+/// ```
+/// use madsim::runtime::Runtime;
+///
+/// let rt = Runtime::new();
+/// let outputs = rt.block_on(async {
+///     madsim::join!(async { 1 }, async { "two" }, async { 3.0 })
+/// });
+/// assert_eq!(outputs, (1, "two", 3.0));
+/// ```
+#[macro_export]
+#[cfg_attr(docsrs, doc(cfg(madsim)))]
+macro_rules! join {
+    // all branches are normalized, each is prefixed by `_`s to skip the preceding fields
+    (@{ $( ( $($skip:tt)* ) $e:expr, )* }) => {{
+        use $crate::export::futures::future::{maybe_done, poll_fn, Future};
+        use ::std::{pin::Pin, task::Poll};
+
+        let mut futures = ( $( maybe_done($e), )* );
+        // the futures are never moved since they are borrowed
+        let futures = &mut futures;
+        poll_fn(move |cx| {
+            let mut pending = false;
+            for i in $crate::export::join_order(0 $(+ $crate::join!(@count $e))*) {
+                let mut k = 0usize;
+                $(
+                    if i == k {
+                        let ( $($skip,)* fut, .. ) = &mut *futures;
+                        // Safety: the future is never moved
+                        let fut = unsafe { Pin::new_unchecked(fut) };
+                        pending |= fut.poll(cx).is_pending();
+                    }
+                    k += 1;
+                )*
+                let _ = k;
+            }
+            if pending {
+                return Poll::Pending;
+            }
+            Poll::Ready(( $({
+                let ( $($skip,)* fut, .. ) = &mut *futures;
+                // Safety: the future is never moved
+                let fut = unsafe { Pin::new_unchecked(fut) };
+                fut.take_output().expect("future completed")
+            }, )* ))
+        })
+        .await
+    }};
+    (@{ $($t:tt)* } ( $($s:tt)* ) $e:expr, $($rest:tt)*) => {
+        $crate::join!(@{ $($t)* ( $($s)* ) $e, } ( $($s)* _ ) $($rest)*)
+    };
+    (@{ $($t:tt)* } ( $($s:tt)* )) => {
+        $crate::join!(@{ $($t)* })
+    };
+    (@count $e:expr) => {
+        1usize
+    };
+
+    ($($e:expr),+ $(,)?) => {
+        $crate::join!(@{ } () $($e,)+)
+    };
+}
+
+/// Returns the order to poll the branches in [`join!`].
+pub fn join_order(branches: usize) -> Vec<usize> {
+    use rand::seq::SliceRandom;
+    let mut order = (0..branches).collect::<Vec<_>>();
+    if branches > 1 {
+        order.shuffle(&mut crate::rand::thread_rng());
+    }
+    order
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{runtime::Runtime, time};
+    use std::{
+        collections::HashSet,
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+
+    #[test]
+    fn random_poll_order() {
+        let run = |seed: u64| {
+            let runtime = Runtime::with_seed_and_config(seed, crate::Config::default());
+            runtime.block_on(async {
+                let log = Arc::new(Mutex::new(vec![]));
+                let branch = |i: usize| {
+                    let log = log.clone();
+                    async move {
+                        log.lock().unwrap().push(i);
+                        time::sleep(Duration::from_secs(1)).await;
+                        log.lock().unwrap().push(i + 10);
+                        i
+                    }
+                };
+                let outputs = crate::join!(branch(0), branch(1), branch(2), branch(3));
+                assert_eq!(outputs, (0, 1, 2, 3));
+                let log = log.lock().unwrap().clone();
+                log
+            })
+        };
+        let mut logs = HashSet::new();
+        for seed in 0..10 {
+            let log = run(seed);
+            assert_eq!(log, run(seed));
+            // every branch is polled once before any of them wakes up from the sleep
+            let (mut first, mut second) = (log[..4].to_vec(), log[4..].to_vec());
+            first.sort();
+            second.sort();
+            assert_eq!(first, [0, 1, 2, 3]);
+            assert_eq!(second, [10, 11, 12, 13]);
+            logs.insert(log);
+        }
+        // the branches are not always polled from left to right
+        assert_eq!(logs.len(), 10);
+    }
+
+    #[test]
+    fn single_branch() {
+        let runtime = Runtime::new();
+        let output = runtime.block_on(async { crate::join!(async { 1 }) });
+        assert_eq!(output, (1,));
+    }
+}
@@ -1,5 +1,6 @@
 #![deny(missing_docs)]
 
+pub use self::check::{assert_deterministic, check, check_all};
 pub use self::config::Config;
 pub(crate) use self::runtime::context;
 
@@ -7,14 +8,41 @@ pub(crate) use self::runtime::context;
 #[cfg_attr(docsrs, doc(cfg(feature = "macros")))]
 pub use madsim_macros::{main, test, tokio_main, tokio_test};
 
+mod check;
 mod config;
 pub mod fs;
+pub mod generate;
+pub(crate) mod join;
 pub mod net;
 #[cfg_attr(docsrs, doc(cfg(madsim)))]
 pub mod plugin;
 pub mod rand;
 #[cfg_attr(docsrs, doc(cfg(madsim)))]
 pub mod runtime;
+pub(crate) mod select;
+pub mod stream;
+pub mod sync;
 pub mod task;
 pub mod time;
 mod utils;
+
+/// Returns `true` if called within a madsim runtime.
+///
+/// Unlike the `madsim` cfg, which tells whether the simulator is compiled in, this checks at
+/// runtime whether the current thread is running a simulation. Code compiled with the
+/// simulator may still run outside of it, e.g. in a plain `#[test]` or a helper thread.
+/// Without the `madsim` cfg, this function always returns `false`.
+///
+/// # Example
+///
+/// ```
+/// use madsim::runtime::Runtime;
+///
+/// assert!(!madsim::is_simulated());
+/// Runtime::new().block_on(async {
+///     assert!(madsim::is_simulated());
+/// });
+/// ```
+pub fn is_simulated() -> bool {
+    context::try_current(|_| ()).is_some()
+}
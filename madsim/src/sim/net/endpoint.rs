@@ -132,6 +132,7 @@ impl Endpoint {
             .await
             .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "network is down"))?;
         self.guard.net.rand_delay().await?;
+        crate::task::consume_budget().await;
 
         trace!("recv: {} <- {}, tag={}", self.guard.addr, msg.from, msg.tag);
         Ok((msg.data, msg.from))
@@ -188,6 +189,7 @@ impl Endpoint {
 
         let (tx, rx, addr) = (self.conn_rx.recv().await)
             .map_err(|e| io::Error::new(io::ErrorKind::ConnectionReset, e))?;
+        crate::task::consume_budget().await;
         let sender = Sender {
             _guard: self.guard.clone(),
             tx,
@@ -215,6 +217,7 @@ pub struct Receiver {
 impl Sender {
     #[doc(hidden)]
     pub async fn send(&self, value: Payload) -> io::Result<()> {
+        crate::task::consume_budget().await;
         (self.tx.send(value))
             .map_err(|_| io::Error::new(io::ErrorKind::ConnectionReset, "connection reset"))
     }
@@ -223,8 +226,10 @@ impl Sender {
 impl Receiver {
     #[doc(hidden)]
     pub async fn recv(&mut self) -> io::Result<Payload> {
-        (self.rx.recv().await)
-            .ok_or_else(|| io::Error::new(io::ErrorKind::ConnectionReset, "connection reset"))
+        let payload = (self.rx.recv().await)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::ConnectionReset, "connection reset"));
+        crate::task::consume_budget().await;
+        payload
     }
 }
 
@@ -160,8 +160,10 @@ impl Endpoint {
                     net.send_to_raw(from, rsp_tag, Box::new((rsp, Bytes::from(data))))
                         .await
                         .unwrap();
-                });
+                })
+                .detach();
             }
-        });
+        })
+        .detach();
     }
 }
@@ -24,7 +24,8 @@ use rand::{
     distributions::Standard,
     prelude::{Distribution, SmallRng},
 };
-
+use rand_chacha::{ChaCha12Rng, ChaCha8Rng};
+use serde::{Deserialize, Serialize};
 use spin::Mutex;
 use std::cell::Cell;
 use std::sync::Arc;
@@ -45,6 +46,9 @@ pub mod prelude {
 }
 
 /// Global deterministic random number generator.
+///
+/// The same random stream is shared by user code and the simulator itself,
+/// e.g. for task scheduling and network latency. Retrieve it by [`thread_rng()`].
 #[cfg_attr(docsrs, doc(cfg(madsim)))]
 #[derive(Clone)]
 pub struct GlobalRng {
@@ -53,14 +57,85 @@ pub struct GlobalRng {
 
 struct Inner {
     seed: u64,
-    rng: SmallRng,
+    rng: InnerRng,
     log: Option<Vec<u8>>,
     check: Option<(Vec<u8>, usize)>,
 }
 
+/// The algorithm of the global random number generator.
+///
+/// A `(seed, algorithm)` pair is a stable contract: it generates the same random stream
+/// across madsim versions, except for [`SmallRng`](RngAlgorithm::SmallRng) whose
+/// algorithm is defined by the `rand` crate.
+#[cfg_attr(docsrs, doc(cfg(madsim)))]
+#[derive(Debug, Default, Serialize, Deserialize, PartialEq, Eq, Hash, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum RngAlgorithm {
+    /// `SmallRng` of `rand` 0.8, seeded by `seed_from_u64`.
+    ///
+    /// It may change when upgrading the `rand` crate or on other platforms.
+    #[default]
+    SmallRng,
+    /// ChaCha with 8 rounds of `rand_chacha` 0.3, seeded by `seed_from_u64`.
+    ChaCha8,
+    /// ChaCha with 12 rounds of `rand_chacha` 0.3, seeded by `seed_from_u64`.
+    ChaCha12,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) enum InnerRng {
+    SmallRng(SmallRng),
+    ChaCha8(ChaCha8Rng),
+    ChaCha12(ChaCha12Rng),
+}
+
+impl InnerRng {
+    fn new(seed: u64, algorithm: RngAlgorithm) -> Self {
+        match algorithm {
+            RngAlgorithm::SmallRng => InnerRng::SmallRng(SeedableRng::seed_from_u64(seed)),
+            RngAlgorithm::ChaCha8 => InnerRng::ChaCha8(SeedableRng::seed_from_u64(seed)),
+            RngAlgorithm::ChaCha12 => InnerRng::ChaCha12(SeedableRng::seed_from_u64(seed)),
+        }
+    }
+}
+
+impl RngCore for InnerRng {
+    fn next_u32(&mut self) -> u32 {
+        match self {
+            InnerRng::SmallRng(rng) => rng.next_u32(),
+            InnerRng::ChaCha8(rng) => rng.next_u32(),
+            InnerRng::ChaCha12(rng) => rng.next_u32(),
+        }
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        match self {
+            InnerRng::SmallRng(rng) => rng.next_u64(),
+            InnerRng::ChaCha8(rng) => rng.next_u64(),
+            InnerRng::ChaCha12(rng) => rng.next_u64(),
+        }
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        match self {
+            InnerRng::SmallRng(rng) => rng.fill_bytes(dest),
+            InnerRng::ChaCha8(rng) => rng.fill_bytes(dest),
+            InnerRng::ChaCha12(rng) => rng.fill_bytes(dest),
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        match self {
+            InnerRng::SmallRng(rng) => rng.try_fill_bytes(dest),
+            InnerRng::ChaCha8(rng) => rng.try_fill_bytes(dest),
+            InnerRng::ChaCha12(rng) => rng.try_fill_bytes(dest),
+        }
+    }
+}
+
 impl GlobalRng {
-    /// Create a new RNG using the given seed.
-    pub(crate) fn new_with_seed(seed: u64) -> Self {
+    /// Create a new RNG using the given seed and algorithm.
+    pub(crate) fn new_with_seed(seed: u64, algorithm: RngAlgorithm) -> Self {
         // XXX: call this function to make sure it won't be gc.
         unsafe { getentropy(std::ptr::null_mut(), 0) };
         if !init_std_random_state(seed) {
@@ -71,7 +146,7 @@ impl GlobalRng {
 
         let inner = Inner {
             seed,
-            rng: SeedableRng::seed_from_u64(seed),
+            rng: InnerRng::new(seed, algorithm),
             log: None,
             check: None,
         };
@@ -81,7 +156,7 @@ impl GlobalRng {
     }
 
     /// Call function on the inner RNG.
-    pub(crate) fn with<T>(&self, f: impl FnOnce(&mut SmallRng) -> T) -> T {
+    pub(crate) fn with<T>(&self, f: impl FnOnce(&mut InnerRng) -> T) -> T {
         let mut lock = self.inner.lock();
         let ret = f(&mut lock.rng);
         // log or check
@@ -107,6 +182,18 @@ impl GlobalRng {
         ret
     }
 
+    /// Take a snapshot of the state of the generator.
+    pub fn snapshot(&self) -> RngState {
+        RngState(self.inner.lock().rng.clone())
+    }
+
+    /// Restore the state of the generator from a snapshot.
+    ///
+    /// The subsequent random stream is exactly the same as the one after the snapshot was taken.
+    pub fn restore(&self, state: RngState) {
+        self.inner.lock().rng = state.0;
+    }
+
     pub(crate) fn seed(&self) -> u64 {
         let lock = self.inner.lock();
         lock.seed
@@ -132,6 +219,14 @@ impl GlobalRng {
 }
 
 /// Retrieve the deterministic random number generator from the current madsim context.
+///
+/// The generator draws from the same stream as the scheduler. So consuming randomness
+/// also shifts the subsequent scheduling decisions. This is intended: the whole execution,
+/// including the generated values, is determined by the seed.
+///
+/// # Panics
+///
+/// This function panics if called outside a madsim runtime.
 pub fn thread_rng() -> GlobalRng {
     crate::context::current(|h| h.rand.clone())
 }
@@ -163,6 +258,71 @@ where
     thread_rng().gen()
 }
 
+/// Shuffles a slice in place using the global random number generator.
+///
+/// # Example
+///
+/// ```
+/// use madsim::runtime::Runtime;
+///
+/// Runtime::new().block_on(async {
+///     let mut nodes = [1, 2, 3, 4];
+///     madsim::rand::shuffle(&mut nodes);
+/// });
+/// ```
+pub fn shuffle<T>(slice: &mut [T]) {
+    use rand::seq::SliceRandom;
+    slice.shuffle(&mut thread_rng());
+}
+
+/// Returns a random element of the slice using the global random number generator,
+/// or `None` if the slice is empty.
+///
+/// # Example
+///
+/// ```
+/// use madsim::runtime::Runtime;
+///
+/// Runtime::new().block_on(async {
+///     let nodes = [1, 2, 3, 4];
+///     let victim = madsim::rand::choose(&nodes).unwrap();
+///     assert!(nodes.contains(victim));
+/// });
+/// ```
+pub fn choose<T>(slice: &[T]) -> Option<&T> {
+    use rand::seq::SliceRandom;
+    slice.choose(&mut thread_rng())
+}
+
+/// Generates the bytes of a random (version 4) UUID using the global random number generator.
+///
+/// Libraries like `uuid` read randomness from the OS, which may not be intercepted by madsim.
+/// Create UUIDs from this function instead to make them deterministic, e.g. with
+/// `uuid::Uuid::from_bytes(madsim::rand::uuid_v4())`.
+///
+/// # Example
+///
+/// ```
+/// use madsim::runtime::Runtime;
+///
+/// Runtime::new().block_on(async {
+///     let uuid = madsim::rand::uuid_v4();
+///     assert_eq!(uuid[6] >> 4, 4); // version
+///     assert_eq!(uuid[8] >> 6, 0b10); // variant
+/// });
+/// ```
+pub fn uuid_v4() -> [u8; 16] {
+    let mut bytes: [u8; 16] = random();
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    bytes
+}
+
+/// A snapshot of the state of [`GlobalRng`].
+#[cfg_attr(docsrs, doc(cfg(madsim)))]
+#[derive(Debug, Clone)]
+pub struct RngState(InnerRng);
+
 /// Random log for determinism check.
 #[cfg_attr(docsrs, doc(cfg(madsim)))]
 #[derive(Debug, PartialEq, Eq)]
@@ -262,8 +422,134 @@ unsafe extern "C" fn getentropy(buf: *mut u8, buflen: usize) -> i32 {
 
 #[cfg(test)]
 mod tests {
+    use super::*;
     use crate::runtime::Runtime;
-    use std::collections::{BTreeSet, HashMap};
+    use std::collections::{BTreeSet, HashMap, HashSet};
+
+    #[test]
+    fn deterministic_thread_rng() {
+        let run = |seed: u64| {
+            let runtime = Runtime::with_seed_and_config(seed, crate::Config::default());
+            runtime.block_on(async {
+                let mut rng = thread_rng();
+                let mut bytes = [0u8; 8];
+                rng.fill_bytes(&mut bytes);
+                (rng.gen_range(0..100), rng.next_u64(), bytes)
+            })
+        };
+        let mut values = HashSet::new();
+        for seed in 0..10 {
+            let value = run(seed);
+            assert_eq!(value, run(seed));
+            assert!((0..100).contains(&value.0));
+            values.insert(value);
+        }
+        assert_eq!(values.len(), 10);
+    }
+
+    #[test]
+    fn shared_thread_rng() {
+        let run = |split: bool| {
+            let runtime = Runtime::with_seed_and_config(1, crate::Config::default());
+            runtime.block_on(async {
+                let (mut a, mut b) = (thread_rng(), thread_rng());
+                if split {
+                    (a.next_u64(), b.next_u64())
+                } else {
+                    (a.next_u64(), a.next_u64())
+                }
+            })
+        };
+        // every handle draws from the same stream
+        assert_eq!(run(true), run(false));
+    }
+
+    #[test]
+    fn snapshot_restore() {
+        let runtime = Runtime::new();
+        runtime.block_on(async {
+            let rng = thread_rng();
+            let state = rng.snapshot();
+            let seq1 = (0..10).map(|_| random::<u64>()).collect::<Vec<_>>();
+            rng.restore(state.clone());
+            let seq2 = (0..10).map(|_| random::<u64>()).collect::<Vec<_>>();
+            assert_eq!(seq1, seq2);
+        });
+    }
+
+    #[test]
+    fn deterministic_uuid() {
+        let run = |seed: u64| {
+            let runtime = Runtime::with_seed_and_config(seed, crate::Config::default());
+            runtime.block_on(async { (0..5).map(|_| uuid_v4()).collect::<Vec<_>>() })
+        };
+        let mut uuids = HashSet::new();
+        for seed in 0..10 {
+            let seq = run(seed);
+            assert_eq!(seq, run(seed));
+            for uuid in &seq {
+                assert_eq!(uuid[6] >> 4, 4);
+                assert_eq!(uuid[8] >> 6, 0b10);
+            }
+            uuids.extend(seq);
+        }
+        assert_eq!(uuids.len(), 50);
+    }
+
+    #[test]
+    fn deterministic_shuffle_choose() {
+        let run = |seed: u64| {
+            let runtime = Runtime::with_seed_and_config(seed, crate::Config::default());
+            runtime.block_on(async {
+                let mut v = (0..10).collect::<Vec<_>>();
+                shuffle(&mut v);
+                let c = *choose(&v).unwrap();
+                assert_eq!(choose::<i32>(&[]), None);
+                (v, c)
+            })
+        };
+        let mut shuffled = HashSet::new();
+        let mut chosen = HashSet::new();
+        for seed in 0..100 {
+            let (v, c) = run(seed);
+            assert_eq!((v.clone(), c), run(seed));
+            let mut sorted = v.clone();
+            sorted.sort();
+            assert_eq!(sorted, (0..10).collect::<Vec<_>>());
+            shuffled.insert(v);
+            chosen.insert(c);
+        }
+        assert_eq!(shuffled.len(), 100);
+        // every element can be chosen
+        assert_eq!(chosen, (0..10).collect());
+    }
+
+    #[test]
+    fn rng_algorithm() {
+        let run = |seed: u64, algorithm: RngAlgorithm| {
+            let config = crate::Config {
+                rng_algorithm: algorithm,
+                ..Default::default()
+            };
+            let runtime = Runtime::with_seed_and_config(seed, config);
+            runtime.block_on(async { (0..4).map(|_| random::<u64>()).collect::<Vec<_>>() })
+        };
+        for algorithm in [
+            RngAlgorithm::SmallRng,
+            RngAlgorithm::ChaCha8,
+            RngAlgorithm::ChaCha12,
+        ] {
+            assert_eq!(run(1, algorithm), run(1, algorithm));
+        }
+        assert_ne!(
+            run(1, RngAlgorithm::SmallRng),
+            run(1, RngAlgorithm::ChaCha8)
+        );
+        assert_ne!(
+            run(1, RngAlgorithm::ChaCha8),
+            run(1, RngAlgorithm::ChaCha12)
+        );
+    }
 
     #[test]
     #[cfg_attr(target_os = "linux", ignore)]
@@ -0,0 +1,110 @@
+use tracing::{level_filters::LevelFilter, subscriber::Interest, Metadata, Subscriber};
+use tracing_subscriber::layer::{Context, Layer};
+
+/// A tracing layer that filters events and spans by the level of the current node.
+///
+/// Inside a task, the level set by [`Handle::set_node_log_level`] for its node is used.
+/// Otherwise, the default level is used. This allows turning up the verbosity of a single
+/// misbehaving node without drowning in the logs of the whole cluster.
+///
+/// The layer disables filtered events and spans for the whole subscriber,
+/// so it should be the only level filter in the subscriber.
+///
+/// [`Handle::set_node_log_level`]: super::Handle::set_node_log_level
+///
+/// # Example
+///
+/// ```
+/// use madsim::runtime::{Handle, NodeLogFilter, Runtime};
+/// use tracing::level_filters::LevelFilter;
+/// use tracing_subscriber::layer::SubscriberExt;
+///
+/// let subscriber = tracing_subscriber::registry()
+///     .with(tracing_subscriber::fmt::layer())
+///     .with(NodeLogFilter::new(LevelFilter::WARN));
+/// tracing::subscriber::with_default(subscriber, || {
+///     let rt = Runtime::new();
+///     let node = rt.create_node().build();
+///     rt.handle().set_node_log_level(node.id(), LevelFilter::DEBUG);
+///     rt.block_on(node.spawn(async {
+///         tracing::debug!("only debug logs on this node are printed");
+///     }))
+///     .unwrap();
+/// });
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct NodeLogFilter {
+    default: LevelFilter,
+}
+
+impl NodeLogFilter {
+    /// Creates a filter with the level used outside nodes with a level set.
+    pub fn new(default: LevelFilter) -> Self {
+        NodeLogFilter { default }
+    }
+}
+
+impl<S: Subscriber> Layer<S> for NodeLogFilter {
+    fn register_callsite(&self, _metadata: &'static Metadata<'static>) -> Interest {
+        // the level depends on the current node, so it can not be cached
+        Interest::sometimes()
+    }
+
+    fn enabled(&self, metadata: &Metadata<'_>, _ctx: Context<'_, S>) -> bool {
+        let level = crate::context::try_current_task()
+            .and_then(|task| task.node.log_level())
+            .unwrap_or(self.default);
+        level >= *metadata.level()
+    }
+
+    fn max_level_hint(&self) -> Option<LevelFilter> {
+        // any node may have a higher level
+        Some(LevelFilter::TRACE)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runtime::Runtime;
+    use std::sync::{Arc, Mutex};
+    use tracing_subscriber::layer::SubscriberExt;
+
+    /// Records the levels of events.
+    struct Record(Arc<Mutex<Vec<tracing::Level>>>);
+
+    impl<S: Subscriber> Layer<S> for Record {
+        fn on_event(&self, event: &tracing::Event<'_>, _ctx: Context<'_, S>) {
+            self.0.lock().unwrap().push(*event.metadata().level());
+        }
+    }
+
+    #[test]
+    fn node_log_level() {
+        let levels = Arc::new(Mutex::new(vec![]));
+        let subscriber = tracing_subscriber::registry()
+            .with(Record(levels.clone()))
+            .with(NodeLogFilter::new(LevelFilter::WARN));
+        tracing::subscriber::with_default(subscriber, || {
+            let rt = Runtime::new();
+            let node1 = rt.create_node().build();
+            let node2 = rt.create_node().build();
+            rt.handle()
+                .set_node_log_level(node1.id(), LevelFilter::DEBUG);
+            rt.handle().restart(node1.id());
+            let node1 = rt.handle().get_node(node1.id()).unwrap();
+            let log = || async {
+                tracing::debug!("debug");
+                tracing::warn!("warn");
+            };
+            rt.block_on(node1.spawn(log())).unwrap();
+            rt.block_on(node2.spawn(log())).unwrap();
+        });
+        use tracing::Level;
+        // the level of node 1 is kept after restart
+        assert_eq!(
+            *levels.lock().unwrap(),
+            [Level::DEBUG, Level::WARN, Level::WARN]
+        );
+    }
+}
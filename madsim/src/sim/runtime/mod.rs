@@ -8,14 +8,17 @@ use std::{
     collections::HashMap,
     future::Future,
     net::IpAddr,
+    rc::Rc,
     sync::Arc,
-    time::Duration,
+    time::{Duration, Instant},
 };
 
 mod builder;
 pub(crate) mod context;
+mod log;
 
 pub use self::builder::Builder;
+pub use self::log::NodeLogFilter;
 
 /// The madsim runtime.
 ///
@@ -30,7 +33,7 @@ pub use self::builder::Builder;
 /// [file system]: crate::fs
 pub struct Runtime {
     rand: rand::GlobalRng,
-    task: task::Executor,
+    task: Rc<task::Executor>,
     handle: Handle,
 }
 
@@ -48,21 +51,37 @@ impl Runtime {
 
     /// Create a new runtime instance with given seed and config.
     pub fn with_seed_and_config(seed: u64, config: Config) -> Self {
-        let rand = rand::GlobalRng::new_with_seed(seed);
-        let task = task::Executor::new(rand.clone());
+        let rand = rand::GlobalRng::new_with_seed(seed, config.rng_algorithm);
+        let task = task::Executor::new(rand.clone(), config.task.clone());
         let handle = Handle {
             rand: rand.clone(),
             time: task.time_handle().clone(),
             task: task.handle().clone(),
             sims: Default::default(),
             config,
+            wait_graph: Default::default(),
         };
+        if handle.config.report_seed_on_panic {
+            install_panic_hook();
+        }
         let rt = Runtime { rand, task, handle };
         rt.add_simulator::<fs::FsSim>();
         rt.add_simulator::<net::NetSim>();
         rt
     }
 
+    /// Returns the random seed used to initialize the runtime.
+    ///
+    /// ```
+    /// use madsim::{Config, runtime::Runtime};
+    ///
+    /// let rt = Runtime::with_seed_and_config(2333, Config::default());
+    /// assert_eq!(rt.seed(), 2333);
+    /// ```
+    pub fn seed(&self) -> u64 {
+        self.rand.seed()
+    }
+
     /// Register a simulator.
     pub fn add_simulator<S: plugin::Simulator>(&self) {
         let mut sims = self.handle.sims.lock();
@@ -123,6 +142,147 @@ impl Runtime {
         self.task.block_on(future)
     }
 
+    /// Run a future to completion on the runtime within a simulated time budget.
+    ///
+    /// The `limit` applies only to this call and overrides the global
+    /// [time limit](Runtime::set_time_limit). Unlike the global limit, exceeding
+    /// it returns [`Elapsed`](crate::time::error::Elapsed) instead of panicking.
+    /// In that case the future is dropped before this function returns.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use madsim::{runtime::Runtime, time::{sleep, Duration}};
+    ///
+    /// let rt = Runtime::new();
+    /// let f = async { sleep(Duration::from_secs(1)).await };
+    /// assert!(rt.block_on_timeout(f, Duration::from_secs(2)).is_ok());
+    ///
+    /// let f = async { sleep(Duration::from_secs(2)).await };
+    /// assert!(rt.block_on_timeout(f, Duration::from_secs(1)).is_err());
+    /// ```
+    pub fn block_on_timeout<F: Future>(
+        &self,
+        future: F,
+        limit: Duration,
+    ) -> Result<F::Output, crate::time::error::Elapsed> {
+        let _guard = crate::context::enter(self.handle.clone());
+        self.task.block_on_timeout(future, limit)
+    }
+
+    /// Run a future on the runtime for at most `duration` of simulated time.
+    ///
+    /// Returns `None` if the duration elapses before the future completes.
+    /// In that case the future is dropped before this function returns.
+    /// The global [time limit](Runtime::set_time_limit) is ignored during this call.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use madsim::{runtime::Runtime, time::{sleep, Duration}};
+    ///
+    /// let rt = Runtime::new();
+    /// let chaos = async {
+    ///     loop {
+    ///         sleep(Duration::from_secs(1)).await;
+    ///     }
+    /// };
+    /// assert_eq!(rt.run_for(Duration::from_secs(600), chaos), None);
+    /// assert_eq!(rt.run_for(Duration::from_secs(1), async { 1 }), Some(1));
+    /// ```
+    pub fn run_for<F: Future>(&self, duration: Duration, future: F) -> Option<F::Output> {
+        let _guard = crate::context::enter(self.handle.clone());
+        self.task.block_on_timeout(future, duration).ok()
+    }
+
+    /// Returns the amount of simulated time elapsed since the runtime was created.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use madsim::{runtime::Runtime, time::{sleep, Duration}};
+    ///
+    /// let rt = Runtime::new();
+    /// rt.block_on(async {
+    ///     sleep(Duration::from_secs(1)).await;
+    /// });
+    /// assert!(rt.elapsed() >= Duration::from_secs(1));
+    /// ```
+    pub fn elapsed(&self) -> Duration {
+        self.handle.elapsed()
+    }
+
+    /// Run exactly one task from the ready queue.
+    ///
+    /// Returns `false` if there is no ready task. Time will not advance to the next timer event.
+    ///
+    /// This is useful for inspecting the state of the system between polls.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use madsim::runtime::Runtime;
+    ///
+    /// let rt = Runtime::new();
+    /// assert!(!rt.step());
+    /// rt.create_node().build().spawn(async {});
+    /// assert!(rt.step());
+    /// assert!(!rt.step());
+    /// ```
+    pub fn step(&self) -> bool {
+        let _guard = crate::context::enter(self.handle.clone());
+        self.task.step()
+    }
+
+    /// Check that no task is left unfinished.
+    ///
+    /// Returns an error listing the tasks still in the ready queue and the nodes
+    /// with alive tasks. This helps to find tasks that never completed because
+    /// the test ended early.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use madsim::{runtime::Runtime, time::{sleep, Duration}};
+    ///
+    /// let rt = Runtime::new();
+    /// rt.block_on(async {
+    ///     madsim::task::spawn(sleep(Duration::from_secs(1)));
+    /// });
+    /// assert!(rt.assert_no_pending_tasks().is_err());
+    /// ```
+    pub fn assert_no_pending_tasks(&self) -> Result<(), task::PendingTasks> {
+        self.task.check_no_pending_tasks()
+    }
+
+    /// Enable or disable advancing time automatically. Enabled by default.
+    ///
+    /// When disabled, the clock stands still while tasks are being polled,
+    /// and does not jump to the next timer when all tasks are blocked.
+    /// Time only moves forward when [`TimeHandle::advance`](time::TimeHandle::advance)
+    /// is called. If all tasks are blocked, the runtime panics.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use madsim::{runtime::Runtime, time::{Duration, Instant, TimeHandle}};
+    ///
+    /// let rt = Runtime::new();
+    /// rt.set_auto_advance(false);
+    ///
+    /// rt.block_on(async {
+    ///     let t0 = Instant::now();
+    ///     madsim::task::yield_now().await;
+    ///     assert_eq!(t0.elapsed(), Duration::ZERO);
+    ///
+    ///     TimeHandle::current().advance(Duration::from_secs(1));
+    ///     assert_eq!(t0.elapsed(), Duration::from_secs(1));
+    /// });
+    /// ```
+    pub fn set_auto_advance(&self, enabled: bool) {
+        self.handle.time.set_auto_advance(enabled);
+    }
+
     /// Set a time limit of the execution.
     ///
     /// The runtime will panic when time limit exceeded.
@@ -143,6 +303,85 @@ impl Runtime {
         self.task.set_time_limit(limit);
     }
 
+    /// Set a hook called before each poll of a task.
+    ///
+    /// The hook does not consume any randomness, so it won't affect the execution.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use madsim::runtime::Runtime;
+    /// use std::sync::{Arc, Mutex};
+    ///
+    /// let mut rt = Runtime::new();
+    /// let trace = Arc::new(Mutex::new(vec![]));
+    /// let trace0 = trace.clone();
+    /// rt.set_task_poll_hook(move |event| trace0.lock().unwrap().push(event.task));
+    ///
+    /// rt.block_on(async {});
+    /// assert_eq!(trace.lock().unwrap().len(), 1);
+    /// ```
+    pub fn set_task_poll_hook(&mut self, hook: impl Fn(task::TaskPollEvent) + 'static) {
+        self.task.set_poll_hook(hook);
+    }
+
+    /// Set the distribution of the time spent on each poll of a task.
+    ///
+    /// By default, the time is drawn uniformly from [`task::Config::poll_duration`].
+    /// The function is called with the global random number generator, so the execution
+    /// remains deterministic. See [`task::uniform_latency`] and [`task::lognormal_latency`]
+    /// for the built-in distributions.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use madsim::{runtime::Runtime, task, time::Instant};
+    /// use std::time::Duration;
+    ///
+    /// let mut rt = Runtime::new();
+    /// rt.set_poll_latency(task::lognormal_latency(Duration::from_micros(1), 1.0));
+    ///
+    /// rt.block_on(async {
+    ///     let t0 = Instant::now();
+    ///     task::yield_now().await;
+    ///     assert!(t0.elapsed() > Duration::ZERO);
+    /// });
+    /// ```
+    pub fn set_poll_latency(
+        &mut self,
+        latency: impl Fn(&mut dyn rand::RngCore) -> Duration + 'static,
+    ) {
+        self.task.set_poll_latency(latency);
+    }
+
+    /// Set a hook called on each lifecycle transition of a node.
+    ///
+    /// The hook is called in a deterministic order and does not consume any randomness,
+    /// so it won't affect the execution.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use madsim::runtime::Runtime;
+    /// use madsim::task::NodeEventKind;
+    /// use std::sync::{Arc, Mutex};
+    ///
+    /// let rt = Runtime::new();
+    /// let events = Arc::new(Mutex::new(vec![]));
+    /// let events0 = events.clone();
+    /// rt.set_node_event_hook(move |event| events0.lock().unwrap().push(event.kind));
+    ///
+    /// let node = rt.create_node().build();
+    /// rt.handle().kill(node.id());
+    /// assert_eq!(
+    ///     *events.lock().unwrap(),
+    ///     [NodeEventKind::Created, NodeEventKind::Killed]
+    /// );
+    /// ```
+    pub fn set_node_event_hook(&self, hook: impl Fn(task::NodeEvent) + Send + Sync + 'static) {
+        self.task.handle().set_node_event_hook(hook);
+    }
+
     /// Check determinism of the future.
     ///
     /// # Example
@@ -189,6 +428,27 @@ impl Runtime {
     }
 }
 
+/// Install a panic hook that reports the seed and the simulated time of the current
+/// runtime if [`Config::report_seed_on_panic`] is set.
+///
+/// The previous hook is still called afterwards. The hook is only installed once.
+fn install_panic_hook() {
+    static ONCE: std::sync::Once = std::sync::Once::new();
+    ONCE.call_once(|| {
+        let prev = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            crate::context::try_current(|h| {
+                if h.config.report_seed_on_panic {
+                    let seed = h.seed();
+                    let time = h.time.elapsed();
+                    tracing::error!(seed, ?time, "panicked in simulation");
+                }
+            });
+            prev(info);
+        }));
+    });
+}
+
 fn panic_with_info(seed: u64, hash: u64, payload: Box<dyn Any + Send>) -> ! {
     eprintln!(
         "note: run with `MADSIM_TEST_SEED={}` environment variable to reproduce this error",
@@ -206,6 +466,8 @@ pub struct Handle {
     pub(crate) task: task::TaskHandle,
     pub(crate) sims: Arc<Mutex<HashMap<TypeId, Arc<dyn plugin::Simulator>>>>,
     pub(crate) config: Config,
+    /// The holders of and waiters for locks, used to detect deadlocks.
+    pub(crate) wait_graph: Arc<Mutex<crate::sync::WaitGraph>>,
 }
 
 impl Handle {
@@ -222,6 +484,31 @@ impl Handle {
         context::current(|h| h.clone())
     }
 
+    /// Run a future to completion on the runtime of this handle.
+    ///
+    /// This is the same as [`Runtime::block_on`], but does not require the `Runtime`.
+    ///
+    /// # Panics
+    ///
+    /// This function is not reentrant. It must be called from the thread that created the
+    /// runtime, while the runtime is alive and is not running. It panics if called from
+    /// within a task, including the future passed to another `block_on`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use madsim::runtime::Runtime;
+    ///
+    /// let rt = Runtime::new();
+    /// let handle = rt.handle().clone();
+    /// let output = handle.block_on(async { 1 });
+    /// assert_eq!(output, 1);
+    /// ```
+    pub fn block_on<F: Future>(&self, future: F) -> F::Output {
+        let _guard = context::enter(self.clone());
+        self.task.block_on(future)
+    }
+
     /// Returns the random seed of the current runtime.
     ///
     /// ```
@@ -234,12 +521,42 @@ impl Handle {
         self.rand.seed()
     }
 
+    /// Returns the amount of simulated time elapsed since the runtime was created.
+    pub fn elapsed(&self) -> Duration {
+        self.time.elapsed()
+    }
+
+    /// Take a snapshot of the state of the global random number generator.
+    pub fn rng_snapshot(&self) -> rand::RngState {
+        self.rand.snapshot()
+    }
+
+    /// Restore the state of the global random number generator from a snapshot.
+    ///
+    /// This allows exploring different executions from the same point.
+    pub fn restore_rng(&self, state: rand::RngState) {
+        self.rand.restore(state);
+    }
+
     /// Kill a node.
     ///
-    /// - All tasks spawned on this node will be killed immediately.
+    /// - All tasks spawned on this node will be killed immediately. Their futures are dropped
+    ///   before this function returns, except the current task if it is on this node, which
+    ///   is dropped once it yields.
     /// - All data that has not been flushed to the disk will be lost.
     pub fn kill(&self, id: NodeId) {
         self.task.kill(id);
+        self.reset_node(id);
+    }
+
+    /// Kill a node because it exceeded its memory limit.
+    pub(crate) fn oom_kill(&self, id: NodeId) {
+        self.task.oom_kill(id);
+        self.reset_node(id);
+    }
+
+    /// Reset the node in all simulators.
+    fn reset_node(&self, id: NodeId) {
         let sims = self.sims.lock();
         let values = sims.values();
         for sim in values {
@@ -247,14 +564,157 @@ impl Handle {
         }
     }
 
+    /// Kill a node at the given simulated time.
+    ///
+    /// This is a no-op if the node does not exist at that time.
+    pub fn kill_at(&self, id: NodeId, deadline: Instant) {
+        // timers fire in the context of the runtime
+        self.time.add_global_timer_at(deadline, move || {
+            context::current(|handle| {
+                if handle.task.get_node(id).is_some() {
+                    handle.kill(id);
+                }
+            })
+        });
+    }
+
+    /// Gracefully shut down a node.
+    ///
+    /// All tasks on the node will observe [`is_node_shutting_down`] returns `true`.
+    /// The node will be killed once all of its tasks finish, or after `timeout` elapsed.
+    ///
+    /// This models the SIGTERM-then-SIGKILL procedure of real services.
+    ///
+    /// [`is_node_shutting_down`]: crate::task::is_node_shutting_down
+    pub async fn shutdown(&self, id: NodeId, timeout: Duration) {
+        let _ = self.time.timeout(timeout, self.task.shutdown(id)).await;
+        self.kill(id);
+    }
+
     /// Restart a node。
     pub fn restart(&self, id: NodeId) {
         self.task.restart(id);
-        let sims = self.sims.lock();
-        let values = sims.values();
-        for sim in values {
-            sim.reset_node(id);
-        }
+        self.reset_node(id);
+    }
+
+    /// Kill a node now and restart it after `delay` in simulated time.
+    ///
+    /// This models the backoff of a process supervisor. The node stays dead during the delay,
+    /// and is not restarted again if it has been restarted by others in the meantime,
+    /// even if it has crashed again since then.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use madsim::{runtime::{Handle, Runtime}, time::{sleep, Duration}};
+    ///
+    /// let rt = Runtime::new();
+    /// let node = rt.create_node().init(|| async {}).build();
+    /// rt.block_on(async move {
+    ///     let handle = Handle::current();
+    ///     handle.restart_after(node.id(), Duration::from_secs(5));
+    ///     assert!(!handle.is_alive(node.id()));
+    ///     sleep(Duration::from_secs(6)).await;
+    ///     assert!(handle.is_alive(node.id()));
+    ///     assert_eq!(handle.crash_count(node.id()), 1);
+    /// });
+    /// ```
+    pub fn restart_after(&self, id: NodeId, delay: Duration) {
+        self.kill(id);
+        // a crash in the meantime belongs to a later incarnation of the node
+        let crashes = self.task.crash_count(id);
+        // timers fire in the context of the runtime
+        self.time.add_timer(delay, move || {
+            context::current(|handle| {
+                if !handle.task.is_alive(id) && handle.task.crash_count(id) == crashes {
+                    handle.task.start(id);
+                }
+            })
+        });
+    }
+
+    /// Set the log level of a node, which is kept after restart.
+    ///
+    /// This only takes effect with a [`NodeLogFilter`] in the tracing subscriber.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if the node does not exist.
+    pub fn set_node_log_level(&self, id: NodeId, level: tracing::level_filters::LevelFilter) {
+        self.task.set_node_log_level(id, level);
+    }
+
+    /// Takes the scheduling decisions recorded so far with
+    /// [`task::Config::record_trace`](crate::task::Config::record_trace).
+    ///
+    /// Set the trace as [`task::Config::replay_trace`](crate::task::Config::replay_trace)
+    /// of a new runtime to reproduce the schedule.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use madsim::{runtime::Runtime, task, Config};
+    ///
+    /// let run = |config: Config| {
+    ///     let rt = Runtime::with_seed_and_config(1, config);
+    ///     rt.block_on(async {
+    ///         let tasks = (0..3).map(|i| task::spawn(async move { i }));
+    ///         task::join_all(tasks).await;
+    ///     });
+    ///     rt.handle().take_trace()
+    /// };
+    /// let mut config = Config::default();
+    /// config.task.record_trace = true;
+    /// let trace = run(config.clone());
+    ///
+    /// config.task.replay_trace = Some(trace.clone());
+    /// assert_eq!(run(config), trace);
+    /// ```
+    pub fn take_trace(&self) -> Vec<task::ScheduleEntry> {
+        self.task.take_trace()
+    }
+
+    /// Returns the number of times the node has been killed while running.
+    ///
+    /// This includes kills by [`kill`](Self::kill), [`restart`](Self::restart) and the
+    /// memory limit. Killing a node that is already dead does not count.
+    /// Combined with [`elapsed`](crate::time::TimeHandle::elapsed), this can be used to
+    /// detect crash loops.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if the node does not exist.
+    pub fn crash_count(&self, id: NodeId) -> u64 {
+        self.task.crash_count(id)
+    }
+
+    /// Restart a node with a new initial task.
+    ///
+    /// The new initial task replaces the old one in subsequent restarts.
+    /// This models deploying a new version of the node.
+    pub fn restart_with<F>(&self, id: NodeId, init: impl Fn() -> F + 'static)
+    where
+        F: Future + 'static,
+    {
+        self.task.restart_with(
+            id,
+            Arc::new(move |handle| {
+                handle.spawn_init(init()).detach();
+            }),
+        );
+        self.reset_node(id);
+    }
+
+    /// Make a task panic at its next poll.
+    ///
+    /// The panic is raised inside the task as if its future panicked, so it goes through
+    /// the panic hook, is reported to the handler of [`spawn_with_panic_handler`], and
+    /// otherwise unwinds the simulation like any other panic of a task.
+    /// Does nothing if the task has finished.
+    ///
+    /// [`spawn_with_panic_handler`]: NodeHandle::spawn_with_panic_handler
+    pub fn inject_panic(&self, id: task::Id) {
+        self.task.inject_panic(id);
     }
 
     /// Pause the execution of a node.
@@ -262,11 +722,136 @@ impl Handle {
         self.task.pause(id);
     }
 
+    /// Set the number of CPU cores of a node.
+    ///
+    /// This models resizing the machine of a node. The new value is kept after restart.
+    pub fn set_cores(&self, id: NodeId, cores: usize) {
+        self.task.set_cores(id, cores);
+    }
+
     /// Resume the execution of a node.
     pub fn resume(&self, id: NodeId) {
         self.task.resume(id);
     }
 
+    /// Returns `true` if the node is paused.
+    pub fn is_paused(&self, id: NodeId) -> bool {
+        self.task.is_paused(id)
+    }
+
+    /// Returns `true` if the node exists and has not been killed since the last restart.
+    pub fn is_alive(&self, id: NodeId) -> bool {
+        self.task.is_alive(id)
+    }
+
+    /// Create an empty group of nodes, such as an availability zone or a rack.
+    ///
+    /// Groups model correlated failures: all nodes of a group can be killed or
+    /// paused at once.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use madsim::runtime::Runtime;
+    ///
+    /// let rt = Runtime::new();
+    /// let handle = rt.handle();
+    /// let az = handle.create_group("az-1");
+    /// let node1 = rt.create_node().build();
+    /// let node2 = rt.create_node().build();
+    /// handle.add_node_to_group(az, node1.id());
+    /// handle.add_node_to_group(az, node2.id());
+    /// assert_eq!(handle.group_nodes(az), [node1.id(), node2.id()]);
+    ///
+    /// handle.kill_group(az);
+    /// assert!(!handle.is_alive(node1.id()));
+    /// assert!(!handle.is_alive(node2.id()));
+    /// ```
+    pub fn create_group(&self, name: &str) -> task::GroupId {
+        self.task.create_group(name)
+    }
+
+    /// Add a node to the group. A node can be in multiple groups.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if the group or the node does not exist.
+    pub fn add_node_to_group(&self, group: task::GroupId, node: NodeId) {
+        self.task.add_node_to_group(group, node);
+    }
+
+    /// Returns the name of the group, or `None` if the group does not exist.
+    pub fn group_name(&self, group: task::GroupId) -> Option<String> {
+        self.task.group_name(group)
+    }
+
+    /// Returns the nodes of the group in ascending order of IDs.
+    pub fn group_nodes(&self, group: task::GroupId) -> Vec<NodeId> {
+        self.task.group_nodes(group)
+    }
+
+    /// Returns the groups containing the node.
+    pub fn node_groups(&self, node: NodeId) -> Vec<task::GroupId> {
+        self.task.node_groups(node)
+    }
+
+    /// Returns a stream of subsequent lifecycle transitions of nodes.
+    ///
+    /// Each call returns an independent stream that receives all events emitted after it is
+    /// created, in the same order as the [node event hook](Runtime::set_node_event_hook).
+    /// Events are buffered without blocking the emitter: once [`task::NODE_EVENTS_CAPACITY`]
+    /// events are buffered, the oldest ones are dropped.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use futures_util::StreamExt;
+    /// use madsim::{runtime::{Handle, Runtime}, task::NodeEventKind};
+    ///
+    /// let rt = Runtime::new();
+    /// rt.block_on(async {
+    ///     let handle = Handle::current();
+    ///     let mut events = handle.node_events();
+    ///     let node = handle.create_node().build();
+    ///     handle.restart(node.id());
+    ///     for kind in [NodeEventKind::Created, NodeEventKind::Killed, NodeEventKind::Restarted] {
+    ///         let event = events.next().await.unwrap();
+    ///         assert_eq!((event.node, event.kind), (node.id(), kind));
+    ///     }
+    /// });
+    /// ```
+    pub fn node_events(&self) -> task::NodeEvents {
+        self.task.node_events()
+    }
+
+    /// Kill all nodes of the group. See [`kill`](Self::kill).
+    pub fn kill_group(&self, group: task::GroupId) {
+        for id in self.group_nodes(group) {
+            self.kill(id);
+        }
+    }
+
+    /// Restart all nodes of the group. See [`restart`](Self::restart).
+    pub fn restart_group(&self, group: task::GroupId) {
+        for id in self.group_nodes(group) {
+            self.restart(id);
+        }
+    }
+
+    /// Pause all nodes of the group. See [`pause`](Self::pause).
+    pub fn pause_group(&self, group: task::GroupId) {
+        for id in self.group_nodes(group) {
+            self.pause(id);
+        }
+    }
+
+    /// Resume all nodes of the group. See [`resume`](Self::resume).
+    pub fn resume_group(&self, group: task::GroupId) {
+        for id in self.group_nodes(group) {
+            self.resume(id);
+        }
+    }
+
     /// Create a node which will be bound to the specified address.
     pub fn create_node(&self) -> NodeBuilder<'_> {
         NodeBuilder::new(self)
@@ -276,6 +861,121 @@ impl Handle {
     pub fn get_node(&self, id: NodeId) -> Option<NodeHandle> {
         self.task.get_node(id).map(|task| NodeHandle { task })
     }
+
+    /// Returns the total number of alive tasks spawned on all nodes.
+    pub fn total_task_count(&self) -> usize {
+        self.task.total_task_count()
+    }
+
+    /// Spawn a future onto the node with the given ID.
+    ///
+    /// Returns `None` if the node does not exist or has been killed.
+    pub fn spawn_on<F>(&self, id: NodeId, future: F) -> Option<JoinHandle<F::Output>>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        self.task.spawn_on(id, future)
+    }
+
+    /// Returns the total number of tasks ever spawned on all nodes.
+    pub fn tasks_spawned_total(&self) -> u64 {
+        self.task.tasks_spawned_total()
+    }
+
+    /// Returns the number of tasks waiting in the ready queue to be polled.
+    ///
+    /// A task is counted once for each time it was woken up before being polled. Runnables of
+    /// killed nodes are counted until the executor discards them. This can be used to detect
+    /// a scheduling backlog, e.g. tasks that keep waking themselves up faster than they are
+    /// polled.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use madsim::{runtime::{Handle, Runtime}, task, time::{sleep, Duration}};
+    ///
+    /// Runtime::new().block_on(async {
+    ///     let handle = Handle::current();
+    ///     for _ in 0..3 {
+    ///         task::spawn(async {});
+    ///     }
+    ///     assert_eq!(handle.ready_task_count(), 3);
+    ///     sleep(Duration::from_secs(1)).await;
+    ///     assert_eq!(handle.ready_task_count(), 0);
+    /// });
+    /// ```
+    pub fn ready_task_count(&self) -> usize {
+        self.task.ready_task_count()
+    }
+
+    /// Returns the number of timer events fired so far.
+    ///
+    /// This can be used to detect tests registering a huge number of timers.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use madsim::{runtime::Handle, runtime::Runtime, time::{sleep, Duration}};
+    ///
+    /// Runtime::new().block_on(async {
+    ///     let handle = Handle::current();
+    ///     let count = handle.timer_event_count();
+    ///     for _ in 0..10 {
+    ///         sleep(Duration::from_secs(1)).await;
+    ///     }
+    ///     assert_eq!(handle.timer_event_count() - count, 10);
+    ///     assert!(handle.advance_count() >= 10);
+    /// });
+    /// ```
+    pub fn timer_event_count(&self) -> u64 {
+        self.time.timer_event_count()
+    }
+
+    /// Returns the number of times the runtime tried to advance the clock to the next
+    /// timer event because no task was ready.
+    pub fn advance_count(&self) -> u64 {
+        self.time.advance_count()
+    }
+
+    /// Returns the IDs of all created nodes in ascending order.
+    ///
+    /// The main node is not included. Killed nodes are still listed since they can be restarted.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use madsim::runtime::Runtime;
+    ///
+    /// let rt = Runtime::new();
+    /// let node1 = rt.create_node().build();
+    /// let node2 = rt.create_node().build();
+    /// rt.handle().kill(node1.id());
+    /// assert_eq!(rt.handle().list_nodes(), vec![node1.id(), node2.id()]);
+    /// ```
+    pub fn list_nodes(&self) -> Vec<NodeId> {
+        self.task.list_nodes()
+    }
+
+    /// Returns the IDs of the main node and all created nodes in ascending order.
+    ///
+    /// The main node has ID 0, so it always comes first.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use madsim::{runtime::Runtime, task::NodeId};
+    ///
+    /// let rt = Runtime::new();
+    /// let node = rt.create_node().build();
+    /// let nodes = rt.handle().list_nodes_with_main();
+    /// assert_eq!(nodes, vec![NodeId::from_raw(0), node.id()]);
+    /// ```
+    pub fn list_nodes_with_main(&self) -> Vec<NodeId> {
+        let mut ids = vec![NodeId::zero()];
+        ids.extend(self.task.list_nodes());
+        ids
+    }
 }
 
 /// Builds a node with custom configurations.
@@ -284,6 +984,10 @@ pub struct NodeBuilder<'a> {
     name: Option<String>,
     ip: Option<IpAddr>,
     cores: Option<usize>,
+    clock_skew: Option<Duration>,
+    paused: bool,
+    memory_limit: Option<usize>,
+    max_tasks: Option<usize>,
     init: Option<task::InitFn>,
 }
 
@@ -294,6 +998,10 @@ impl<'a> NodeBuilder<'a> {
             name: None,
             ip: None,
             cores: None,
+            clock_skew: None,
+            paused: false,
+            memory_limit: None,
+            max_tasks: None,
             init: None,
         }
     }
@@ -314,7 +1022,7 @@ impl<'a> NodeBuilder<'a> {
         F: Future + 'static,
     {
         self.init = Some(Arc::new(move |handle| {
-            handle.spawn_local(future());
+            handle.spawn_init(future()).detach();
         }));
         self
     }
@@ -334,12 +1042,58 @@ impl<'a> NodeBuilder<'a> {
         self
     }
 
+    /// Set the clock skew of the node.
+    ///
+    /// The clock observed by tasks on the node will be ahead of the global clock by `skew`.
+    /// This affects [`Instant::now`](std::time::Instant::now), [`SystemTime::now`](std::time::SystemTime::now)
+    /// and the deadline of [`sleep_until`](crate::time::sleep_until). Timers still fire at the global time.
+    pub fn clock_skew(mut self, skew: Duration) -> Self {
+        self.clock_skew = Some(skew);
+        self
+    }
+
+    /// Set whether the node is created paused.
+    ///
+    /// No task of a paused node runs, including the initial task, until it is
+    /// [resumed](Handle::resume). This allows releasing several nodes at the same instant.
+    pub fn paused(mut self, paused: bool) -> Self {
+        self.paused = paused;
+        self
+    }
+
+    /// Set the memory limit of the node in bytes.
+    ///
+    /// The node is killed once the memory recorded by [`record_alloc`](crate::task::record_alloc)
+    /// exceeds the limit. This models an OOM kill.
+    pub fn memory_limit(mut self, bytes: usize) -> Self {
+        self.memory_limit = Some(bytes);
+        self
+    }
+
+    /// Set the maximum number of alive tasks of the node.
+    ///
+    /// Spawning a task on the node beyond the limit panics with a message naming the node.
+    /// This turns a task leak into an immediate failure instead of a slow OOM.
+    pub fn max_tasks(mut self, max: usize) -> Self {
+        self.max_tasks = Some(max);
+        self
+    }
+
     /// Build a node.
     pub fn build(self) -> NodeHandle {
-        let task = self
-            .handle
-            .task
-            .create_node(self.name, self.init, self.cores);
+        let task = self.handle.task.create_node(
+            self.name,
+            self.init,
+            self.cores,
+            self.clock_skew,
+            self.paused,
+        );
+        if let Some(limit) = self.memory_limit {
+            self.handle.task.set_memory_limit(task.id(), limit);
+        }
+        if let Some(max) = self.max_tasks {
+            self.handle.task.set_max_tasks(task.id(), max);
+        }
         let sims = self.handle.sims.lock();
         let values = sims.values();
         for sim in values {
@@ -366,6 +1120,41 @@ impl NodeHandle {
         self.task.id()
     }
 
+    /// Returns the number of alive tasks spawned on this node.
+    pub fn task_count(&self) -> usize {
+        self.task.task_count()
+    }
+
+    /// Returns the IDs of alive tasks spawned on this node in ascending order.
+    pub fn alive_task_ids(&self) -> Vec<task::Id> {
+        self.task.alive_task_ids()
+    }
+
+    /// Returns the IDs and names of alive tasks spawned on this node in ascending order of IDs.
+    pub fn alive_tasks(&self) -> Vec<(task::Id, Option<String>)> {
+        self.task.alive_tasks()
+    }
+
+    /// Returns the maximum number of simultaneously alive tasks on this node.
+    ///
+    /// It is reset when the node is killed or restarted.
+    pub fn max_task_count(&self) -> usize {
+        self.task.max_task_count()
+    }
+
+    /// Returns the number of bytes recorded by [`record_alloc`](crate::task::record_alloc)
+    /// on this node.
+    pub fn memory_usage(&self) -> usize {
+        self.task.memory_usage()
+    }
+
+    /// Waits for the initial task of the node to yield for the first time or complete.
+    ///
+    /// Resolves immediately if the node has no initial task.
+    pub fn wait_ready(&self) -> impl Future<Output = ()> {
+        self.task.wait_ready()
+    }
+
     /// Spawn a future onto the runtime.
     pub fn spawn<F>(&self, future: F) -> JoinHandle<F::Output>
     where
@@ -374,6 +1163,32 @@ impl NodeHandle {
     {
         self.task.spawn(future)
     }
+
+    /// Spawn a future onto the runtime without a [`JoinHandle`].
+    ///
+    /// See [`TaskNodeHandle::spawn_detached`](task::TaskNodeHandle::spawn_detached).
+    pub fn spawn_detached<F>(&self, future: F)
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        self.task.spawn_detached(future);
+    }
+
+    /// Spawn a future onto the runtime, calling `on_panic` with its task ID if it panics.
+    ///
+    /// See [`TaskNodeHandle::spawn_with_panic_handler`](task::TaskNodeHandle::spawn_with_panic_handler).
+    pub fn spawn_with_panic_handler<F>(
+        &self,
+        future: F,
+        on_panic: impl Fn(task::Id) + Send + 'static,
+    ) -> JoinHandle<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        self.task.spawn_with_panic_handler(future, on_panic)
+    }
 }
 
 /// Initialize logger.
@@ -382,3 +1197,234 @@ pub fn init_logger() {
     static LOGGER_INIT: Once = Once::new();
     LOGGER_INIT.call_once(tracing_subscriber::fmt::init);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{Config, NodeId, Runtime};
+    use std::{io, sync::Arc, time::Duration};
+
+    #[test]
+    fn report_seed_on_panic() {
+        #[derive(Clone, Default)]
+        struct Buf(Arc<std::sync::Mutex<Vec<u8>>>);
+        impl io::Write for Buf {
+            fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+                self.0.lock().unwrap().write(buf)
+            }
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let buf = Buf::default();
+        let buf0 = buf.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .with_writer(move || buf0.clone())
+            .with_ansi(false)
+            .finish();
+        let config = Config {
+            report_seed_on_panic: true,
+            ..Default::default()
+        };
+        tracing::subscriber::with_default(subscriber, || {
+            // create spans with the subscriber
+            let rt = Runtime::with_seed_and_config(4213, config);
+            let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                rt.block_on(async {
+                    crate::time::sleep(Duration::from_secs(8)).await;
+                    panic!("boom");
+                })
+            }));
+            assert!(result.is_err());
+        });
+        let output = String::from_utf8(buf.0.lock().unwrap().clone()).unwrap();
+        assert!(output.contains("panicked in simulation"), "{output}");
+        assert!(output.contains("seed=4213"), "{output}");
+        assert!(output.contains("time=8."), "{output}");
+    }
+
+    #[test]
+    fn run_for_drops_future() {
+        struct Dropped(Arc<std::sync::atomic::AtomicBool>);
+        impl Drop for Dropped {
+            fn drop(&mut self) {
+                self.0.store(true, std::sync::atomic::Ordering::SeqCst);
+            }
+        }
+
+        let rt = Runtime::new();
+        let dropped = Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let guard = Dropped(dropped.clone());
+        let output = rt.run_for(Duration::from_secs(10), async move {
+            let _guard = guard;
+            crate::time::sleep(Duration::from_secs(20)).await;
+        });
+        assert_eq!(output, None);
+        assert!(dropped.load(std::sync::atomic::Ordering::SeqCst));
+        assert!(rt.elapsed() >= Duration::from_secs(10));
+        assert!(rt.elapsed() < Duration::from_secs(20));
+    }
+
+    #[test]
+    fn block_on_timeout_drops_borrowing_future() {
+        struct Guard<'a>(&'a std::cell::Cell<bool>);
+        impl Drop for Guard<'_> {
+            fn drop(&mut self) {
+                self.0.set(true);
+            }
+        }
+
+        let rt = Runtime::new();
+        let dropped = std::cell::Cell::new(false);
+        let guard = Guard(&dropped);
+        let result = rt.block_on_timeout(
+            async move {
+                let _guard = guard;
+                crate::time::sleep(Duration::from_secs(20)).await;
+            },
+            Duration::from_secs(10),
+        );
+        assert!(result.is_err());
+        // the future borrowing `dropped` must not outlive this call
+        assert!(dropped.get());
+        // the runtime can still be used afterwards
+        rt.block_on(async { crate::time::sleep(Duration::from_secs(20)).await });
+    }
+
+    #[test]
+    fn list_nodes() {
+        let rt = Runtime::new();
+        let handle = rt.handle();
+        assert_eq!(handle.list_nodes(), []);
+        assert_eq!(handle.list_nodes_with_main(), [NodeId::zero()]);
+        let nodes = (0..3)
+            .map(|_| rt.create_node().build().id())
+            .collect::<Vec<_>>();
+        handle.kill(nodes[1]);
+        assert_eq!(handle.list_nodes(), nodes);
+        let all = handle.list_nodes_with_main();
+        assert_eq!(all[0], NodeId::zero());
+        assert_eq!(all[1..], nodes);
+        // every listed node can be looked up
+        for id in all {
+            assert!(handle.get_node(id).is_some());
+        }
+    }
+
+    #[test]
+    fn block_on_timeout_leaves_no_timer() {
+        let rt = Runtime::new();
+        let output = rt.block_on_timeout(async { 1 }, Duration::from_secs(100));
+        assert_eq!(output.ok(), Some(1));
+        assert_eq!(rt.handle().timer_event_count(), 0);
+
+        // the deadlock is reported without jumping to the old deadline
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            rt.block_on(std::future::pending::<()>())
+        }));
+        let err = result.unwrap_err();
+        let msg = err.downcast_ref::<String>().unwrap();
+        assert!(msg.starts_with("no events"), "{msg}");
+        assert!(rt.elapsed() < Duration::from_secs(1));
+        assert_eq!(rt.handle().timer_event_count(), 0);
+    }
+
+    #[test]
+    fn groups() {
+        let rt = Runtime::new();
+        let handle = rt.handle();
+        let rack = handle.create_group("rack");
+        let az = handle.create_group("az");
+        let nodes = (0..3)
+            .map(|_| rt.create_node().build().id())
+            .collect::<Vec<_>>();
+        handle.add_node_to_group(rack, nodes[0]);
+        for &id in &nodes[..2] {
+            handle.add_node_to_group(az, id);
+        }
+        assert_eq!(handle.group_name(az).as_deref(), Some("az"));
+        assert_eq!(handle.node_groups(nodes[0]), [rack, az]);
+        assert_eq!(handle.node_groups(nodes[2]), []);
+
+        handle.pause_group(az);
+        assert!(handle.is_paused(nodes[0]) && handle.is_paused(nodes[1]));
+        assert!(!handle.is_paused(nodes[2]));
+        handle.resume_group(az);
+        assert!(!handle.is_paused(nodes[0]));
+
+        handle.kill_group(rack);
+        assert!(!handle.is_alive(nodes[0]));
+        assert!(handle.is_alive(nodes[1]));
+        handle.restart_group(rack);
+        assert!(handle.is_alive(nodes[0]));
+    }
+
+    #[test]
+    fn restart_after() {
+        use crate::time::sleep;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let rt = Runtime::new();
+        let starts = Arc::new(AtomicUsize::new(0));
+        let starts0 = starts.clone();
+        let node = rt
+            .create_node()
+            .init(move || {
+                starts0.fetch_add(1, Ordering::SeqCst);
+                async {}
+            })
+            .build();
+        let id = node.id();
+        let handle = rt.handle().clone();
+        rt.block_on(async move {
+            handle.restart_after(id, Duration::from_secs(5));
+            sleep(Duration::from_secs(4)).await;
+            assert!(!handle.is_alive(id));
+            assert_eq!(starts.load(Ordering::SeqCst), 1);
+            sleep(Duration::from_secs(2)).await;
+            assert!(handle.is_alive(id));
+            assert_eq!(starts.load(Ordering::SeqCst), 2);
+
+            // a node restarted in the meantime is not restarted again
+            handle.restart_after(id, Duration::from_secs(5));
+            handle.restart(id);
+            sleep(Duration::from_secs(6)).await;
+            assert_eq!(starts.load(Ordering::SeqCst), 3);
+
+            // nor is a node restarted and then killed by others
+            handle.restart_after(id, Duration::from_secs(5));
+            handle.restart(id);
+            sleep(Duration::from_secs(1)).await;
+            handle.kill(id);
+            sleep(Duration::from_secs(6)).await;
+            assert!(!handle.is_alive(id));
+            assert_eq!(starts.load(Ordering::SeqCst), 4);
+
+            // killing a dead node is not a crash
+            handle.kill(id);
+            handle.kill(id);
+            assert_eq!(handle.crash_count(id), 4);
+        });
+    }
+
+    #[test]
+    fn handle_block_on() {
+        let rt = Runtime::new();
+        let handle = rt.handle().clone();
+        handle.block_on(async { crate::time::sleep(Duration::from_secs(1)).await });
+        assert_eq!(rt.block_on(async { 1 }), 1);
+        assert!(rt.elapsed() >= Duration::from_secs(1));
+        drop(rt);
+        let block_on = std::panic::AssertUnwindSafe(|| handle.block_on(async {}));
+        let result = std::panic::catch_unwind(block_on);
+        assert!(result.is_err(), "the runtime has been dropped");
+    }
+
+    #[test]
+    #[should_panic(expected = "can not block on a future while the runtime is running")]
+    fn handle_block_on_reentrant() {
+        let rt = Runtime::new();
+        let handle = rt.handle().clone();
+        rt.block_on(async move { handle.block_on(async {}) });
+    }
+}
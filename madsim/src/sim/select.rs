@@ -0,0 +1,151 @@
+/// Waits on multiple concurrent branches, returning when the first branch completes.
+///
+/// This is similar to [`futures::select!`], but the branches are not tried in the order
+/// they are written. Each time, the first branch to try is chosen by the deterministic
+/// random number generator, and the others follow in order. So when several branches are
+/// ready at the same time, the one written first does not always win.
+///
+/// Each branch has the form `pattern = future => expression`, and branches are separated
+/// by commas. The same requirements as [`futures::select!`] apply to the futures.
+/// A `default => expression` branch runs if no future is ready, and a
+/// `complete => expression` branch runs once all futures have terminated.
+///
+/// [`futures::select!`]: https://docs.rs/futures/0.3/futures/macro.select.html
+///
+/// # Example
+///
+/// ```
+/// use madsim::runtime::Runtime;
+/// use madsim::export::futures::future::ready;
+///
+/// let rt = Runtime::new();
+/// let branch = rt.block_on(async {
+///     madsim::select! {
+///         a = ready(1) => a,
+///         b = ready(2) => b,
+///     }
+/// });
+/// assert!(branch == 1 || branch == 2);
+/// ```
+#[macro_export]
+#[cfg_attr(docsrs, doc(cfg(madsim)))]
+macro_rules! select {
+    // split the `complete` and `default` branches from the future branches
+    (@parse [$($fut:tt)*] [$($other:tt)*] complete => $b:expr $(, $($rest:tt)*)?) => {
+        $crate::select!(@parse [$($fut)*] [$($other)* complete => $b,] $($($rest)*)?)
+    };
+    (@parse [$($fut:tt)*] [$($other:tt)*] default => $b:expr $(, $($rest:tt)*)?) => {
+        $crate::select!(@parse [$($fut)*] [$($other)* default => $b,] $($($rest)*)?)
+    };
+    // a future given by name is borrowed rather than moved, like in `futures::select!`
+    (@parse [$($fut:tt)*] [$($other:tt)*] $p:pat = $f:ident => $b:expr $(, $($rest:tt)*)?) => {
+        $crate::select!(@parse [$($fut)* { $p = &mut $f => $b }] [$($other)*] $($($rest)*)?)
+    };
+    (@parse [$($fut:tt)*] [$($other:tt)*] $p:pat = $f:expr => $b:expr $(, $($rest:tt)*)?) => {
+        $crate::select!(@parse [$($fut)* { $p = $f => $b }] [$($other)*] $($($rest)*)?)
+    };
+    (@parse [] [$($other:tt)*]) => {
+        $crate::export::futures::select_biased! { $($other)* }
+    };
+    (@parse [$({ $p:pat = $f:expr => $b:expr })+] [$($other:tt)*]) => {{
+        let start = $crate::export::select_start(0 $(+ $crate::select!(@count $f))+);
+        $crate::select!(@rotate start, 0usize, [$($other)*], [], [$({ $p = $f => $b })+])
+    }};
+    // try all branches in order, starting from the `$start`th one
+    (@rotate $start:ident, $k:expr, $other:tt, [$($done:tt)*], [$head:tt $($tail:tt)*]) => {
+        if $start == $k {
+            $crate::select!(@biased $other $head $($tail)* $($done)*)
+        } else {
+            $crate::select!(@rotate $start, $k + 1, $other, [$($done)* $head], [$($tail)*])
+        }
+    };
+    (@rotate $start:ident, $k:expr, $other:tt, [$($done:tt)*], []) => {
+        unreachable!()
+    };
+    (@biased [$($other:tt)*] $({ $p:pat = $f:expr => $b:expr })*) => {
+        $crate::export::futures::select_biased! { $($p = $f => $b,)* $($other)* }
+    };
+    (@count $f:expr) => {
+        1usize
+    };
+
+    ($($tokens:tt)+) => {
+        $crate::select!(@parse [] [] $($tokens)+)
+    };
+}
+
+/// Returns the index of the first branch to try in [`select!`].
+pub fn select_start(branches: usize) -> usize {
+    use crate::rand::Rng;
+    crate::rand::thread_rng().gen_range(0..branches)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{runtime::Runtime, time};
+    use futures_util::future::{pending, ready, FutureExt};
+    use std::{collections::HashSet, time::Duration};
+
+    #[test]
+    fn select() {
+        let run = |seed: u64| {
+            let runtime = Runtime::with_seed_and_config(seed, crate::Config::default());
+            runtime.block_on(async {
+                crate::select! {
+                    a = ready(0) => a,
+                    b = ready(1) => b,
+                    c = ready(2) => c,
+                    _ = pending::<()>() => unreachable!(),
+                }
+            })
+        };
+        // deterministic for the same seed
+        assert_eq!(run(1), run(1));
+        // all ready branches are possible
+        let branches = (0..20).map(run).collect::<HashSet<_>>();
+        assert_eq!(branches, HashSet::from([0, 1, 2]));
+    }
+
+    #[test]
+    fn select_first_ready() {
+        let runtime = Runtime::new();
+        runtime.block_on(async {
+            let branch = crate::select! {
+                _ = time::sleep(Duration::from_secs(2)).fuse() => 0,
+                _ = time::sleep(Duration::from_secs(1)).fuse() => 1,
+            };
+            assert_eq!(branch, 1);
+        });
+    }
+
+    #[test]
+    fn select_default() {
+        let runtime = Runtime::new();
+        runtime.block_on(async {
+            let branch = crate::select! {
+                _ = time::sleep(Duration::from_secs(1)).fuse() => 0,
+                default => 1,
+                _ = time::sleep(Duration::from_secs(2)).fuse() => 2,
+            };
+            assert_eq!(branch, 1);
+        });
+    }
+
+    #[test]
+    fn select_complete() {
+        let runtime = Runtime::new();
+        runtime.block_on(async {
+            let mut a = ready(1).fuse();
+            let mut b = ready(2).fuse();
+            let mut sum = 0;
+            loop {
+                crate::select! {
+                    x = a => sum += x,
+                    complete => break,
+                    y = b => sum += y,
+                }
+            }
+            assert_eq!(sum, 3);
+        });
+    }
+}
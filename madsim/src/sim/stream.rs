@@ -0,0 +1,242 @@
+//! Deterministic combinators for streams.
+//!
+//! Their counterparts in [`futures::stream`] poll the constituent streams round-robin, so
+//! when several streams are ready, the merged items alternate between them in a fixed
+//! pattern. These poll the ready streams in a random order chosen by the deterministic
+//! random number generator instead, which explores other interleavings of the items.
+//!
+//! [`futures::stream`]: https://docs.rs/futures/0.3/futures/stream/index.html
+
+use futures_util::Stream;
+use rand::seq::SliceRandom;
+use std::{
+    fmt,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+/// Merges two streams into one, yielding items as they become available on either.
+///
+/// When both streams have an item ready, which one is yielded first is chosen by the
+/// deterministic random number generator. The merged stream ends when both streams end.
+///
+/// # Example
+///
+/// ```
+/// use madsim::{runtime::Runtime, stream};
+/// use madsim::export::futures::{stream::iter, StreamExt};
+///
+/// Runtime::new().block_on(async {
+///     let mut items = stream::select(iter([1, 2]), iter([3, 4])).collect::<Vec<_>>().await;
+///     items.sort();
+///     assert_eq!(items, [1, 2, 3, 4]);
+/// });
+/// ```
+pub fn select<St1, St2>(stream1: St1, stream2: St2) -> Select<St1, St2>
+where
+    St1: Stream,
+    St2: Stream<Item = St1::Item>,
+{
+    Select {
+        stream1: Some(Box::pin(stream1)),
+        stream2: Some(Box::pin(stream2)),
+    }
+}
+
+/// Stream returned by [`select`].
+#[must_use = "streams do nothing unless polled"]
+pub struct Select<St1, St2> {
+    stream1: Option<Pin<Box<St1>>>,
+    stream2: Option<Pin<Box<St2>>>,
+}
+
+impl<St1, St2> Stream for Select<St1, St2>
+where
+    St1: Stream,
+    St2: Stream<Item = St1::Item>,
+{
+    type Item = St1::Item;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+        let mut order = [0, 1];
+        if this.stream1.is_some() && this.stream2.is_some() {
+            order.shuffle(&mut crate::rand::thread_rng());
+        }
+        for i in order {
+            let poll = match i {
+                0 => poll_next_or_end(&mut this.stream1, cx),
+                _ => poll_next_or_end(&mut this.stream2, cx),
+            };
+            if let Poll::Ready(Some(item)) = poll {
+                return Poll::Ready(Some(item));
+            }
+        }
+        if this.stream1.is_none() && this.stream2.is_none() {
+            return Poll::Ready(None);
+        }
+        Poll::Pending
+    }
+}
+
+impl<St1, St2> fmt::Debug for Select<St1, St2> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Select")
+            .field("stream1_done", &self.stream1.is_none())
+            .field("stream2_done", &self.stream2.is_none())
+            .finish()
+    }
+}
+
+/// Merges a collection of streams into one, yielding items as they become available on any.
+///
+/// When several streams have an item ready, which one is yielded first is chosen by the
+/// deterministic random number generator. The merged stream ends when all streams end.
+///
+/// # Example
+///
+/// ```
+/// use madsim::{runtime::Runtime, stream};
+/// use madsim::export::futures::{stream::iter, StreamExt};
+///
+/// Runtime::new().block_on(async {
+///     let streams = (0..3).map(|i| iter([i * 10, i * 10 + 1]));
+///     let mut items = stream::select_all(streams).collect::<Vec<_>>().await;
+///     items.sort();
+///     assert_eq!(items, [0, 1, 10, 11, 20, 21]);
+/// });
+/// ```
+pub fn select_all<I>(streams: I) -> SelectAll<I::Item>
+where
+    I: IntoIterator,
+    I::Item: Stream,
+{
+    let streams = streams
+        .into_iter()
+        .map(|s| Some(Box::pin(s)))
+        .collect::<Vec<_>>();
+    SelectAll { streams }
+}
+
+/// Stream returned by [`select_all`].
+#[must_use = "streams do nothing unless polled"]
+pub struct SelectAll<St> {
+    streams: Vec<Option<Pin<Box<St>>>>,
+}
+
+impl<St: Stream> SelectAll<St> {
+    /// Adds a stream to the set.
+    pub fn push(&mut self, stream: St) {
+        self.streams.push(Some(Box::pin(stream)));
+    }
+
+    /// Returns the number of streams that have not ended.
+    pub fn len(&self) -> usize {
+        self.streams.iter().filter(|s| s.is_some()).count()
+    }
+
+    /// Returns `true` if all streams have ended.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<St: Stream> Stream for SelectAll<St> {
+    type Item = St::Item;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+        let mut pending = (0..this.streams.len())
+            .filter(|&i| this.streams[i].is_some())
+            .collect::<Vec<_>>();
+        if pending.len() > 1 {
+            pending.shuffle(&mut crate::rand::thread_rng());
+        }
+        for i in pending {
+            if let Poll::Ready(Some(item)) = poll_next_or_end(&mut this.streams[i], cx) {
+                return Poll::Ready(Some(item));
+            }
+        }
+        this.streams.retain(Option::is_some);
+        if this.streams.is_empty() {
+            return Poll::Ready(None);
+        }
+        Poll::Pending
+    }
+}
+
+impl<St> fmt::Debug for SelectAll<St> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SelectAll")
+            .field("len", &self.streams.iter().filter(|s| s.is_some()).count())
+            .finish()
+    }
+}
+
+/// Polls the stream for the next item, and removes it once it ends.
+fn poll_next_or_end<St: Stream>(
+    stream: &mut Option<Pin<Box<St>>>,
+    cx: &mut Context<'_>,
+) -> Poll<Option<St::Item>> {
+    let Some(s) = stream else {
+        return Poll::Ready(None);
+    };
+    let poll = s.as_mut().poll_next(cx);
+    if let Poll::Ready(None) = poll {
+        *stream = None;
+    }
+    poll
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runtime::Runtime;
+    use futures_util::{stream::iter, StreamExt};
+    use std::collections::HashSet;
+
+    #[test]
+    fn random_interleaving() {
+        let run = |seed: u64| {
+            let runtime = Runtime::with_seed_and_config(seed, crate::Config::default());
+            runtime.block_on(async {
+                let a = select(iter([0, 1, 2]), iter([10, 11, 12]));
+                let b = select_all([iter([20, 21]), iter([30, 31]), iter([40, 41])]);
+                a.chain(b).collect::<Vec<_>>().await
+            })
+        };
+        let mut interleavings = HashSet::new();
+        for seed in 0..10 {
+            let items = run(seed);
+            assert_eq!(items, run(seed));
+            // the order within each stream is kept
+            let pos = |x| items.iter().position(|&i| i == x).unwrap();
+            assert!(pos(0) < pos(1) && pos(1) < pos(2) && pos(10) < pos(11));
+            assert!(pos(20) < pos(21) && pos(30) < pos(31) && pos(40) < pos(41));
+            let mut sorted = items.clone();
+            sorted.sort();
+            assert_eq!(sorted, [0, 1, 2, 10, 11, 12, 20, 21, 30, 31, 40, 41]);
+            interleavings.insert(items);
+        }
+        assert_eq!(interleavings.len(), 10);
+        // `select` does not always alternate between the streams
+        let alternating = [0, 10, 1, 11, 2, 12];
+        assert!(interleavings.iter().any(|i| i[..6] != alternating));
+    }
+
+    #[test]
+    fn pending_stream() {
+        let runtime = Runtime::new();
+        runtime.block_on(async {
+            let delayed = futures_util::stream::once(async {
+                crate::time::sleep(std::time::Duration::from_secs(1)).await;
+                2
+            });
+            let mut merged = select_all([delayed.boxed(), iter([1]).boxed()]);
+            assert_eq!(merged.next().await, Some(1));
+            assert_eq!(merged.next().await, Some(2));
+            assert_eq!(merged.next().await, None);
+            assert!(merged.is_empty());
+        });
+    }
+}
@@ -0,0 +1,267 @@
+use crate::task::NodeInfo;
+use spin::Mutex;
+use std::{
+    collections::BTreeMap,
+    fmt,
+    future::poll_fn,
+    sync::{Arc, Weak},
+    task::{Poll, Waker},
+};
+
+/// A barrier enables multiple tasks to synchronize the beginning of some computation.
+///
+/// All parties are released at the same simulated instant, in an order determined by
+/// the global random number generator.
+///
+/// # Kill
+///
+/// If a task waiting on the barrier is killed, its arrival is withdrawn.
+/// What happens to the rest depends on the [`KillPolicy`]:
+///
+/// - [`KillPolicy::Wait`] (default): the barrier still requires `n` parties,
+///   so the rest keep waiting for a replacement, and block forever if none comes.
+/// - [`KillPolicy::Release`]: the killed party leaves the barrier, so it requires one
+///   party fewer, and the rest are released if all remaining parties have arrived.
+///
+/// # Example
+///
+/// ```
+/// use madsim::{runtime::Runtime, sync::Barrier};
+/// use std::sync::Arc;
+///
+/// let rt = Runtime::new();
+/// let barrier = Arc::new(Barrier::new(3));
+/// let tasks = (0..3)
+///     .map(|_| {
+///         let barrier = barrier.clone();
+///         rt.create_node().build().spawn(async move { barrier.wait().await })
+///     })
+///     .collect::<Vec<_>>();
+/// rt.block_on(async move {
+///     let mut leaders = 0;
+///     for task in tasks {
+///         leaders += task.await.unwrap().is_leader() as usize;
+///     }
+///     assert_eq!(leaders, 1);
+/// });
+/// ```
+pub struct Barrier {
+    state: Mutex<State>,
+    policy: KillPolicy,
+}
+
+/// What to do when a task waiting on a [`Barrier`] is killed.
+#[cfg_attr(docsrs, doc(cfg(madsim)))]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum KillPolicy {
+    /// Keep requiring the same number of parties.
+    #[default]
+    Wait,
+    /// Remove the killed party from the barrier.
+    Release,
+}
+
+struct State {
+    /// The number of parties required to release the barrier.
+    parties: usize,
+    /// The waiters of the current generation.
+    waiters: BTreeMap<u64, Option<Waker>>,
+    /// The number of times the barrier has been released.
+    generation: u64,
+    /// The ID of the next waiter.
+    next_id: u64,
+}
+
+/// A result returned from [`Barrier::wait`] when all parties have arrived.
+#[derive(Debug, Clone)]
+pub struct BarrierWaitResult(bool);
+
+impl BarrierWaitResult {
+    /// Returns `true` if this task was the last to arrive at the barrier.
+    pub fn is_leader(&self) -> bool {
+        self.0
+    }
+}
+
+impl Barrier {
+    /// Creates a new barrier that can block `n` tasks.
+    ///
+    /// A barrier will block `n - 1` tasks which call [`wait`](Self::wait) and then wake up
+    /// all tasks at once when the `n`-th task calls `wait`. A barrier of 0 behaves like 1.
+    pub fn new(n: usize) -> Self {
+        Self::with_kill_policy(n, KillPolicy::default())
+    }
+
+    /// Creates a new barrier with the given policy on killed waiters.
+    #[cfg_attr(docsrs, doc(cfg(madsim)))]
+    pub fn with_kill_policy(n: usize, policy: KillPolicy) -> Self {
+        Barrier {
+            state: Mutex::new(State {
+                parties: n.max(1),
+                waiters: BTreeMap::new(),
+                generation: 0,
+                next_id: 0,
+            }),
+            policy,
+        }
+    }
+
+    /// Does not resolve until all parties have arrived.
+    ///
+    /// This function is not cancel safe: a cancelled wait still counts as arrived,
+    /// unless the task is killed.
+    pub async fn wait(&self) -> BarrierWaitResult {
+        let (generation, id) = {
+            let mut state = self.state.lock();
+            let id = state.next_id;
+            state.next_id += 1;
+            state.waiters.insert(id, None);
+            if state.waiters.len() >= state.parties {
+                let wakers = state.release();
+                drop(state);
+                wake_all(wakers);
+                return BarrierWaitResult(true);
+            }
+            (state.generation, id)
+        };
+        let _guard = WaitGuard {
+            barrier: self,
+            generation,
+            id,
+            node: crate::context::try_current_task().map(|task| Arc::downgrade(&task.node)),
+        };
+        poll_fn(|cx| {
+            let mut state = self.state.lock();
+            if state.generation != generation {
+                return Poll::Ready(BarrierWaitResult(false));
+            }
+            state.waiters.insert(id, Some(cx.waker().clone()));
+            Poll::Pending
+        })
+        .await
+    }
+}
+
+impl State {
+    /// Releases all waiters of the current generation, returning their wakers.
+    fn release(&mut self) -> Vec<Waker> {
+        self.generation += 1;
+        let waiters = std::mem::take(&mut self.waiters);
+        waiters.into_values().flatten().collect()
+    }
+}
+
+fn wake_all(mut wakers: Vec<Waker>) {
+    crate::rand::shuffle(&mut wakers);
+    for waker in wakers {
+        waker.wake();
+    }
+}
+
+/// Withdraws the arrival if the waiting task is killed.
+struct WaitGuard<'a> {
+    barrier: &'a Barrier,
+    generation: u64,
+    id: u64,
+    node: Option<Weak<NodeInfo>>,
+}
+
+impl Drop for WaitGuard<'_> {
+    fn drop(&mut self) {
+        let killed = (self.node.as_ref())
+            .and_then(|node| node.upgrade())
+            .is_some_and(|node| node.is_killed());
+        if !killed {
+            return;
+        }
+        let mut state = self.barrier.state.lock();
+        if state.generation != self.generation {
+            return;
+        }
+        state.waiters.remove(&self.id);
+        if self.barrier.policy == KillPolicy::Release {
+            state.parties = (state.parties - 1).max(1);
+            if !state.waiters.is_empty() && state.waiters.len() >= state.parties {
+                let wakers = state.release();
+                drop(state);
+                wake_all(wakers);
+            }
+        }
+    }
+}
+
+impl fmt::Debug for Barrier {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = self.state.lock();
+        f.debug_struct("Barrier")
+            .field("parties", &state.parties)
+            .field("arrived", &state.waiters.len())
+            .field("policy", &self.policy)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        runtime::{Handle, Runtime},
+        time::{sleep, timeout, Duration, Instant},
+    };
+
+    #[test]
+    fn synchronize_start() {
+        let runtime = Runtime::new();
+        let barrier = Arc::new(Barrier::new(3));
+        let nodes = (0..3)
+            .map(|_| runtime.create_node().build())
+            .collect::<Vec<_>>();
+        runtime.block_on(async move {
+            let t0 = Instant::now();
+            let tasks = (nodes.iter().enumerate())
+                .map(|(i, node)| {
+                    let barrier = barrier.clone();
+                    node.spawn(async move {
+                        sleep(Duration::from_secs(i as u64 + 1)).await;
+                        let result = barrier.wait().await;
+                        (result.is_leader(), Instant::now())
+                    })
+                })
+                .collect::<Vec<_>>();
+            let mut leaders = vec![];
+            for task in tasks {
+                let (is_leader, t) = task.await.unwrap();
+                leaders.push(is_leader);
+                let t = t - t0;
+                assert!(t >= Duration::from_secs(3));
+                assert!(t < Duration::from_secs(3) + Duration::from_millis(1));
+            }
+            // the last one to arrive is the leader
+            assert_eq!(leaders, [false, false, true]);
+        });
+    }
+
+    #[test]
+    fn kill_waiter() {
+        let run = |policy| {
+            let runtime = Runtime::new();
+            let barrier = Arc::new(Barrier::with_kill_policy(2, policy));
+            let node = runtime.create_node().build();
+            runtime.block_on(async move {
+                let barrier0 = barrier.clone();
+                node.spawn(async move {
+                    barrier0.wait().await;
+                    unreachable!("the node should be killed");
+                });
+                sleep(Duration::from_secs(1)).await;
+                Handle::current().kill(node.id());
+                sleep(Duration::from_secs(1)).await;
+                timeout(Duration::from_secs(1), barrier.wait())
+                    .await
+                    .is_ok()
+            })
+        };
+        assert!(run(KillPolicy::Release));
+        assert!(!run(KillPolicy::Wait));
+    }
+}
@@ -0,0 +1,212 @@
+use spin::Mutex;
+use std::{
+    collections::BTreeMap,
+    fmt,
+    future::poll_fn,
+    sync::{Arc, Weak},
+    task::{Poll, Waker},
+};
+
+/// A token which can be used to signal a cancellation request to one or more tasks.
+///
+/// The API is the same as `tokio_util::sync::CancellationToken`.
+/// Cancelling a token cancels all its children, and wakes up the waiting tasks
+/// in an order determined by the global random number generator.
+///
+/// Each node has a root token which is cancelled when the node is killed,
+/// see [`current_node`](Self::current_node).
+///
+/// # Example
+///
+/// ```
+/// use madsim::{runtime::Runtime, sync::CancellationToken};
+///
+/// Runtime::new().block_on(async {
+///     let token = CancellationToken::new();
+///     let child = token.child_token();
+///     let task = madsim::task::spawn(async move { child.cancelled().await });
+///     token.cancel();
+///     task.await.unwrap();
+/// });
+/// ```
+#[derive(Clone, Default)]
+pub struct CancellationToken {
+    inner: Arc<Inner>,
+}
+
+#[derive(Default)]
+struct Inner {
+    state: Mutex<State>,
+}
+
+#[derive(Default)]
+struct State {
+    cancelled: bool,
+    children: Vec<Weak<Inner>>,
+    waiters: BTreeMap<u64, Waker>,
+    next_id: u64,
+}
+
+impl CancellationToken {
+    /// Creates a new token which is not cancelled.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the root token of the current node, which is cancelled when the node is killed.
+    ///
+    /// A restarted node has a new root token.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if called outside a madsim task.
+    #[cfg_attr(docsrs, doc(cfg(madsim)))]
+    pub fn current_node() -> Self {
+        crate::context::current_task().node.token.clone()
+    }
+
+    /// Creates a child token which is cancelled when this token is cancelled.
+    ///
+    /// Cancelling the child token does not cancel this token.
+    pub fn child_token(&self) -> Self {
+        let child = Self::new();
+        let mut state = self.inner.state.lock();
+        if state.cancelled {
+            child.inner.state.lock().cancelled = true;
+        } else {
+            state.children.retain(|c| c.strong_count() > 0);
+            state.children.push(Arc::downgrade(&child.inner));
+        }
+        child
+    }
+
+    /// Cancels this token and all its children.
+    pub fn cancel(&self) {
+        self.inner.cancel();
+    }
+
+    /// Returns `true` if this token has been cancelled.
+    pub fn is_cancelled(&self) -> bool {
+        self.inner.state.lock().cancelled
+    }
+
+    /// Waits until this token is cancelled.
+    pub async fn cancelled(&self) {
+        let mut guard = WaitGuard {
+            inner: &self.inner,
+            id: None,
+        };
+        poll_fn(|cx| {
+            let mut state = self.inner.state.lock();
+            if state.cancelled {
+                return Poll::Ready(());
+            }
+            let id = *guard.id.get_or_insert_with(|| {
+                state.next_id += 1;
+                state.next_id
+            });
+            state.waiters.insert(id, cx.waker().clone());
+            Poll::Pending
+        })
+        .await;
+        guard.id = None;
+    }
+}
+
+impl Inner {
+    fn cancel(&self) {
+        let (mut wakers, children) = {
+            let mut state = self.state.lock();
+            if state.cancelled {
+                return;
+            }
+            state.cancelled = true;
+            let waiters = std::mem::take(&mut state.waiters);
+            let children = std::mem::take(&mut state.children);
+            (waiters.into_values().collect::<Vec<_>>(), children)
+        };
+        // the node may be killed outside the runtime context
+        if crate::context::try_current(|_| ()).is_some() {
+            crate::rand::shuffle(&mut wakers);
+        }
+        for waker in wakers {
+            waker.wake();
+        }
+        for child in children.iter().filter_map(Weak::upgrade) {
+            child.cancel();
+        }
+    }
+}
+
+/// Removes the waker if the wait is cancelled.
+struct WaitGuard<'a> {
+    inner: &'a Inner,
+    id: Option<u64>,
+}
+
+impl Drop for WaitGuard<'_> {
+    fn drop(&mut self) {
+        if let Some(id) = self.id {
+            self.inner.state.lock().waiters.remove(&id);
+        }
+    }
+}
+
+impl fmt::Debug for CancellationToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CancellationToken")
+            .field("is_cancelled", &self.is_cancelled())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        runtime::{Handle, Runtime},
+        time::{sleep, timeout, Duration},
+    };
+
+    #[test]
+    fn hierarchy() {
+        let runtime = Runtime::new();
+        runtime.block_on(async {
+            let parent = CancellationToken::new();
+            let child = parent.child_token();
+            let grandchild = child.child_token();
+            let sibling = parent.child_token();
+
+            sibling.cancel();
+            assert!(!parent.is_cancelled());
+            assert!(!child.is_cancelled());
+            let result = timeout(Duration::from_secs(1), grandchild.cancelled()).await;
+            assert!(result.is_err());
+
+            let task = crate::task::spawn(async move { grandchild.cancelled().await });
+            sleep(Duration::from_secs(1)).await;
+            parent.cancel();
+            task.await.unwrap();
+            assert!(child.is_cancelled());
+            assert!(parent.child_token().is_cancelled());
+        });
+    }
+
+    #[test]
+    fn kill_node() {
+        let runtime = Runtime::new();
+        let node = runtime.create_node().build();
+        runtime.block_on(async move {
+            let (tx, rx) = crate::sync::oneshot::channel();
+            node.spawn(async move {
+                tx.send(CancellationToken::current_node().child_token())
+                    .unwrap();
+                std::future::pending::<()>().await;
+            });
+            let child = rx.await.unwrap();
+            assert!(!child.is_cancelled());
+            Handle::current().kill(node.id());
+            child.cancelled().await;
+        });
+    }
+}
@@ -0,0 +1,178 @@
+use spin::Mutex;
+use std::{
+    collections::HashMap,
+    fmt,
+    future::poll_fn,
+    sync::Arc,
+    task::{Poll, Waker},
+};
+
+/// A set of named checkpoints for coordinating tasks around logical phases.
+///
+/// Tasks wait on a checkpoint by name until it is signaled. Once signaled, all waiters
+/// are released together at the same simulated instant, in an order determined by the
+/// global random number generator. Later waiters return immediately.
+///
+/// Checkpoints are cheap to clone and can be shared across nodes.
+///
+/// # Example
+///
+/// ```
+/// use madsim::{runtime::Runtime, sync::Checkpoint, time::{sleep, Duration, Instant}};
+///
+/// let rt = Runtime::new();
+/// let checkpoint = Checkpoint::new();
+/// checkpoint.register("after election");
+///
+/// let node = rt.create_node().build();
+/// let cp = checkpoint.clone();
+/// let follower = node.spawn(async move {
+///     cp.wait("after election").await;
+///     Instant::now()
+/// });
+/// rt.block_on(async move {
+///     let t0 = Instant::now();
+///     sleep(Duration::from_secs(1)).await;
+///     checkpoint.signal("after election");
+///     assert!(follower.await.unwrap() - t0 >= Duration::from_secs(1));
+/// });
+/// ```
+#[derive(Clone, Default)]
+pub struct Checkpoint {
+    inner: Arc<Mutex<HashMap<String, State>>>,
+}
+
+#[derive(Default)]
+struct State {
+    signaled: bool,
+    waiters: Vec<Waker>,
+}
+
+impl Checkpoint {
+    /// Creates a new set of checkpoints.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers a checkpoint named `name`. Does nothing if it is already registered.
+    pub fn register(&self, name: &str) {
+        self.inner.lock().entry(name.into()).or_default();
+    }
+
+    /// Waits until the checkpoint named `name` is signaled.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if the checkpoint is not registered.
+    pub async fn wait(&self, name: &str) {
+        poll_fn(|cx| {
+            let mut inner = self.inner.lock();
+            let state = inner
+                .get_mut(name)
+                .unwrap_or_else(|| panic!("checkpoint {name:?} is not registered"));
+            if state.signaled {
+                return Poll::Ready(());
+            }
+            if !state.waiters.iter().any(|w| w.will_wake(cx.waker())) {
+                state.waiters.push(cx.waker().clone());
+            }
+            Poll::Pending
+        })
+        .await
+    }
+
+    /// Signals the checkpoint named `name`, releasing all waiters.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if the checkpoint is not registered.
+    pub fn signal(&self, name: &str) {
+        let mut waiters = {
+            let mut inner = self.inner.lock();
+            let state = inner
+                .get_mut(name)
+                .unwrap_or_else(|| panic!("checkpoint {name:?} is not registered"));
+            state.signaled = true;
+            std::mem::take(&mut state.waiters)
+        };
+        crate::rand::shuffle(&mut waiters);
+        for waker in waiters {
+            waker.wake();
+        }
+    }
+
+    /// Returns `true` if the checkpoint named `name` has been signaled.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if the checkpoint is not registered.
+    pub fn is_signaled(&self, name: &str) -> bool {
+        let inner = self.inner.lock();
+        let state = inner
+            .get(name)
+            .unwrap_or_else(|| panic!("checkpoint {name:?} is not registered"));
+        state.signaled
+    }
+}
+
+impl fmt::Debug for Checkpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let inner = self.inner.lock();
+        let mut names = inner.keys().collect::<Vec<_>>();
+        names.sort();
+        f.debug_struct("Checkpoint").field("names", &names).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        runtime::Runtime,
+        time::{sleep, Duration, Instant},
+    };
+
+    #[test]
+    fn release_together() {
+        let runtime = Runtime::new();
+        let checkpoint = Checkpoint::new();
+        checkpoint.register("phase 1");
+        checkpoint.register("phase 2");
+        let nodes = (0..3)
+            .map(|_| runtime.create_node().build())
+            .collect::<Vec<_>>();
+        runtime.block_on(async move {
+            let t0 = Instant::now();
+            let tasks = nodes
+                .iter()
+                .map(|node| {
+                    let cp = checkpoint.clone();
+                    node.spawn(async move {
+                        cp.wait("phase 1").await;
+                        Instant::now()
+                    })
+                })
+                .collect::<Vec<_>>();
+            sleep(Duration::from_secs(1)).await;
+            assert!(!checkpoint.is_signaled("phase 1"));
+            checkpoint.signal("phase 1");
+            for task in tasks {
+                let t = task.await.unwrap() - t0;
+                assert!(t >= Duration::from_secs(1));
+                assert!(t < Duration::from_secs(1) + Duration::from_millis(1));
+            }
+            // signaled checkpoints return immediately
+            checkpoint.wait("phase 1").await;
+            assert!(!checkpoint.is_signaled("phase 2"));
+        });
+    }
+
+    #[test]
+    #[should_panic(expected = "checkpoint \"unknown\" is not registered")]
+    fn unregistered() {
+        let runtime = Runtime::new();
+        runtime.block_on(async {
+            Checkpoint::new().wait("unknown").await;
+        });
+    }
+}
@@ -0,0 +1,240 @@
+use super::mpsc::Sender;
+use crate::task::NodeId;
+use rand::seq::SliceRandom;
+use spin::Mutex;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+    sync::Arc,
+    time::Duration,
+};
+
+/// What happens to a message crossing a partition boundary.
+#[cfg_attr(docsrs, doc(cfg(madsim)))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PartitionFault {
+    /// The message is silently dropped. The send still succeeds.
+    Drop,
+    /// The message is delivered after an extra delay.
+    Delay(Duration),
+}
+
+/// Injects network partitions into [`mpsc`] channels between nodes.
+///
+/// Channels are registered with the nodes at both ends. While a partition is in effect,
+/// messages sent on a channel whose nodes are in different groups are dropped or delayed
+/// according to the [`PartitionFault`]. Nodes that are not registered with any channel are
+/// never partitioned.
+///
+/// On a channel created by [`mpsc::channel_with_latency`], the extra delay of
+/// [`PartitionFault::Delay`] is added on top of the latency drawn for the message, and
+/// dropped messages draw no latency. A partition only affects messages sent while it is
+/// in effect: messages already in flight are still delivered after [`heal`](Self::heal).
+///
+/// [`mpsc`]: super::mpsc
+/// [`mpsc::channel_with_latency`]: super::mpsc::channel_with_latency
+///
+/// # Example
+///
+/// ```
+/// use madsim::{runtime::Runtime, sync::{mpsc, FaultInjector, PartitionFault}};
+///
+/// let rt = Runtime::new();
+/// let node1 = rt.create_node().build();
+/// let node2 = rt.create_node().build();
+/// rt.block_on(async move {
+///     let fault = FaultInjector::new(PartitionFault::Drop);
+///     let (tx, mut rx) = mpsc::channel(8);
+///     fault.register(&tx, node1.id(), node2.id());
+///
+///     // split the two nodes into two groups
+///     assert_eq!(fault.partition(2).len(), 2);
+///     tx.send(1).await.unwrap();
+///     assert!(rx.try_recv().is_err());
+///
+///     fault.heal();
+///     tx.send(2).await.unwrap();
+///     assert_eq!(rx.try_recv(), Ok(2));
+/// });
+/// ```
+#[cfg_attr(docsrs, doc(cfg(madsim)))]
+#[derive(Clone)]
+pub struct FaultInjector {
+    inner: Arc<Mutex<Inner>>,
+}
+
+struct Inner {
+    fault: PartitionFault,
+    /// The nodes of registered channels.
+    nodes: BTreeSet<NodeId>,
+    /// The group of each node. Empty if there is no partition.
+    groups: BTreeMap<NodeId, usize>,
+}
+
+/// What happens to a message sent on a registered channel.
+pub(super) enum Verdict {
+    Deliver,
+    Drop,
+    Delay(Duration),
+}
+
+impl FaultInjector {
+    /// Creates an injector that applies `fault` to messages crossing a partition.
+    pub fn new(fault: PartitionFault) -> Self {
+        FaultInjector {
+            inner: Arc::new(Mutex::new(Inner {
+                fault,
+                nodes: BTreeSet::new(),
+                groups: BTreeMap::new(),
+            })),
+        }
+    }
+
+    /// Registers a channel carrying messages from node `src` to node `dst`.
+    ///
+    /// The registration applies to all clones of the sender.
+    pub fn register<T>(&self, tx: &Sender<T>, src: NodeId, dst: NodeId) {
+        let mut inner = self.inner.lock();
+        inner.nodes.insert(src);
+        inner.nodes.insert(dst);
+        drop(inner);
+        tx.set_link(self.clone(), src, dst);
+    }
+
+    /// Randomly splits the registered nodes into `groups` non-empty groups,
+    /// replacing the current partition.
+    ///
+    /// The assignment is chosen by the global random number generator. If there are fewer
+    /// nodes than `groups`, each node is put in its own group. Returns the groups.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `groups` is 0.
+    pub fn partition(&self, groups: usize) -> Vec<Vec<NodeId>> {
+        assert!(groups > 0, "partition requires at least one group");
+        let mut inner = self.inner.lock();
+        let mut nodes = inner.nodes.iter().copied().collect::<Vec<_>>();
+        nodes.shuffle(&mut crate::rand::thread_rng());
+        let mut result = vec![vec![]; groups.min(nodes.len())];
+        inner.groups.clear();
+        for (i, node) in nodes.into_iter().enumerate() {
+            let group = i % result.len();
+            inner.groups.insert(node, group);
+            result[group].push(node);
+        }
+        for group in &mut result {
+            group.sort();
+        }
+        result
+    }
+
+    /// Removes the partition, so that all messages are delivered again.
+    pub fn heal(&self) {
+        self.inner.lock().groups.clear();
+    }
+
+    /// Returns `true` if a partition is in effect.
+    pub fn is_partitioned(&self) -> bool {
+        !self.inner.lock().groups.is_empty()
+    }
+
+    /// Returns what happens to a message from `src` to `dst`.
+    pub(super) fn verdict(&self, src: NodeId, dst: NodeId) -> Verdict {
+        let inner = self.inner.lock();
+        match (inner.groups.get(&src), inner.groups.get(&dst)) {
+            (Some(a), Some(b)) if a != b => match inner.fault {
+                PartitionFault::Drop => Verdict::Drop,
+                PartitionFault::Delay(delay) => Verdict::Delay(delay),
+            },
+            _ => Verdict::Deliver,
+        }
+    }
+}
+
+impl fmt::Debug for FaultInjector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let inner = self.inner.lock();
+        f.debug_struct("FaultInjector")
+            .field("fault", &inner.fault)
+            .field("nodes", &inner.nodes.len())
+            .field("partitioned", &!inner.groups.is_empty())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{runtime::Runtime, sync::mpsc, time::Instant};
+    use std::collections::HashSet;
+
+    #[test]
+    fn deterministic_partition() {
+        let groups = |seed| {
+            let runtime = Runtime::with_seed_and_config(seed, crate::Config::default());
+            let nodes = (0..6)
+                .map(|_| runtime.create_node().build().id())
+                .collect::<Vec<_>>();
+            runtime.block_on(async move {
+                let fault = FaultInjector::new(PartitionFault::Drop);
+                for w in nodes.windows(2) {
+                    let (tx, _rx) = mpsc::channel::<()>(1);
+                    fault.register(&tx, w[0], w[1]);
+                }
+                let groups = fault.partition(3);
+                // every node is in exactly one group
+                let mut all = groups.concat();
+                all.sort();
+                assert_eq!(all, nodes);
+                // messages are dropped across groups and delivered within a group
+                for a in &groups {
+                    for b in &groups {
+                        let verdict = fault.verdict(a[0], b[1]);
+                        if a == b {
+                            assert!(matches!(verdict, Verdict::Deliver));
+                        } else {
+                            assert!(matches!(verdict, Verdict::Drop));
+                        }
+                    }
+                }
+                groups
+            })
+        };
+        let mut partitions = HashSet::new();
+        for seed in 0..10 {
+            let groups1 = groups(seed);
+            assert_eq!(groups1, groups(seed));
+            assert_eq!(groups1.len(), 3);
+            assert!(groups1.iter().all(|g| g.len() == 2));
+            partitions.insert(groups1);
+        }
+        // the nodes are grouped at random
+        assert!(partitions.len() > 1);
+    }
+
+    #[test]
+    fn delay_with_latency() {
+        let runtime = Runtime::new();
+        let node1 = runtime.create_node().build();
+        let node2 = runtime.create_node().build();
+        let fault = FaultInjector::new(PartitionFault::Delay(Duration::from_secs(10)));
+        let latency = |_: &mut dyn rand::RngCore| Duration::from_secs(1);
+        let (tx, mut rx) = mpsc::channel_with_latency(8, latency);
+        fault.register(&tx, node1.id(), node2.id());
+        let t0 = runtime.block_on(async {
+            fault.partition(2);
+            Instant::now()
+        });
+        assert!(fault.is_partitioned());
+        runtime
+            .block_on(node1.spawn(async move { tx.send(1).await.unwrap() }))
+            .unwrap();
+        // messages in flight are still delivered after healing
+        fault.heal();
+        let elapsed = runtime.block_on(node2.spawn(async move {
+            assert_eq!(rx.recv().await, Some(1));
+            t0.elapsed()
+        }));
+        assert!(elapsed.unwrap() >= Duration::from_secs(11));
+    }
+}
@@ -0,0 +1,25 @@
+//! Synchronization primitives for coordinating simulated nodes.
+//!
+//! Unlike their counterparts in `tokio::sync`, when several tasks contend for a primitive,
+//! the order in which they are woken up is determined by the global random number generator.
+
+pub use self::barrier::{Barrier, BarrierWaitResult, KillPolicy};
+pub use self::cancellation_token::CancellationToken;
+pub use self::checkpoint::Checkpoint;
+pub use self::fault::{FaultInjector, PartitionFault};
+pub(crate) use self::mutex::WaitGraph;
+pub use self::mutex::{Mutex, MutexGuard};
+pub use self::notify::{Notified, Notify};
+pub use self::rwlock::{Preference, RwLock, RwLockReadGuard, RwLockWriteGuard, TryLockError};
+pub use self::semaphore::{AcquireError, Semaphore, SemaphorePermit, TryAcquireError};
+
+mod barrier;
+mod cancellation_token;
+mod checkpoint;
+mod fault;
+pub mod mpsc;
+mod mutex;
+mod notify;
+pub mod oneshot;
+mod rwlock;
+mod semaphore;
@@ -0,0 +1,536 @@
+//! A bounded multi-producer, single-consumer queue.
+//!
+//! When the channel is full, the blocked senders are woken up one at a time as slots free
+//! up, in an order determined by the global random number generator instead of FIFO.
+//! This surfaces fairness bugs in producer/consumer code.
+
+use super::fault::{FaultInjector, Verdict};
+use crate::{
+    rand::Rng,
+    task::{coop, NodeId},
+    time::TimeHandle,
+};
+use rand::RngCore;
+use spin::Mutex;
+use std::{
+    collections::{BTreeMap, VecDeque},
+    fmt,
+    future::poll_fn,
+    sync::Arc,
+    task::{ready, Poll, Waker},
+    time::Duration,
+};
+
+/// Errors returned by the channel, which are the same as `tokio::sync::mpsc::error`.
+pub mod error {
+    pub use tokio::sync::mpsc::error::{SendError, TryRecvError, TrySendError};
+}
+
+use self::error::{SendError, TryRecvError, TrySendError};
+
+/// Creates a bounded mpsc channel for communicating between asynchronous tasks.
+///
+/// # Panics
+///
+/// Panics if the buffer capacity is 0.
+///
+/// # Example
+///
+/// ```
+/// use madsim::{runtime::Runtime, sync::mpsc};
+///
+/// Runtime::new().block_on(async {
+///     let (tx, mut rx) = mpsc::channel(1);
+///     madsim::task::spawn(async move {
+///         for i in 0..3 {
+///             tx.send(i).await.unwrap();
+///         }
+///     });
+///     for i in 0..3 {
+///         assert_eq!(rx.recv().await, Some(i));
+///     }
+///     assert_eq!(rx.recv().await, None);
+/// });
+/// ```
+pub fn channel<T>(buffer: usize) -> (Sender<T>, Receiver<T>) {
+    new(buffer, None)
+}
+
+/// Creates a bounded mpsc channel whose values are delivered after a simulated latency.
+///
+/// Each value sent becomes receivable after a latency drawn from `latency` with the global
+/// random number generator, so values may be reordered. This is a lightweight way to model
+/// message passing between nodes without the network simulator. A value in flight occupies
+/// a slot of the channel until it is received. To partition the nodes at both ends,
+/// register the channel with a [`FaultInjector`](super::FaultInjector).
+///
+/// # Panics
+///
+/// Panics if the buffer capacity is 0.
+///
+/// # Example
+///
+/// ```
+/// use madsim::{runtime::Runtime, sync::mpsc, task, time::{Duration, Instant}};
+///
+/// Runtime::new().block_on(async {
+///     let latency = task::uniform_latency(Duration::from_millis(10)..Duration::from_millis(20));
+///     let (tx, mut rx) = mpsc::channel_with_latency(8, latency);
+///     let t0 = Instant::now();
+///     tx.send(1).await.unwrap();
+///     assert_eq!(rx.recv().await, Some(1));
+///     assert!(t0.elapsed() >= Duration::from_millis(10));
+/// });
+/// ```
+#[cfg_attr(docsrs, doc(cfg(madsim)))]
+pub fn channel_with_latency<T>(
+    buffer: usize,
+    latency: impl Fn(&mut dyn RngCore) -> Duration + Send + Sync + 'static,
+) -> (Sender<T>, Receiver<T>) {
+    new(buffer, Some(Box::new(latency)))
+}
+
+fn new<T>(buffer: usize, latency: Option<LatencyFn>) -> (Sender<T>, Receiver<T>) {
+    assert!(buffer > 0, "mpsc bounded channel requires buffer > 0");
+    let chan = Arc::new(Mutex::new(Chan {
+        queue: VecDeque::with_capacity(buffer),
+        in_flight: BTreeMap::new(),
+        latency,
+        link: None,
+        cap: buffer,
+        senders: 1,
+        rx_closed: false,
+        send_waiters: BTreeMap::new(),
+        recv_waker: None,
+        scheduled: None,
+        next_id: 0,
+    }));
+    (Sender { chan: chan.clone() }, Receiver { chan })
+}
+
+/// A function to draw the latency of each value.
+type LatencyFn = Box<dyn Fn(&mut dyn RngCore) -> Duration + Send + Sync>;
+
+/// Sends values to the associated [`Receiver`].
+pub struct Sender<T> {
+    chan: Arc<Mutex<Chan<T>>>,
+}
+
+/// Receives values from the associated [`Sender`].
+pub struct Receiver<T> {
+    chan: Arc<Mutex<Chan<T>>>,
+}
+
+struct Chan<T> {
+    /// The values that can be received.
+    queue: VecDeque<T>,
+    /// The values in flight, indexed by the elapsed time when they arrive and the sequence.
+    in_flight: BTreeMap<(Duration, u64), T>,
+    latency: Option<LatencyFn>,
+    /// The fault injector and the nodes at both ends, if registered.
+    link: Option<(FaultInjector, NodeId, NodeId)>,
+    cap: usize,
+    /// The number of senders.
+    senders: usize,
+    rx_closed: bool,
+    /// The wakers of senders blocked on a full channel.
+    send_waiters: BTreeMap<u64, Waker>,
+    recv_waker: Option<Waker>,
+    /// The arrival time and the receiver of the scheduled wakeup, if any.
+    scheduled: Option<(Duration, Waker)>,
+    /// The ID of the next blocked sender or value in flight.
+    next_id: u64,
+}
+
+impl<T> Chan<T> {
+    /// Returns the number of values in the channel, including those in flight.
+    fn len(&self) -> usize {
+        self.queue.len() + self.in_flight.len()
+    }
+
+    /// Pushes a value to the queue, or puts it in flight if there is a latency or delay.
+    ///
+    /// The value is dropped if the fault injector says so.
+    fn push(&mut self, value: T) {
+        let delay = match &self.link {
+            Some((fault, src, dst)) => match fault.verdict(*src, *dst) {
+                Verdict::Deliver => None,
+                Verdict::Drop => return,
+                Verdict::Delay(delay) => Some(delay),
+            },
+            None => None,
+        };
+        if self.latency.is_none() && delay.is_none() {
+            self.queue.push_back(value);
+            self.wake_receiver();
+            return;
+        }
+        let (rand, time) = crate::context::current(|h| (h.rand.clone(), h.time.clone()));
+        let mut arrival = time.elapsed() + delay.unwrap_or_default();
+        if let Some(latency) = &self.latency {
+            arrival += rand.with(|rng| latency(rng));
+        }
+        let id = self.next_id;
+        self.next_id += 1;
+        self.in_flight.insert((arrival, id), value);
+        self.wake_receiver();
+    }
+
+    /// Pops the next value that has arrived.
+    ///
+    /// If there are only values in flight, schedules a wakeup of the receiver at the
+    /// arrival of the first one, unless it has already been scheduled.
+    fn pop(&mut self, waker: Option<&Waker>) -> Option<T> {
+        if let Some(&(arrival, _)) = self.in_flight.keys().next() {
+            let time = TimeHandle::current();
+            let now = time.elapsed();
+            while let Some(entry) = self.in_flight.first_entry() {
+                if entry.key().0 > now {
+                    break;
+                }
+                self.queue.push_back(entry.remove());
+            }
+            if self.queue.is_empty() {
+                if let Some(waker) = waker {
+                    let scheduled = matches!(
+                        &self.scheduled,
+                        Some((t, w)) if *t == arrival && w.will_wake(waker)
+                    );
+                    if !scheduled {
+                        self.scheduled = Some((arrival, waker.clone()));
+                        let waker = waker.clone();
+                        time.add_timer(arrival - now, move || waker.wake());
+                    }
+                }
+            }
+        }
+        let value = self.queue.pop_front()?;
+        self.wake_one_sender();
+        Some(value)
+    }
+
+    /// Wakes up a random blocked sender.
+    fn wake_one_sender(&mut self) {
+        if self.send_waiters.is_empty() {
+            return;
+        }
+        let i = crate::rand::thread_rng().gen_range(0..self.send_waiters.len());
+        let id = *self.send_waiters.keys().nth(i).unwrap();
+        self.send_waiters.remove(&id).unwrap().wake();
+    }
+
+    fn wake_receiver(&mut self) {
+        if let Some(waker) = self.recv_waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+impl<T> Sender<T> {
+    /// Sends a value, waiting until there is capacity.
+    ///
+    /// Returns an error with the value if the receiver has been dropped or closed.
+    pub async fn send(&self, value: T) -> Result<(), SendError<T>> {
+        let mut value = Some(value);
+        let mut guard = SendGuard {
+            chan: &self.chan,
+            id: None,
+        };
+        poll_fn(|cx| {
+            let coop = ready!(coop::poll_proceed(cx));
+            let mut chan = self.chan.lock();
+            if let Some(id) = guard.id.take() {
+                chan.send_waiters.remove(&id);
+            }
+            if chan.rx_closed {
+                coop.made_progress();
+                return Poll::Ready(Err(SendError(value.take().unwrap())));
+            }
+            if chan.len() < chan.cap {
+                coop.made_progress();
+                chan.push(value.take().unwrap());
+                return Poll::Ready(Ok(()));
+            }
+            let id = chan.next_id;
+            chan.next_id += 1;
+            chan.send_waiters.insert(id, cx.waker().clone());
+            guard.id = Some(id);
+            Poll::Pending
+        })
+        .await
+    }
+
+    /// Attempts to immediately send a value.
+    pub fn try_send(&self, value: T) -> Result<(), TrySendError<T>> {
+        let mut chan = self.chan.lock();
+        if chan.rx_closed {
+            return Err(TrySendError::Closed(value));
+        }
+        if chan.len() >= chan.cap {
+            return Err(TrySendError::Full(value));
+        }
+        chan.push(value);
+        Ok(())
+    }
+
+    /// Returns `true` if the receiver has been dropped or closed.
+    pub fn is_closed(&self) -> bool {
+        self.chan.lock().rx_closed
+    }
+
+    /// Returns the current capacity of the channel.
+    pub fn capacity(&self) -> usize {
+        let chan = self.chan.lock();
+        chan.cap - chan.len()
+    }
+
+    /// Returns the maximum buffer capacity of the channel.
+    pub fn max_capacity(&self) -> usize {
+        self.chan.lock().cap
+    }
+
+    /// Registers the channel with a fault injector.
+    pub(super) fn set_link(&self, fault: FaultInjector, src: NodeId, dst: NodeId) {
+        self.chan.lock().link = Some((fault, src, dst));
+    }
+}
+
+/// Passes the wakeup to another sender if a woken sender is cancelled.
+struct SendGuard<'a, T> {
+    chan: &'a Mutex<Chan<T>>,
+    /// The ID of the blocked sender.
+    id: Option<u64>,
+}
+
+impl<T> Drop for SendGuard<'_, T> {
+    fn drop(&mut self) {
+        let Some(id) = self.id else {
+            return;
+        };
+        let mut chan = self.chan.lock();
+        let woken = chan.send_waiters.remove(&id).is_none();
+        if woken && chan.len() < chan.cap {
+            chan.wake_one_sender();
+        }
+    }
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Self {
+        self.chan.lock().senders += 1;
+        Sender {
+            chan: self.chan.clone(),
+        }
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        let mut chan = self.chan.lock();
+        chan.senders -= 1;
+        if chan.senders == 0 {
+            chan.wake_receiver();
+        }
+    }
+}
+
+impl<T> Receiver<T> {
+    /// Receives the next value, or `None` if the channel is closed and empty.
+    pub async fn recv(&mut self) -> Option<T> {
+        poll_fn(|cx| {
+            let coop = ready!(coop::poll_proceed(cx));
+            let mut chan = self.chan.lock();
+            if let Some(value) = chan.pop(Some(cx.waker())) {
+                coop.made_progress();
+                return Poll::Ready(Some(value));
+            }
+            if chan.in_flight.is_empty() && (chan.senders == 0 || chan.rx_closed) {
+                coop.made_progress();
+                return Poll::Ready(None);
+            }
+            chan.recv_waker = Some(cx.waker().clone());
+            Poll::Pending
+        })
+        .await
+    }
+
+    /// Tries to receive the next value without waiting.
+    pub fn try_recv(&mut self) -> Result<T, TryRecvError> {
+        let mut chan = self.chan.lock();
+        if let Some(value) = chan.pop(None) {
+            return Ok(value);
+        }
+        if chan.in_flight.is_empty() && (chan.senders == 0 || chan.rx_closed) {
+            return Err(TryRecvError::Disconnected);
+        }
+        Err(TryRecvError::Empty)
+    }
+
+    /// Closes the receiving half of a channel without dropping it.
+    ///
+    /// Buffered values can still be received.
+    pub fn close(&mut self) {
+        let mut chan = self.chan.lock();
+        chan.rx_closed = true;
+        for (_, waker) in std::mem::take(&mut chan.send_waiters) {
+            waker.wake();
+        }
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        self.close();
+    }
+}
+
+impl<T> fmt::Debug for Sender<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Sender").finish_non_exhaustive()
+    }
+}
+
+impl<T> fmt::Debug for Receiver<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Receiver").finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        runtime::Runtime,
+        time::{sleep, Duration},
+    };
+    use std::{collections::HashSet, sync::Arc};
+
+    #[test]
+    fn close() {
+        let runtime = Runtime::new();
+        runtime.block_on(async {
+            let (tx, mut rx) = channel(1);
+            tx.try_send(1).unwrap();
+            assert!(matches!(tx.try_send(2), Err(TrySendError::Full(2))));
+            assert_eq!(tx.capacity(), 0);
+            rx.close();
+            assert!(tx.is_closed());
+            assert_eq!(tx.send(3).await, Err(SendError(3)));
+            assert_eq!(rx.recv().await, Some(1));
+            assert_eq!(rx.recv().await, None);
+
+            let (tx, mut rx) = channel::<i32>(1);
+            drop(tx);
+            assert_eq!(rx.try_recv(), Err(TryRecvError::Disconnected));
+        });
+    }
+
+    #[test]
+    fn random_wakeup_order() {
+        let order = |seed| {
+            let runtime = Runtime::with_seed_and_config(seed, crate::Config::default());
+            runtime.block_on(async {
+                let (tx, mut rx) = channel(1);
+                tx.send(usize::MAX).await.unwrap();
+                for i in 0..8 {
+                    let tx = tx.clone();
+                    crate::task::spawn(async move { tx.send(i).await.unwrap() });
+                    // let the sender block before spawning the next one
+                    sleep(Duration::from_millis(1)).await;
+                }
+                drop(tx);
+                let mut order = vec![];
+                while let Some(i) = rx.recv().await {
+                    order.push(i);
+                    sleep(Duration::from_secs(1)).await;
+                }
+                order
+            })
+        };
+        let mut orders = HashSet::new();
+        for seed in 0..10 {
+            let seq = order(seed);
+            assert_eq!(seq, order(seed));
+            // the buffered value comes first, then each blocked sender exactly once
+            assert_eq!(seq[0], usize::MAX);
+            let mut senders = seq[1..].to_vec();
+            senders.sort();
+            assert_eq!(senders, (0..8).collect::<Vec<_>>());
+            orders.insert(seq);
+        }
+        // the senders are not woken up in the order they blocked
+        assert_eq!(orders.len(), 10);
+    }
+
+    #[test]
+    fn latency() {
+        let run = |seed| {
+            let runtime = Runtime::with_seed_and_config(seed, crate::Config::default());
+            runtime.block_on(async {
+                let drawn = Arc::new(Mutex::new(vec![]));
+                let drawn0 = drawn.clone();
+                let range = Duration::from_millis(10)..Duration::from_millis(100);
+                let uniform = crate::task::uniform_latency(range);
+                let latency = move |rng: &mut dyn RngCore| {
+                    let latency = uniform(rng);
+                    drawn0.lock().push(latency);
+                    latency
+                };
+                let (tx, mut rx) = channel_with_latency(8, latency);
+                let t0 = crate::time::Instant::now();
+                let mut sent = vec![];
+                for i in 0..8 {
+                    sent.push(t0.elapsed());
+                    tx.send(i).await.unwrap();
+                }
+                // values in flight occupy the capacity
+                assert!(matches!(tx.try_send(8), Err(TrySendError::Full(8))));
+                assert!(matches!(rx.try_recv(), Err(TryRecvError::Empty)));
+                drop(tx);
+                // each value arrives after its own latency
+                let arrival = |i: usize| sent[i] + drawn.lock()[i];
+                let mut order = vec![];
+                while let Some(i) = rx.recv().await {
+                    let elapsed = t0.elapsed();
+                    assert!(elapsed >= arrival(i));
+                    assert!(elapsed < arrival(i) + Duration::from_millis(1));
+                    order.push(i);
+                }
+                // so values sent later may overtake earlier ones
+                let mut expected = (0..8).collect::<Vec<_>>();
+                expected.sort_by_key(|&i| arrival(i));
+                assert_eq!(order, expected);
+                order
+            })
+        };
+        let mut orders = HashSet::new();
+        for seed in 0..10 {
+            let order = run(seed);
+            assert_eq!(order, run(seed));
+            orders.insert(order);
+        }
+        assert_eq!(orders.len(), 10);
+    }
+
+    #[test]
+    fn latency_schedules_one_wakeup() {
+        let runtime = Runtime::new();
+        let handle = runtime.handle().clone();
+        runtime.block_on(async move {
+            let (tx, mut rx) = channel_with_latency(1, |_| Duration::from_millis(10));
+            tx.send(1).await.unwrap();
+            let count = handle.timer_event_count();
+            // poll the receiver repeatedly while the value is in flight
+            std::future::poll_fn(|cx| {
+                for _ in 0..10 {
+                    let recv = std::pin::pin!(rx.recv());
+                    assert!(std::future::Future::poll(recv, cx).is_pending());
+                }
+                Poll::Ready(())
+            })
+            .await;
+            assert_eq!(rx.recv().await, Some(1));
+            assert_eq!(handle.timer_event_count() - count, 1);
+        });
+    }
+}
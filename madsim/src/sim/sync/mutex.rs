@@ -0,0 +1,506 @@
+use super::TryLockError;
+use crate::{rand::Rng, task::Id};
+use spin::Mutex as SpinMutex;
+use std::{
+    cell::UnsafeCell,
+    collections::{BTreeMap, HashMap},
+    fmt,
+    future::poll_fn,
+    marker::PhantomData,
+    ops::{Deref, DerefMut},
+    sync::{Arc, OnceLock},
+    task::{Poll, Waker},
+};
+
+/// An asynchronous mutual exclusion lock.
+///
+/// When the lock is released and several tasks are waiting, the next holder is chosen by
+/// the global random number generator.
+///
+/// With [`Config::detect_deadlocks`](crate::task::Config::detect_deadlocks), the lock
+/// tracks which task holds it and which lock each task waits for. A task that is about to
+/// wait for a lock, closing a cycle of tasks waiting for each other, panics with the tasks
+/// and locks involved. Since the schedule is deterministic, so is the detection.
+///
+/// A lock held by a task is released when the task is killed.
+///
+/// # Example
+///
+/// ```
+/// use madsim::{runtime::Runtime, sync::Mutex};
+///
+/// Runtime::new().block_on(async {
+///     let lock = Mutex::new(5);
+///     {
+///         let mut n = lock.lock().await;
+///         *n += 1;
+///         assert!(lock.try_lock().is_err());
+///     }
+///     assert_eq!(*lock.lock().await, 6);
+/// });
+/// ```
+pub struct Mutex<T: ?Sized> {
+    state: SpinMutex<State>,
+    /// The name of the lock in deadlock reports.
+    name: Option<String>,
+    /// The identity of the lock, assigned when it is first acquired.
+    id: OnceLock<u64>,
+    data: UnsafeCell<T>,
+}
+
+unsafe impl<T: ?Sized + Send> Send for Mutex<T> {}
+unsafe impl<T: ?Sized + Send> Sync for Mutex<T> {}
+
+struct State {
+    locked: bool,
+    waiters: BTreeMap<u64, Waiter>,
+    next_id: u64,
+}
+
+struct Waiter {
+    /// The waiting task, if deadlock detection is enabled.
+    task: Option<Id>,
+    waker: Option<Waker>,
+    granted: bool,
+}
+
+/// RAII structure used to release the exclusive lock of a [`Mutex`] when dropped.
+#[must_use]
+pub struct MutexGuard<'a, T: ?Sized> {
+    lock: &'a Mutex<T>,
+    _marker: PhantomData<&'a mut T>,
+}
+
+unsafe impl<T: ?Sized + Send + Sync> Sync for MutexGuard<'_, T> {}
+
+impl<T> Mutex<T> {
+    /// Creates a new lock in an unlocked state.
+    pub fn new(value: T) -> Self {
+        Mutex {
+            state: SpinMutex::new(State {
+                locked: false,
+                waiters: BTreeMap::new(),
+                next_id: 0,
+            }),
+            name: None,
+            id: OnceLock::new(),
+            data: UnsafeCell::new(value),
+        }
+    }
+
+    /// Creates a new lock with a name that identifies it in deadlock reports.
+    #[cfg_attr(docsrs, doc(cfg(madsim)))]
+    pub fn with_name(value: T, name: impl Into<String>) -> Self {
+        Mutex {
+            name: Some(name.into()),
+            ..Self::new(value)
+        }
+    }
+
+    /// Consumes the lock, returning the underlying data.
+    pub fn into_inner(self) -> T {
+        self.data.into_inner()
+    }
+}
+
+impl<T: ?Sized> Mutex<T> {
+    /// Locks this mutex, waiting until it can be acquired.
+    ///
+    /// # Panics
+    ///
+    /// With deadlock detection enabled, panics if waiting would close a cycle of tasks
+    /// waiting for each other.
+    pub async fn lock(&self) -> MutexGuard<'_, T> {
+        let graph = WaitGraph::current();
+        let task = graph.as_ref().map(|_| crate::task::id());
+        let id = {
+            let mut state = self.state.lock();
+            if !state.locked {
+                state.locked = true;
+                drop(state);
+                if let (Some(graph), Some(task)) = (&graph, task) {
+                    let lock = self.lock_id(graph);
+                    graph.lock().acquire(lock, task);
+                }
+                return MutexGuard::new(self);
+            }
+            if let (Some(graph), Some(task)) = (&graph, task) {
+                let lock = self.lock_id(graph);
+                graph.lock().wait(lock, task);
+            }
+            let id = state.next_id;
+            state.next_id += 1;
+            let waiter = Waiter {
+                task,
+                waker: None,
+                granted: false,
+            };
+            state.waiters.insert(id, waiter);
+            id
+        };
+        let mut guard = AcquireGuard {
+            lock: self,
+            id: Some(id),
+        };
+        poll_fn(|cx| {
+            let mut state = self.state.lock();
+            let waiter = state.waiters.get_mut(&id).unwrap();
+            if !waiter.granted {
+                waiter.waker = Some(cx.waker().clone());
+                return Poll::Pending;
+            }
+            state.waiters.remove(&id);
+            guard.id = None;
+            Poll::Ready(())
+        })
+        .await;
+        MutexGuard::new(self)
+    }
+
+    /// Attempts to acquire the lock without waiting.
+    pub fn try_lock(&self) -> Result<MutexGuard<'_, T>, TryLockError> {
+        let mut state = self.state.lock();
+        if state.locked {
+            return Err(TryLockError(()));
+        }
+        state.locked = true;
+        drop(state);
+        if let Some(graph) = WaitGraph::current() {
+            if let Some(task) = crate::task::try_id() {
+                let lock = self.lock_id(&graph);
+                graph.lock().acquire(lock, task);
+            }
+        }
+        Ok(MutexGuard::new(self))
+    }
+
+    /// Returns a mutable reference to the underlying data.
+    pub fn get_mut(&mut self) -> &mut T {
+        self.data.get_mut()
+    }
+
+    /// Returns the identity of the lock in the wait-for graph.
+    fn lock_id(&self, graph: &SpinMutex<WaitGraph>) -> u64 {
+        *self.id.get_or_init(|| {
+            let mut graph = graph.lock();
+            let id = graph.next_lock_id;
+            graph.next_lock_id += 1;
+            let name = match &self.name {
+                Some(name) => format!("`{name}`"),
+                None => format!("#{id}"),
+            };
+            graph.names.insert(id, name);
+            id
+        })
+    }
+
+    /// Releases the lock, or passes it to a random waiter.
+    fn release(&self) {
+        // the lock may be released when a killed task is dropped outside its context
+        let graph = self.id.get().and(WaitGraph::get());
+        let mut state = self.state.lock();
+        let waiters = (state.waiters.iter())
+            .filter(|(_, w)| !w.granted)
+            .map(|(id, _)| *id)
+            .collect::<Vec<_>>();
+        if waiters.is_empty() {
+            state.locked = false;
+            if let Some(graph) = graph {
+                graph.lock().release(*self.id.get().unwrap());
+            }
+            return;
+        }
+        let i = crate::rand::thread_rng().gen_range(0..waiters.len());
+        let waiter = state.waiters.get_mut(&waiters[i]).unwrap();
+        waiter.granted = true;
+        if let Some(waker) = &waiter.waker {
+            waker.wake_by_ref();
+        }
+        if let (Some(graph), Some(task)) = (graph, waiter.task) {
+            graph.lock().acquire(*self.id.get().unwrap(), task);
+        }
+    }
+}
+
+/// Removes the waiter and releases the granted lock if the acquisition is cancelled.
+struct AcquireGuard<'a, T: ?Sized> {
+    lock: &'a Mutex<T>,
+    id: Option<u64>,
+}
+
+impl<T: ?Sized> Drop for AcquireGuard<'_, T> {
+    fn drop(&mut self) {
+        let Some(id) = self.id else {
+            return;
+        };
+        let waiter = self.lock.state.lock().waiters.remove(&id);
+        if let Some(waiter) = waiter {
+            if let (Some(graph), Some(task)) = (WaitGraph::get(), waiter.task) {
+                graph.lock().waiting.remove(&task);
+            }
+            if waiter.granted {
+                self.lock.release();
+            }
+        }
+    }
+}
+
+/// The holders of locks and the locks tasks are waiting for, used to detect deadlocks.
+#[derive(Default)]
+pub(crate) struct WaitGraph {
+    /// The task holding each lock.
+    holders: HashMap<u64, Id>,
+    /// The lock each task is waiting for.
+    waiting: HashMap<Id, u64>,
+    /// The display names of locks.
+    names: HashMap<u64, String>,
+    next_lock_id: u64,
+}
+
+impl WaitGraph {
+    /// Returns the graph of the current runtime if deadlock detection is enabled.
+    fn get() -> Option<Arc<SpinMutex<WaitGraph>>> {
+        crate::context::try_current(|h| {
+            (h.config.task.detect_deadlocks).then(|| h.wait_graph.clone())
+        })
+        .flatten()
+    }
+
+    /// Returns the graph if deadlock detection is enabled and called from a task.
+    fn current() -> Option<Arc<SpinMutex<WaitGraph>>> {
+        Self::get().filter(|_| crate::task::try_id().is_some())
+    }
+
+    fn acquire(&mut self, lock: u64, task: Id) {
+        self.waiting.remove(&task);
+        self.holders.insert(lock, task);
+    }
+
+    fn release(&mut self, lock: u64) {
+        self.holders.remove(&lock);
+    }
+
+    /// Records that the task waits for the lock.
+    ///
+    /// # Panics
+    ///
+    /// Panics if it closes a cycle of waiting tasks.
+    fn wait(&mut self, lock: u64, task: Id) {
+        self.waiting.insert(task, lock);
+        let mut path = vec![];
+        let mut next = lock;
+        while let Some(&holder) = self.holders.get(&next) {
+            path.push((next, holder));
+            if holder == task {
+                self.waiting.remove(&task);
+                panic!("deadlock detected: {}", self.describe(task, &path));
+            }
+            match self.waiting.get(&holder) {
+                Some(&lock) if path.len() <= self.holders.len() => next = lock,
+                _ => return,
+            }
+        }
+    }
+
+    /// Describes a cycle starting from the task.
+    fn describe(&self, task: Id, path: &[(u64, Id)]) -> String {
+        let mut waiter = task;
+        let mut edges = vec![];
+        for (lock, holder) in path {
+            edges.push(format!(
+                "task {waiter} waits for lock {} held by task {holder}",
+                self.names[lock]
+            ));
+            waiter = *holder;
+        }
+        edges.join(", ")
+    }
+}
+
+impl<'a, T: ?Sized> MutexGuard<'a, T> {
+    fn new(lock: &'a Mutex<T>) -> Self {
+        MutexGuard {
+            lock,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T: ?Sized> Deref for MutexGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<T: ?Sized> DerefMut for MutexGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.lock.data.get() }
+    }
+}
+
+impl<T: ?Sized> Drop for MutexGuard<'_, T> {
+    fn drop(&mut self) {
+        self.lock.release();
+    }
+}
+
+impl<T: Default> Default for Mutex<T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+impl<T: ?Sized> fmt::Debug for Mutex<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = self.state.lock();
+        f.debug_struct("Mutex")
+            .field("name", &self.name)
+            .field("locked", &state.locked)
+            .field("waiters", &state.waiters.len())
+            .finish_non_exhaustive()
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for MutexGuard<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        runtime::Runtime,
+        task::spawn,
+        time::{sleep, Duration},
+    };
+    use std::{collections::HashSet, panic::AssertUnwindSafe, sync::Mutex as StdMutex};
+
+    #[test]
+    fn random_grant_order() {
+        let order = |seed| {
+            let runtime = Runtime::with_seed_and_config(seed, crate::Config::default());
+            runtime.block_on(async {
+                let lock = Arc::new(Mutex::new(vec![]));
+                let guard = lock.lock().await;
+                let mut tasks = vec![];
+                for i in 0..8 {
+                    let lock = lock.clone();
+                    tasks.push(spawn(async move { lock.lock().await.push(i) }));
+                    // let the task block before spawning the next one
+                    sleep(Duration::from_millis(1)).await;
+                }
+                drop(guard);
+                for task in tasks {
+                    task.await.unwrap();
+                }
+                let order = lock.lock().await.clone();
+                order
+            })
+        };
+        let mut orders = HashSet::new();
+        for seed in 0..10 {
+            let order1 = order(seed);
+            assert_eq!(order1, order(seed));
+            // each waiter is granted the lock once
+            let mut sorted = order1.clone();
+            sorted.sort();
+            assert_eq!(sorted, (0..8).collect::<Vec<_>>());
+            orders.insert(order1);
+        }
+        // the waiters are not granted in the order they blocked
+        assert_eq!(orders.len(), 10);
+    }
+
+    /// Runs two tasks locking `a` and `b` in opposite orders, and records their IDs in `ids`.
+    fn inversion(seed: u64, ids: &StdMutex<Vec<Id>>) {
+        let mut config = crate::Config::default();
+        config.task.detect_deadlocks = true;
+        let runtime = Runtime::with_seed_and_config(seed, config);
+        runtime.block_on(async {
+            let a = Arc::new(Mutex::with_name((), "a"));
+            let b = Arc::new(Mutex::with_name((), "b"));
+            let (a1, b1) = (a.clone(), b.clone());
+            let t1 = spawn(async move {
+                let _a = a1.lock().await;
+                sleep(Duration::from_secs(1)).await;
+                let _b = b1.lock().await;
+            });
+            let t2 = spawn(async move {
+                let _b = b.lock().await;
+                sleep(Duration::from_secs(1)).await;
+                let _a = a.lock().await;
+            });
+            *ids.lock().unwrap() = vec![t1.id(), t2.id()];
+            t1.await.unwrap();
+            t2.await.unwrap();
+        });
+    }
+
+    #[test]
+    #[should_panic(expected = "deadlock detected: task ")]
+    fn detect_lock_order_inversion() {
+        inversion(1, &StdMutex::new(vec![]));
+    }
+
+    #[test]
+    fn deterministic_report() {
+        let report = |seed| {
+            let ids = StdMutex::new(vec![]);
+            let payload = std::panic::catch_unwind(AssertUnwindSafe(|| inversion(seed, &ids)));
+            let msg = payload
+                .unwrap_err()
+                .downcast_ref::<String>()
+                .unwrap()
+                .clone();
+            let ids = ids.into_inner().unwrap();
+            (msg, ids[0], ids[1])
+        };
+        for seed in 0..10 {
+            let (msg, t1, t2) = report(seed);
+            // the cycle names both tasks and both locks, from whichever task closed it
+            let from_t1 = format!(
+                "deadlock detected: task {t1} waits for lock `b` held by task {t2}, \
+                 task {t2} waits for lock `a` held by task {t1}"
+            );
+            let from_t2 = format!(
+                "deadlock detected: task {t2} waits for lock `a` held by task {t1}, \
+                 task {t1} waits for lock `b` held by task {t2}"
+            );
+            assert!(msg == from_t1 || msg == from_t2, "{msg}");
+            assert_eq!(msg, report(seed).0);
+        }
+    }
+
+    #[test]
+    fn guard_is_sync_only_for_sync_data() {
+        fn assert_sync<T: Sync>() {}
+        assert_sync::<MutexGuard<'_, u8>>();
+
+        // resolving `some_item` is ambiguous if the guard implements `Sync`
+        trait AmbiguousIfSync<A> {
+            fn some_item() {}
+        }
+        impl<T: ?Sized> AmbiguousIfSync<()> for T {}
+        impl<T: ?Sized + Sync> AmbiguousIfSync<u8> for T {}
+        <MutexGuard<'_, std::cell::Cell<u8>> as AmbiguousIfSync<_>>::some_item();
+    }
+
+    #[test]
+    fn no_detection_by_default() {
+        let runtime = Runtime::new();
+        runtime.block_on(async {
+            let a = Arc::new(Mutex::new(0));
+            let a1 = a.clone();
+            let _guard = a.lock().await;
+            let task = spawn(async move { *a1.lock().await += 1 });
+            sleep(Duration::from_secs(1)).await;
+            drop(_guard);
+            task.await.unwrap();
+            assert_eq!(*a.lock().await, 1);
+        });
+    }
+}
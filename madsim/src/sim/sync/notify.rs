@@ -0,0 +1,310 @@
+use crate::{rand::Rng, task::coop};
+use spin::Mutex;
+use std::{
+    collections::BTreeMap,
+    fmt,
+    future::Future,
+    pin::Pin,
+    task::{ready, Context, Poll, Waker},
+};
+
+/// Notifies a single task or all tasks waiting on it.
+///
+/// The API is the same as `tokio::sync::Notify`, except that [`notify_one`] wakes up
+/// a waiter chosen by the global random number generator instead of FIFO,
+/// and [`notify_waiters`] wakes up all waiters in a random order.
+///
+/// [`notify_one`]: Notify::notify_one
+/// [`notify_waiters`]: Notify::notify_waiters
+///
+/// # Example
+///
+/// ```
+/// use madsim::{runtime::Runtime, sync::Notify};
+/// use std::sync::Arc;
+///
+/// Runtime::new().block_on(async {
+///     let notify = Arc::new(Notify::new());
+///     let notify2 = notify.clone();
+///     let task = madsim::task::spawn(async move {
+///         notify2.notified().await;
+///     });
+///     notify.notify_one();
+///     task.await.unwrap();
+/// });
+/// ```
+pub struct Notify {
+    state: Mutex<State>,
+}
+
+struct State {
+    /// Whether a permit is stored by [`Notify::notify_one`].
+    permit: bool,
+    /// The number of times [`Notify::notify_waiters`] has been called.
+    generation: u64,
+    /// The waiters which have been polled.
+    waiters: BTreeMap<u64, Waiter>,
+    next_id: u64,
+}
+
+struct Waiter {
+    waker: Waker,
+    /// Whether the waiter is chosen by [`Notify::notify_one`].
+    notified: bool,
+}
+
+/// Future returned from [`Notify::notified`].
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct Notified<'a> {
+    notify: &'a Notify,
+    /// The generation when the future is created.
+    generation: u64,
+    /// The ID of the waiter, if the future has been polled.
+    id: Option<u64>,
+    done: bool,
+}
+
+impl Notify {
+    /// Creates a new `Notify`, initialized without a permit.
+    pub fn new() -> Self {
+        Notify {
+            state: Mutex::new(State {
+                permit: false,
+                generation: 0,
+                waiters: BTreeMap::new(),
+                next_id: 0,
+            }),
+        }
+    }
+
+    /// Waits for a notification.
+    ///
+    /// The future is notified by [`notify_waiters`](Self::notify_waiters) once created,
+    /// but only by [`notify_one`](Self::notify_one) once polled.
+    pub fn notified(&self) -> Notified<'_> {
+        Notified {
+            notify: self,
+            generation: self.state.lock().generation,
+            id: None,
+            done: false,
+        }
+    }
+
+    /// Notifies a random waiting task.
+    ///
+    /// If there is no waiting task, a permit is stored and the next call to
+    /// [`notified().await`](Self::notified) will complete immediately.
+    pub fn notify_one(&self) {
+        self.state.lock().notify_one();
+    }
+
+    /// Notifies all waiting tasks in a random order.
+    ///
+    /// No permit is stored if there is no waiting task.
+    pub fn notify_waiters(&self) {
+        let mut wakers = {
+            let mut state = self.state.lock();
+            state.generation += 1;
+            let waiters = std::mem::take(&mut state.waiters);
+            waiters.into_values().map(|w| w.waker).collect::<Vec<_>>()
+        };
+        crate::rand::shuffle(&mut wakers);
+        for waker in wakers {
+            waker.wake();
+        }
+    }
+}
+
+impl State {
+    fn notify_one(&mut self) {
+        let candidates = (self.waiters.iter())
+            .filter(|(_, w)| !w.notified)
+            .map(|(id, _)| *id)
+            .collect::<Vec<_>>();
+        if candidates.is_empty() {
+            self.permit = true;
+            return;
+        }
+        let i = crate::rand::thread_rng().gen_range(0..candidates.len());
+        let waiter = self.waiters.get_mut(&candidates[i]).unwrap();
+        waiter.notified = true;
+        waiter.waker.wake_by_ref();
+    }
+}
+
+impl Default for Notify {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Future for Notified<'_> {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if self.done {
+            return Poll::Ready(());
+        }
+        let coop = ready!(coop::poll_proceed(cx));
+        let notify = self.notify;
+        let mut state = notify.state.lock();
+        let ready = if state.generation != self.generation {
+            if let Some(id) = self.id {
+                state.waiters.remove(&id);
+            }
+            true
+        } else if let Some(id) = self.id {
+            let waiter = state.waiters.get_mut(&id).unwrap();
+            if waiter.notified {
+                state.waiters.remove(&id);
+                true
+            } else {
+                waiter.waker = cx.waker().clone();
+                false
+            }
+        } else if state.permit {
+            state.permit = false;
+            true
+        } else {
+            let id = state.next_id;
+            state.next_id += 1;
+            let waiter = Waiter {
+                waker: cx.waker().clone(),
+                notified: false,
+            };
+            state.waiters.insert(id, waiter);
+            drop(state);
+            self.id = Some(id);
+            return Poll::Pending;
+        };
+        drop(state);
+        if ready {
+            coop.made_progress();
+            self.id = None;
+            self.done = true;
+            return Poll::Ready(());
+        }
+        Poll::Pending
+    }
+}
+
+impl Drop for Notified<'_> {
+    fn drop(&mut self) {
+        let Some(id) = self.id else {
+            return;
+        };
+        let mut state = self.notify.state.lock();
+        // pass the notification to another waiter
+        if let Some(waiter) = state.waiters.remove(&id) {
+            if waiter.notified {
+                state.notify_one();
+            }
+        }
+    }
+}
+
+impl fmt::Debug for Notify {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = self.state.lock();
+        f.debug_struct("Notify")
+            .field("permit", &state.permit)
+            .field("waiters", &state.waiters.len())
+            .finish()
+    }
+}
+
+impl fmt::Debug for Notified<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Notified").finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        runtime::Runtime,
+        time::{sleep, timeout, Duration},
+    };
+    use std::{collections::HashSet, sync::Arc};
+
+    #[test]
+    fn permit() {
+        let runtime = Runtime::new();
+        runtime.block_on(async {
+            let notify = Notify::new();
+            notify.notify_one();
+            notify.notify_one();
+            notify.notified().await;
+            // only one permit is stored
+            let result = timeout(Duration::from_secs(1), notify.notified()).await;
+            assert!(result.is_err());
+
+            // `notify_waiters` does not store a permit
+            notify.notify_waiters();
+            let result = timeout(Duration::from_secs(1), notify.notified()).await;
+            assert!(result.is_err());
+
+            // `notify_waiters` notifies futures created before it
+            let notified = notify.notified();
+            notify.notify_waiters();
+            notified.await;
+        });
+    }
+
+    #[test]
+    fn notify_one_random() {
+        let first = |seed| {
+            let runtime = Runtime::with_seed_and_config(seed, crate::Config::default());
+            runtime.block_on(async {
+                let notify = Arc::new(Notify::new());
+                let mut tasks = vec![];
+                for i in 0..8 {
+                    let notify = notify.clone();
+                    tasks.push(crate::task::spawn(async move {
+                        notify.notified().await;
+                        i
+                    }));
+                    // let the task wait before spawning the next one
+                    sleep(Duration::from_millis(1)).await;
+                }
+                notify.notify_one();
+                sleep(Duration::from_secs(1)).await;
+                let finished = (tasks.iter()).position(|task| task.is_finished()).unwrap();
+                assert_eq!(tasks.iter().filter(|t| t.is_finished()).count(), 1);
+                notify.notify_waiters();
+                for task in tasks {
+                    task.await.unwrap();
+                }
+                finished
+            })
+        };
+        let mut notified = HashSet::new();
+        for seed in 0..50 {
+            let finished = first(seed);
+            assert_eq!(finished, first(seed));
+            notified.insert(finished);
+        }
+        // any waiter can be notified, not only the one that waited first
+        assert_eq!(notified, (0..8).collect());
+    }
+
+    #[test]
+    fn pass_on_cancel() {
+        let runtime = Runtime::new();
+        runtime.block_on(async {
+            let notify = Arc::new(Notify::new());
+            let notify2 = notify.clone();
+            let task = crate::task::spawn(async move {
+                notify2.notified().await;
+            });
+            let mut notified = Box::pin(notify.notified());
+            let result = timeout(Duration::from_secs(1), &mut notified).await;
+            assert!(result.is_err());
+            notify.notify_one();
+            // whichever is chosen, dropping ours passes the notification on
+            drop(notified);
+            task.await.unwrap();
+        });
+    }
+}
@@ -0,0 +1,264 @@
+//! A one-shot channel is used for sending a single message between asynchronous tasks.
+//!
+//! The API is the same as `tokio::sync::oneshot`.
+
+use crate::task::coop;
+use spin::Mutex;
+use std::{
+    fmt,
+    future::{poll_fn, Future},
+    pin::Pin,
+    sync::Arc,
+    task::{ready, Context, Poll, Waker},
+};
+
+/// Errors returned by the channel.
+pub mod error {
+    use std::fmt;
+
+    /// Error returned by the [`Receiver`](super::Receiver) if the sender is dropped
+    /// without sending a value.
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct RecvError(pub(super) ());
+
+    /// Error returned by [`Receiver::try_recv`](super::Receiver::try_recv).
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub enum TryRecvError {
+        /// The value has not been sent yet.
+        Empty,
+        /// The sender is dropped without sending a value, or the value has been received.
+        Closed,
+    }
+
+    impl fmt::Display for RecvError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "channel closed")
+        }
+    }
+
+    impl std::error::Error for RecvError {}
+
+    impl fmt::Display for TryRecvError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self {
+                TryRecvError::Empty => write!(f, "channel empty"),
+                TryRecvError::Closed => write!(f, "channel closed"),
+            }
+        }
+    }
+
+    impl std::error::Error for TryRecvError {}
+}
+
+use self::error::{RecvError, TryRecvError};
+
+/// Creates a new one-shot channel for sending single values across asynchronous tasks.
+///
+/// # Example
+///
+/// ```
+/// use madsim::{runtime::Runtime, sync::oneshot};
+///
+/// Runtime::new().block_on(async {
+///     let (tx, rx) = oneshot::channel();
+///     madsim::task::spawn(async move { tx.send(1).unwrap() });
+///     assert_eq!(rx.await, Ok(1));
+///
+///     let (tx, rx) = oneshot::channel::<i32>();
+///     drop(tx);
+///     assert!(rx.await.is_err());
+/// });
+/// ```
+pub fn channel<T>() -> (Sender<T>, Receiver<T>) {
+    let inner = Arc::new(Mutex::new(Inner {
+        value: None,
+        tx_dropped: false,
+        rx_closed: false,
+        tx_waker: None,
+        rx_waker: None,
+    }));
+    (
+        Sender {
+            inner: inner.clone(),
+        },
+        Receiver { inner },
+    )
+}
+
+/// Sends a value to the associated [`Receiver`].
+pub struct Sender<T> {
+    inner: Arc<Mutex<Inner<T>>>,
+}
+
+/// Receives a value from the associated [`Sender`].
+pub struct Receiver<T> {
+    inner: Arc<Mutex<Inner<T>>>,
+}
+
+struct Inner<T> {
+    value: Option<T>,
+    /// Whether the sender is dropped or has sent a value.
+    tx_dropped: bool,
+    /// Whether the receiver is dropped or closed.
+    rx_closed: bool,
+    /// The waker of the task waiting on [`Sender::closed`].
+    tx_waker: Option<Waker>,
+    rx_waker: Option<Waker>,
+}
+
+impl<T> Sender<T> {
+    /// Attempts to send a value on this channel, returning it back if it could not be sent.
+    pub fn send(self, value: T) -> Result<(), T> {
+        let mut inner = self.inner.lock();
+        if inner.rx_closed {
+            return Err(value);
+        }
+        // the receiver is woken up when `self` is dropped
+        inner.value = Some(value);
+        Ok(())
+    }
+
+    /// Waits for the associated [`Receiver`] handle to close.
+    pub async fn closed(&mut self) {
+        poll_fn(|cx| self.poll_closed(cx)).await
+    }
+
+    /// Returns `true` if the associated [`Receiver`] handle has been dropped or closed.
+    pub fn is_closed(&self) -> bool {
+        self.inner.lock().rx_closed
+    }
+
+    /// Checks whether the oneshot channel has been closed, and if not,
+    /// schedules the `Waker` in the provided `Context` to receive a notification.
+    pub fn poll_closed(&mut self, cx: &mut Context<'_>) -> Poll<()> {
+        let mut inner = self.inner.lock();
+        if inner.rx_closed {
+            return Poll::Ready(());
+        }
+        inner.tx_waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        let mut inner = self.inner.lock();
+        inner.tx_dropped = true;
+        if let Some(waker) = inner.rx_waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+impl<T> Receiver<T> {
+    /// Prevents the associated [`Sender`] handle from sending a value.
+    ///
+    /// A value sent before the call can still be received.
+    pub fn close(&mut self) {
+        let mut inner = self.inner.lock();
+        inner.rx_closed = true;
+        if let Some(waker) = inner.tx_waker.take() {
+            waker.wake();
+        }
+    }
+
+    /// Attempts to receive a value without waiting.
+    pub fn try_recv(&mut self) -> Result<T, TryRecvError> {
+        let mut inner = self.inner.lock();
+        if let Some(value) = inner.value.take() {
+            return Ok(value);
+        }
+        if inner.tx_dropped || inner.rx_closed {
+            return Err(TryRecvError::Closed);
+        }
+        Err(TryRecvError::Empty)
+    }
+}
+
+impl<T> Future for Receiver<T> {
+    type Output = Result<T, RecvError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let coop = ready!(coop::poll_proceed(cx));
+        let mut inner = self.inner.lock();
+        if inner.tx_dropped || inner.rx_closed {
+            coop.made_progress();
+            return Poll::Ready(inner.value.take().ok_or(RecvError(())));
+        }
+        inner.rx_waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        self.close();
+    }
+}
+
+impl<T> fmt::Debug for Sender<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Sender").finish_non_exhaustive()
+    }
+}
+
+impl<T> fmt::Debug for Receiver<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Receiver").finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        runtime::{Handle, Runtime},
+        time::{sleep, Duration},
+    };
+
+    #[test]
+    fn drop_sender() {
+        let runtime = Runtime::new();
+        runtime.block_on(async {
+            let (tx, mut rx) = channel::<i32>();
+            assert_eq!(rx.try_recv(), Err(TryRecvError::Empty));
+            crate::task::spawn(async move {
+                sleep(Duration::from_secs(1)).await;
+                drop(tx);
+            });
+            assert_eq!(rx.await, Err(RecvError(())));
+        });
+    }
+
+    #[test]
+    fn drop_receiver() {
+        let runtime = Runtime::new();
+        runtime.block_on(async {
+            let (mut tx, rx) = channel();
+            assert!(!tx.is_closed());
+            crate::task::spawn(async move {
+                sleep(Duration::from_secs(1)).await;
+                drop(rx);
+            });
+            tx.closed().await;
+            assert!(tx.is_closed());
+            assert_eq!(tx.send(1), Err(1));
+        });
+    }
+
+    #[test]
+    fn sender_killed() {
+        let runtime = Runtime::new();
+        let node = runtime.create_node().build();
+        runtime.block_on(async move {
+            let (tx, rx) = channel::<()>();
+            node.spawn(async move {
+                let _tx = tx;
+                std::future::pending::<()>().await;
+            });
+            sleep(Duration::from_secs(1)).await;
+            Handle::current().kill(node.id());
+            assert!(rx.await.is_err());
+        });
+    }
+}
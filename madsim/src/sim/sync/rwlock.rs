@@ -0,0 +1,427 @@
+use crate::rand::Rng;
+use spin::Mutex;
+use std::{
+    cell::UnsafeCell,
+    collections::BTreeMap,
+    fmt,
+    future::poll_fn,
+    ops::{Deref, DerefMut},
+    task::{Poll, Waker},
+};
+
+/// An asynchronous reader-writer lock.
+///
+/// When the lock is released and several tasks are waiting, the next holders are chosen
+/// by the global random number generator among the waiters allowed by the [`Preference`].
+///
+/// A lock held by a task is released when the task is killed.
+///
+/// # Example
+///
+/// ```
+/// use madsim::{runtime::Runtime, sync::RwLock};
+///
+/// Runtime::new().block_on(async {
+///     let lock = RwLock::new(5);
+///     {
+///         let r1 = lock.read().await;
+///         let r2 = lock.read().await;
+///         assert_eq!(*r1 + *r2, 10);
+///         assert!(lock.try_write().is_err());
+///     }
+///     *lock.write().await += 1;
+///     assert_eq!(*lock.read().await, 6);
+/// });
+/// ```
+pub struct RwLock<T: ?Sized> {
+    state: Mutex<State>,
+    preference: Preference,
+    data: UnsafeCell<T>,
+}
+
+unsafe impl<T: ?Sized + Send> Send for RwLock<T> {}
+unsafe impl<T: ?Sized + Send + Sync> Sync for RwLock<T> {}
+
+/// Which kind of waiters a [`RwLock`] prefers when it is released.
+#[cfg_attr(docsrs, doc(cfg(madsim)))]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Preference {
+    /// Choose randomly among all waiters that can acquire the lock.
+    #[default]
+    None,
+    /// Grant the lock to waiting readers before writers.
+    Readers,
+    /// Grant the lock to waiting writers before readers.
+    /// New readers are blocked while a writer is waiting.
+    Writers,
+}
+
+struct State {
+    /// The number of readers holding the lock.
+    readers: usize,
+    /// Whether a writer is holding the lock.
+    writer: bool,
+    waiters: BTreeMap<u64, Waiter>,
+    next_id: u64,
+}
+
+struct Waiter {
+    write: bool,
+    waker: Option<Waker>,
+    granted: bool,
+}
+
+/// Error returned from [`RwLock::try_read`] and [`RwLock::try_write`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TryLockError(pub(super) ());
+
+/// RAII structure used to release the shared read access of a lock when dropped.
+#[must_use]
+pub struct RwLockReadGuard<'a, T: ?Sized> {
+    lock: &'a RwLock<T>,
+}
+
+/// RAII structure used to release the exclusive write access of a lock when dropped.
+#[must_use]
+pub struct RwLockWriteGuard<'a, T: ?Sized> {
+    lock: &'a RwLock<T>,
+}
+
+impl<T> RwLock<T> {
+    /// Creates a new instance of an `RwLock<T>` which is unlocked.
+    pub fn new(value: T) -> Self {
+        Self::with_preference(value, Preference::default())
+    }
+
+    /// Creates a new unlocked `RwLock<T>` with the given preference on waiters.
+    #[cfg_attr(docsrs, doc(cfg(madsim)))]
+    pub fn with_preference(value: T, preference: Preference) -> Self {
+        RwLock {
+            state: Mutex::new(State {
+                readers: 0,
+                writer: false,
+                waiters: BTreeMap::new(),
+                next_id: 0,
+            }),
+            preference,
+            data: UnsafeCell::new(value),
+        }
+    }
+
+    /// Consumes the lock, returning the underlying data.
+    pub fn into_inner(self) -> T {
+        self.data.into_inner()
+    }
+}
+
+impl<T: ?Sized> RwLock<T> {
+    /// Locks this `RwLock` with shared read access, waiting until it can be acquired.
+    pub async fn read(&self) -> RwLockReadGuard<'_, T> {
+        self.acquire(false).await;
+        RwLockReadGuard { lock: self }
+    }
+
+    /// Locks this `RwLock` with exclusive write access, waiting until it can be acquired.
+    pub async fn write(&self) -> RwLockWriteGuard<'_, T> {
+        self.acquire(true).await;
+        RwLockWriteGuard { lock: self }
+    }
+
+    /// Attempts to acquire this `RwLock` with shared read access without waiting.
+    pub fn try_read(&self) -> Result<RwLockReadGuard<'_, T>, TryLockError> {
+        let mut state = self.state.lock();
+        let writer_waiting = state.waiters.values().any(|w| w.write && !w.granted);
+        if state.writer || (self.preference == Preference::Writers && writer_waiting) {
+            return Err(TryLockError(()));
+        }
+        state.readers += 1;
+        Ok(RwLockReadGuard { lock: self })
+    }
+
+    /// Attempts to acquire this `RwLock` with exclusive write access without waiting.
+    pub fn try_write(&self) -> Result<RwLockWriteGuard<'_, T>, TryLockError> {
+        let mut state = self.state.lock();
+        if state.writer || state.readers > 0 {
+            return Err(TryLockError(()));
+        }
+        state.writer = true;
+        Ok(RwLockWriteGuard { lock: self })
+    }
+
+    /// Returns a mutable reference to the underlying data.
+    pub fn get_mut(&mut self) -> &mut T {
+        self.data.get_mut()
+    }
+
+    async fn acquire(&self, write: bool) {
+        let id = {
+            let mut state = self.state.lock();
+            let id = state.next_id;
+            state.next_id += 1;
+            let waiter = Waiter {
+                write,
+                waker: None,
+                granted: false,
+            };
+            state.waiters.insert(id, waiter);
+            state.grant(self.preference);
+            id
+        };
+        let mut guard = AcquireGuard {
+            lock: self,
+            id: Some(id),
+        };
+        poll_fn(|cx| {
+            let mut state = self.state.lock();
+            let waiter = state.waiters.get_mut(&id).unwrap();
+            if !waiter.granted {
+                waiter.waker = Some(cx.waker().clone());
+                return Poll::Pending;
+            }
+            state.waiters.remove(&id);
+            guard.id = None;
+            Poll::Ready(())
+        })
+        .await
+    }
+
+    fn release(&self, write: bool) {
+        let mut state = self.state.lock();
+        if write {
+            state.writer = false;
+        } else {
+            state.readers -= 1;
+        }
+        state.grant(self.preference);
+    }
+}
+
+impl State {
+    /// Grants the lock to random waiters until no more waiters can acquire it.
+    fn grant(&mut self, preference: Preference) {
+        while !self.writer {
+            let pending = |write: bool| {
+                (self.waiters.iter())
+                    .filter(move |(_, w)| !w.granted && w.write == write)
+                    .map(|(id, _)| *id)
+            };
+            let readers = pending(false).collect::<Vec<_>>();
+            let writers = pending(true).collect::<Vec<_>>();
+            let candidates = match preference {
+                Preference::Readers if !readers.is_empty() => readers,
+                Preference::Writers if !writers.is_empty() => match self.readers {
+                    0 => writers,
+                    _ => return,
+                },
+                _ if self.readers > 0 => readers,
+                _ => [readers, writers].concat(),
+            };
+            if candidates.is_empty() {
+                return;
+            }
+            let i = crate::rand::thread_rng().gen_range(0..candidates.len());
+            let waiter = self.waiters.get_mut(&candidates[i]).unwrap();
+            waiter.granted = true;
+            if waiter.write {
+                self.writer = true;
+            } else {
+                self.readers += 1;
+            }
+            if let Some(waker) = &waiter.waker {
+                waker.wake_by_ref();
+            }
+        }
+    }
+}
+
+/// Removes the waiter and releases the granted lock if the acquisition is cancelled.
+struct AcquireGuard<'a, T: ?Sized> {
+    lock: &'a RwLock<T>,
+    id: Option<u64>,
+}
+
+impl<T: ?Sized> Drop for AcquireGuard<'_, T> {
+    fn drop(&mut self) {
+        let Some(id) = self.id else {
+            return;
+        };
+        let waiter = self.lock.state.lock().waiters.remove(&id);
+        if let Some(waiter) = waiter {
+            if waiter.granted {
+                self.lock.release(waiter.write);
+            }
+        }
+    }
+}
+
+impl<T: ?Sized> Deref for RwLockReadGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<T: ?Sized> Drop for RwLockReadGuard<'_, T> {
+    fn drop(&mut self) {
+        self.lock.release(false);
+    }
+}
+
+impl<T: ?Sized> Deref for RwLockWriteGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<T: ?Sized> DerefMut for RwLockWriteGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.lock.data.get() }
+    }
+}
+
+impl<T: ?Sized> Drop for RwLockWriteGuard<'_, T> {
+    fn drop(&mut self) {
+        self.lock.release(true);
+    }
+}
+
+impl<T: Default> Default for RwLock<T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+impl<T: ?Sized> fmt::Debug for RwLock<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = self.state.lock();
+        f.debug_struct("RwLock")
+            .field("readers", &state.readers)
+            .field("writer", &state.writer)
+            .field("waiters", &state.waiters.len())
+            .field("preference", &self.preference)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for RwLockReadGuard<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for RwLockWriteGuard<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+impl fmt::Display for TryLockError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "operation would block")
+    }
+}
+
+impl std::error::Error for TryLockError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        runtime::{Handle, Runtime},
+        time::{sleep, Duration, Instant},
+    };
+    use std::{collections::HashSet, sync::Arc};
+
+    /// Spawns 1 writer and 4 readers waiting on a write-locked lock, and returns
+    /// the order in which they acquire it, with the elapsed seconds at each grant.
+    fn order(seed: u64, preference: Preference) -> Vec<(usize, u64)> {
+        let runtime = Runtime::with_seed_and_config(seed, crate::Config::default());
+        runtime.block_on(async move {
+            let lock = Arc::new(RwLock::with_preference((), preference));
+            let order = Arc::new(Mutex::new(vec![]));
+            let guard = lock.write().await;
+            let t0 = Instant::now();
+            let tasks = (0..5)
+                .map(|i| {
+                    let lock = lock.clone();
+                    let order = order.clone();
+                    crate::task::spawn(async move {
+                        if i == 0 {
+                            let _guard = lock.write().await;
+                            order.lock().push((i, t0.elapsed().as_secs()));
+                            sleep(Duration::from_secs(1)).await;
+                        } else {
+                            let _guard = lock.read().await;
+                            order.lock().push((i, t0.elapsed().as_secs()));
+                            sleep(Duration::from_secs(1)).await;
+                        }
+                    })
+                })
+                .collect::<Vec<_>>();
+            sleep(Duration::from_secs(1)).await;
+            drop(guard);
+            for task in tasks {
+                task.await.unwrap();
+            }
+            let order = order.lock().clone();
+            order
+        })
+    }
+
+    /// Returns the elapsed seconds when the writer and all readers acquired the lock.
+    fn grants(order: &[(usize, u64)]) -> (u64, HashSet<u64>) {
+        let writer = order.iter().find(|(i, _)| *i == 0).unwrap().1;
+        let readers = order.iter().filter(|(i, _)| *i != 0).map(|(_, t)| *t);
+        (writer, readers.collect())
+    }
+
+    #[test]
+    fn random_order() {
+        let mut writer_times = HashSet::new();
+        for seed in 0..10 {
+            let order1 = order(seed, Preference::None);
+            assert_eq!(order1, order(seed, Preference::None));
+            // the readers share the lock, before or after the writer
+            let (writer, readers) = grants(&order1);
+            assert_eq!(readers.len(), 1);
+            assert!(writer == 1 || writer == 2);
+            assert!(!readers.contains(&writer));
+            writer_times.insert(writer);
+        }
+        assert_eq!(writer_times, HashSet::from([1, 2]));
+    }
+
+    #[test]
+    fn preference() {
+        for seed in 0..10 {
+            let writers = order(seed, Preference::Writers);
+            assert_eq!(writers[0].0, 0);
+            assert_eq!(grants(&writers), (1, HashSet::from([2])));
+            let readers = order(seed, Preference::Readers);
+            assert_eq!(readers[4].0, 0);
+            assert_eq!(grants(&readers), (2, HashSet::from([1])));
+        }
+    }
+
+    #[test]
+    fn kill_releases_lock() {
+        let runtime = Runtime::new();
+        let node = runtime.create_node().build();
+        runtime.block_on(async move {
+            let lock = Arc::new(RwLock::new(0));
+            let lock0 = lock.clone();
+            node.spawn(async move {
+                let _guard = lock0.write().await;
+                std::future::pending::<()>().await;
+            });
+            sleep(Duration::from_secs(1)).await;
+            assert!(lock.try_read().is_err());
+            Handle::current().kill(node.id());
+            *lock.write().await += 1;
+            assert_eq!(*lock.read().await, 1);
+        });
+    }
+}
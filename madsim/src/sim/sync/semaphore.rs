@@ -0,0 +1,395 @@
+use crate::{rand::Rng, task::coop};
+use spin::Mutex;
+use std::{
+    collections::BTreeMap,
+    fmt,
+    future::poll_fn,
+    task::{ready, Poll, Waker},
+};
+
+/// Counting semaphore performing asynchronous permit acquisition.
+///
+/// When permits are released and several tasks are waiting, the permits are granted to
+/// a waiter chosen by the global random number generator instead of FIFO.
+/// Only the waiters whose requests can be satisfied are considered.
+///
+/// Permits held by a task are released when the task is killed.
+///
+/// # Example
+///
+/// ```
+/// use madsim::{runtime::Runtime, sync::Semaphore};
+///
+/// Runtime::new().block_on(async {
+///     let semaphore = Semaphore::new(3);
+///     let a = semaphore.acquire().await.unwrap();
+///     let b = semaphore.acquire_many(2).await.unwrap();
+///     assert_eq!(semaphore.available_permits(), 0);
+///     assert!(semaphore.try_acquire().is_err());
+///     drop(a);
+///     assert_eq!(semaphore.available_permits(), 1);
+/// });
+/// ```
+pub struct Semaphore {
+    state: Mutex<State>,
+}
+
+struct State {
+    permits: usize,
+    closed: bool,
+    waiters: BTreeMap<u64, Waiter>,
+    next_id: u64,
+}
+
+struct Waiter {
+    permits: usize,
+    waker: Waker,
+    granted: bool,
+}
+
+/// A permit from the semaphore. The permit is released when dropped.
+#[must_use]
+pub struct SemaphorePermit<'a> {
+    sem: &'a Semaphore,
+    permits: u32,
+}
+
+/// Error returned from [`Semaphore::acquire`] if the semaphore has been closed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AcquireError(());
+
+/// Error returned from [`Semaphore::try_acquire`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TryAcquireError {
+    /// The semaphore has been closed.
+    Closed,
+    /// The semaphore has no available permits.
+    NoPermits,
+}
+
+impl Semaphore {
+    /// Creates a new semaphore with the initial number of permits.
+    pub fn new(permits: usize) -> Self {
+        Semaphore {
+            state: Mutex::new(State {
+                permits,
+                closed: false,
+                waiters: BTreeMap::new(),
+                next_id: 0,
+            }),
+        }
+    }
+
+    /// Returns the current number of available permits.
+    pub fn available_permits(&self) -> usize {
+        self.state.lock().permits
+    }
+
+    /// Adds `n` new permits to the semaphore.
+    pub fn add_permits(&self, n: usize) {
+        let mut state = self.state.lock();
+        state.permits += n;
+        state.grant();
+    }
+
+    /// Acquires a permit from the semaphore.
+    pub async fn acquire(&self) -> Result<SemaphorePermit<'_>, AcquireError> {
+        self.acquire_many(1).await
+    }
+
+    /// Acquires `n` permits from the semaphore.
+    pub async fn acquire_many(&self, n: u32) -> Result<SemaphorePermit<'_>, AcquireError> {
+        let mut guard = AcquireGuard {
+            sem: self,
+            id: None,
+        };
+        poll_fn(|cx| {
+            let coop = ready!(coop::poll_proceed(cx));
+            let mut state = self.state.lock();
+            if state.closed {
+                coop.made_progress();
+                return Poll::Ready(Err(AcquireError(())));
+            }
+            if let Some(id) = guard.id {
+                let waiter = state.waiters.get_mut(&id).unwrap();
+                if !waiter.granted {
+                    waiter.waker = cx.waker().clone();
+                    return Poll::Pending;
+                }
+                state.waiters.remove(&id);
+                guard.id = None;
+            } else if state.permits >= n as usize {
+                state.permits -= n as usize;
+            } else {
+                let id = state.next_id;
+                state.next_id += 1;
+                let waiter = Waiter {
+                    permits: n as usize,
+                    waker: cx.waker().clone(),
+                    granted: false,
+                };
+                state.waiters.insert(id, waiter);
+                guard.id = Some(id);
+                return Poll::Pending;
+            }
+            coop.made_progress();
+            Poll::Ready(Ok(SemaphorePermit {
+                sem: self,
+                permits: n,
+            }))
+        })
+        .await
+    }
+
+    /// Tries to acquire a permit from the semaphore without waiting.
+    pub fn try_acquire(&self) -> Result<SemaphorePermit<'_>, TryAcquireError> {
+        self.try_acquire_many(1)
+    }
+
+    /// Tries to acquire `n` permits from the semaphore without waiting.
+    pub fn try_acquire_many(&self, n: u32) -> Result<SemaphorePermit<'_>, TryAcquireError> {
+        let mut state = self.state.lock();
+        if state.closed {
+            return Err(TryAcquireError::Closed);
+        }
+        if state.permits < n as usize {
+            return Err(TryAcquireError::NoPermits);
+        }
+        state.permits -= n as usize;
+        Ok(SemaphorePermit {
+            sem: self,
+            permits: n,
+        })
+    }
+
+    /// Closes the semaphore, making all pending and future acquisitions fail.
+    pub fn close(&self) {
+        let mut state = self.state.lock();
+        state.closed = true;
+        for (_, waiter) in std::mem::take(&mut state.waiters) {
+            if waiter.granted {
+                state.permits += waiter.permits;
+            }
+            waiter.waker.wake();
+        }
+    }
+
+    /// Returns `true` if the semaphore is closed.
+    pub fn is_closed(&self) -> bool {
+        self.state.lock().closed
+    }
+
+    fn release(&self, n: usize) {
+        let mut state = self.state.lock();
+        state.permits += n;
+        state.grant();
+    }
+}
+
+impl State {
+    /// Grants permits to random waiters until no waiter can be satisfied.
+    fn grant(&mut self) {
+        loop {
+            let candidates = (self.waiters.iter())
+                .filter(|(_, w)| !w.granted && w.permits <= self.permits)
+                .map(|(id, _)| *id)
+                .collect::<Vec<_>>();
+            if candidates.is_empty() {
+                return;
+            }
+            let i = crate::rand::thread_rng().gen_range(0..candidates.len());
+            let waiter = self.waiters.get_mut(&candidates[i]).unwrap();
+            waiter.granted = true;
+            self.permits -= waiter.permits;
+            waiter.waker.wake_by_ref();
+        }
+    }
+}
+
+/// Removes the waiter and returns the granted permits if the acquisition is cancelled.
+struct AcquireGuard<'a> {
+    sem: &'a Semaphore,
+    id: Option<u64>,
+}
+
+impl Drop for AcquireGuard<'_> {
+    fn drop(&mut self) {
+        let Some(id) = self.id else {
+            return;
+        };
+        let mut state = self.sem.state.lock();
+        if let Some(waiter) = state.waiters.remove(&id) {
+            if waiter.granted {
+                state.permits += waiter.permits;
+                state.grant();
+            }
+        }
+    }
+}
+
+impl SemaphorePermit<'_> {
+    /// Forgets the permit without releasing it back to the semaphore.
+    pub fn forget(mut self) {
+        self.permits = 0;
+    }
+
+    /// Returns the number of permits held by `self`.
+    pub fn num_permits(&self) -> usize {
+        self.permits as usize
+    }
+}
+
+impl Drop for SemaphorePermit<'_> {
+    fn drop(&mut self) {
+        if self.permits > 0 {
+            self.sem.release(self.permits as usize);
+        }
+    }
+}
+
+impl fmt::Debug for Semaphore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = self.state.lock();
+        f.debug_struct("Semaphore")
+            .field("permits", &state.permits)
+            .field("waiters", &state.waiters.len())
+            .field("closed", &state.closed)
+            .finish()
+    }
+}
+
+impl fmt::Debug for SemaphorePermit<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SemaphorePermit")
+            .field("permits", &self.permits)
+            .finish()
+    }
+}
+
+impl fmt::Display for AcquireError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "semaphore closed")
+    }
+}
+
+impl std::error::Error for AcquireError {}
+
+impl fmt::Display for TryAcquireError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TryAcquireError::Closed => write!(f, "semaphore closed"),
+            TryAcquireError::NoPermits => write!(f, "no permits available"),
+        }
+    }
+}
+
+impl std::error::Error for TryAcquireError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        runtime::{Handle, Runtime},
+        time::{sleep, Duration, Instant},
+    };
+    use std::{collections::HashSet, sync::Arc};
+
+    #[test]
+    fn grant_order() {
+        let order = |seed| {
+            let runtime = Runtime::with_seed_and_config(seed, crate::Config::default());
+            runtime.block_on(async {
+                let semaphore = Arc::new(Semaphore::new(1));
+                let queued = Arc::new(Mutex::new(vec![]));
+                let granted = Arc::new(Mutex::new(vec![]));
+                let permit = semaphore.acquire().await.unwrap();
+                let t0 = Instant::now();
+                let tasks = (0..8)
+                    .map(|i| {
+                        let semaphore = semaphore.clone();
+                        let queued = queued.clone();
+                        let granted = granted.clone();
+                        crate::task::spawn(async move {
+                            queued.lock().push(i);
+                            let _permit = semaphore.acquire().await.unwrap();
+                            granted.lock().push((i, t0.elapsed().as_secs()));
+                            sleep(Duration::from_secs(1)).await;
+                        })
+                    })
+                    .collect::<Vec<_>>();
+                sleep(Duration::from_secs(1)).await;
+                drop(permit);
+                for task in tasks {
+                    task.await.unwrap();
+                }
+                let queued = queued.lock().clone();
+                let granted = granted.lock().clone();
+                (queued, granted)
+            })
+        };
+        let mut orders = HashSet::new();
+        for seed in 0..10 {
+            let (queued, granted) = order(seed);
+            assert_eq!((queued.clone(), granted.clone()), order(seed));
+            // the single permit is granted to one waiter at a time, once each
+            let times = granted.iter().map(|(_, t)| *t).collect::<Vec<_>>();
+            assert_eq!(times, (1..9).collect::<Vec<_>>());
+            let mut waiters = granted.iter().map(|(i, _)| *i).collect::<Vec<_>>();
+            waiters.sort();
+            assert_eq!(waiters, (0..8).collect::<Vec<_>>());
+            // the position in the queue of each granted waiter
+            let positions = granted
+                .iter()
+                .map(|(i, _)| queued.iter().position(|q| q == i).unwrap())
+                .collect::<Vec<_>>();
+            orders.insert(positions);
+        }
+        // the waiters are not granted in the order they queued
+        assert_eq!(orders.len(), 10);
+    }
+
+    #[test]
+    fn acquire_many() {
+        let runtime = Runtime::new();
+        runtime.block_on(async {
+            let semaphore = Arc::new(Semaphore::new(2));
+            let permit = semaphore.acquire_many(2).await.unwrap();
+            assert_eq!(permit.num_permits(), 2);
+            let sem = semaphore.clone();
+            let task = crate::task::spawn(async move {
+                sem.acquire_many(3).await.unwrap().forget();
+            });
+            sleep(Duration::from_secs(1)).await;
+            drop(permit);
+            assert!(!task.is_finished());
+            semaphore.add_permits(1);
+            task.await.unwrap();
+            assert_eq!(semaphore.available_permits(), 0);
+
+            semaphore.close();
+            assert_eq!(semaphore.acquire().await.unwrap_err(), AcquireError(()));
+            assert_eq!(
+                semaphore.try_acquire().unwrap_err(),
+                TryAcquireError::Closed
+            );
+        });
+    }
+
+    #[test]
+    fn kill_releases_permits() {
+        let runtime = Runtime::new();
+        let node = runtime.create_node().build();
+        runtime.block_on(async move {
+            let semaphore = Arc::new(Semaphore::new(1));
+            let sem = semaphore.clone();
+            node.spawn(async move {
+                let _permit = sem.acquire().await.unwrap();
+                std::future::pending::<()>().await;
+            });
+            sleep(Duration::from_secs(1)).await;
+            assert_eq!(semaphore.available_permits(), 0);
+            Handle::current().kill(node.id());
+            let _permit = semaphore.acquire().await.unwrap();
+        });
+    }
+}
@@ -2,41 +2,354 @@
 
 use super::{
     rand::GlobalRng,
-    time::{TimeHandle, TimeRuntime},
+    time::{error::Elapsed, ClockMode, TimeHandle, TimeRuntime},
     utils::mpsc,
 };
 use async_task::{FallibleTask, Runnable};
-use rand::Rng;
+use futures_util::{FutureExt, Stream};
+use rand::{Rng, RngCore};
+use serde::{Deserialize, Serialize};
 use spin::Mutex;
 use std::{
-    collections::HashMap,
+    cell::{Cell, RefCell},
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque},
     fmt,
     future::Future,
+    hash::{Hash, Hasher},
     io,
-    ops::Deref,
+    ops::{Deref, Range},
+    panic::AssertUnwindSafe,
     pin::Pin,
+    rc::Rc,
     sync::{
-        atomic::{AtomicBool, AtomicU64, Ordering},
-        Arc,
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+        Arc, Weak,
     },
-    task::{Context, Poll},
-    time::Duration,
+    task::{Context, Poll, Waker},
+    time::{Duration, Instant},
 };
-use tracing::*;
+use tokio::sync::Notify;
+use tracing::{level_filters::LevelFilter, *};
 
-pub use tokio::task::yield_now;
+pub use self::builder::Builder;
+pub use self::coop::{consume_budget, yield_for, yield_now};
+pub use self::join_all::{join_all, JoinAll};
+pub use self::join_set::JoinSet;
+pub use self::task_local::{AccessError, LocalKey, TaskLocalFuture};
+
+mod builder;
+pub(crate) mod coop;
+mod join_all;
+mod join_set;
+mod task_local;
 
 pub(crate) struct Executor {
     queue: mpsc::Receiver<(Runnable, Arc<TaskInfo>)>,
     handle: TaskHandle,
     rand: GlobalRng,
     time: TimeRuntime,
-    time_limit: Option<Duration>,
+    time_limit: Cell<Option<Duration>>,
+    config: Config,
+    /// A hook called before each poll of a task.
+    poll_hook: RefCell<Option<PollHook>>,
+    /// A distribution of the time spent on each poll. Overrides `Config::poll_duration`.
+    poll_latency: RefCell<Option<PollLatency>>,
+    /// A flag indicating that a future is being blocked on.
+    running: Cell<bool>,
+    /// The remaining entries of the trace being replayed.
+    replay: RefCell<VecDeque<ScheduleEntry>>,
+}
+
+type PollHook = Box<dyn Fn(TaskPollEvent)>;
+type PollLatency = Box<dyn Fn(&mut dyn RngCore) -> Duration>;
+
+thread_local! {
+    /// The executors created on this thread, found by [`TaskHandle::block_on`].
+    static EXECUTORS: RefCell<Vec<std::rc::Weak<Executor>>> = const { RefCell::new(vec![]) };
+}
+
+/// An event emitted before each poll of a task.
+#[cfg_attr(docsrs, doc(cfg(madsim)))]
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct TaskPollEvent {
+    /// The ID of the task.
+    pub task: Id,
+    /// The node where the task runs.
+    pub node: NodeId,
+    /// The current simulated time.
+    pub time: Instant,
+}
+
+/// An event emitted on a lifecycle transition of a node.
+#[cfg_attr(docsrs, doc(cfg(madsim)))]
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct NodeEvent {
+    /// The node.
+    pub node: NodeId,
+    /// The kind of the transition.
+    pub kind: NodeEventKind,
+    /// The current simulated time.
+    pub time: Instant,
+}
+
+/// The kind of [`NodeEvent`].
+#[cfg_attr(docsrs, doc(cfg(madsim)))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NodeEventKind {
+    /// The node is created.
+    Created,
+    /// The node is killed. Also emitted before [`Restarted`](NodeEventKind::Restarted).
+    Killed,
+    /// The node is restarted.
+    Restarted,
+    /// The node is paused.
+    Paused,
+    /// The node is resumed.
+    Resumed,
+    /// The node is killed because it exceeded its memory limit.
+    OomKilled,
+}
+
+/// The maximum number of events buffered in a [`NodeEvents`] stream.
+///
+/// When the buffer is full, the oldest event is dropped.
+pub const NODE_EVENTS_CAPACITY: usize = 1024;
+
+/// A stream of [`NodeEvent`]s, returned from
+/// [`Handle::node_events`](crate::runtime::Handle::node_events).
+///
+/// The stream never ends. Events emitted while the stream is not polled are buffered
+/// up to [`NODE_EVENTS_CAPACITY`]. Emitting an event never blocks: when the buffer is full,
+/// the oldest event is dropped and counted by [`dropped`](Self::dropped).
+#[cfg_attr(docsrs, doc(cfg(madsim)))]
+pub struct NodeEvents {
+    buffer: Arc<Mutex<NodeEventBuffer>>,
+}
+
+#[derive(Default)]
+struct NodeEventBuffer {
+    events: VecDeque<NodeEvent>,
+    dropped: u64,
+    waker: Option<Waker>,
+}
+
+impl NodeEvents {
+    /// Returns the number of events dropped because the buffer was full.
+    pub fn dropped(&self) -> u64 {
+        self.buffer.lock().dropped
+    }
+}
+
+impl Stream for NodeEvents {
+    type Item = NodeEvent;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<NodeEvent>> {
+        let mut buffer = self.buffer.lock();
+        match buffer.events.pop_front() {
+            Some(event) => Poll::Ready(Some(event)),
+            None => {
+                buffer.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+impl fmt::Debug for NodeEvents {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let buffer = self.buffer.lock();
+        f.debug_struct("NodeEvents")
+            .field("buffered", &buffer.events.len())
+            .field("dropped", &buffer.dropped)
+            .finish()
+    }
+}
+
+/// Task scheduler configurations.
+#[cfg_attr(docsrs, doc(cfg(madsim)))]
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct Config {
+    /// The range of simulated time advanced after each poll of a task.
+    ///
+    /// An empty range means polling a task takes no time.
+    #[serde(default = "default_poll_duration")]
+    pub poll_duration: Range<Duration>,
+
+    /// Whether to advance simulated time after each poll of a task.
+    ///
+    /// If disabled, neither [`poll_duration`](Self::poll_duration) nor a custom
+    /// [poll latency](crate::runtime::Runtime::set_poll_latency) is applied, and time only
+    /// advances to fire timers. The elapsed time then only comes from sleeps and timeouts,
+    /// except for a 50ns margin added each time the clock jumps to a timer.
+    #[serde(default = "default_advance_time_on_poll")]
+    pub advance_time_on_poll: bool,
+
+    /// The policy to choose the next task from the ready queue.
+    #[serde(default)]
+    pub scheduling: Scheduling,
+
+    /// Model the parallelism of multi-core nodes.
+    ///
+    /// If enabled, the time advanced after polling a task is divided by the number of cores
+    /// of its node. This is an approximation that up to `cores` tasks can run concurrently,
+    /// tasks are still polled one by one.
+    #[serde(default)]
+    pub model_cpu_parallelism: bool,
+
+    /// The range of simulated time taken by each blocking operation in [`spawn_blocking`].
+    ///
+    /// An empty range means blocking operations take no time.
+    #[serde(default)]
+    pub blocking_op_duration: Range<Duration>,
+
+    /// The maximum number of [`spawn_blocking`] closures running at the same time on each node.
+    ///
+    /// This models the bounded blocking thread pool of a production runtime. Excess closures
+    /// queue until a slot is freed, and the next one is chosen by the global random number
+    /// generator. `None` means unbounded. Must not be zero.
+    #[serde(default)]
+    pub blocking_pool_size: Option<usize>,
+
+    /// Possibility of deferring a ready task behind others instead of running it immediately.
+    ///
+    /// This amplifies the diversity of schedules. A task is never deferred more than
+    /// [`MAX_DEFERRALS`] times in a row. Must be in range `[0, 1]`.
+    #[serde(default)]
+    pub reorder_probability: f64,
+
+    /// The budget of each poll of a task, like the cooperative scheduling of tokio.
+    ///
+    /// Each ready leaf future (e.g. an elapsed [`sleep`](crate::time::sleep), a
+    /// [`JoinHandle`], a receive from a [`sync`](crate::sync) channel or
+    /// [`consume_budget`]) consumes one unit. Once the budget is exhausted, the task is
+    /// forced to yield to the scheduler. `None` means unconstrained.
+    #[serde(default)]
+    pub task_budget: Option<u32>,
+
+    /// Log a warning when a [`JoinHandle`] is dropped before its task completes.
+    ///
+    /// Dropping a `JoinHandle` silently detaches the task, which may hide a forgotten
+    /// `.await`. Use [`JoinHandle::detach`] to detach a task explicitly without warning.
+    #[serde(default)]
+    pub strict_join_handles: bool,
+
+    /// Record each scheduling decision of the executor.
+    ///
+    /// The trace can be retrieved by [`Handle::take_trace`](crate::runtime::Handle::take_trace)
+    /// and replayed with [`replay_trace`](Self::replay_trace).
+    #[serde(default)]
+    pub record_trace: bool,
+
+    /// A trace of scheduling decisions to follow instead of the random number generator.
+    ///
+    /// The executor picks the tasks from the ready queue in the recorded order, and defers
+    /// them as recorded. This reproduces a schedule even if code changes alter the random
+    /// values consumed elsewhere. Once the recorded task is not in the ready queue, the
+    /// schedule has diverged: a warning is logged and the rest of the trace is ignored.
+    /// Task IDs are assigned in spawn order, so the trace only matches the same test.
+    #[serde(default)]
+    pub replay_trace: Option<Vec<ScheduleEntry>>,
+
+    /// Detect deadlocks on [`sync::Mutex`](crate::sync::Mutex).
+    ///
+    /// A task about to wait for a lock panics if this would close a cycle of tasks waiting
+    /// for locks held by each other. The panic message names the tasks and locks involved.
+    #[serde(default)]
+    pub detect_deadlocks: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            poll_duration: default_poll_duration(),
+            advance_time_on_poll: default_advance_time_on_poll(),
+            scheduling: Scheduling::default(),
+            model_cpu_parallelism: false,
+            blocking_op_duration: Duration::ZERO..Duration::ZERO,
+            blocking_pool_size: None,
+            reorder_probability: 0.0,
+            task_budget: None,
+            strict_join_handles: false,
+            record_trace: false,
+            replay_trace: None,
+            detect_deadlocks: false,
+        }
+    }
+}
+
+impl Hash for Config {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        // `strict_join_handles` and `record_trace` only affect diagnostics
+        self.poll_duration.hash(state);
+        self.advance_time_on_poll.hash(state);
+        self.scheduling.hash(state);
+        self.model_cpu_parallelism.hash(state);
+        self.blocking_op_duration.hash(state);
+        self.blocking_pool_size.hash(state);
+        self.reorder_probability.to_bits().hash(state);
+        self.task_budget.hash(state);
+        self.replay_trace.hash(state);
+        self.detect_deadlocks.hash(state);
+    }
+}
+
+/// A scheduling decision of the executor, recorded with [`Config::record_trace`].
+#[cfg_attr(docsrs, doc(cfg(madsim)))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ScheduleEntry {
+    /// The task picked from the ready queue.
+    pub task: Id,
+    /// The node of the task.
+    pub node: NodeId,
+    /// What the executor did with the task.
+    pub action: ScheduleAction,
+}
+
+/// What the executor did with a task picked from the ready queue.
+#[cfg_attr(docsrs, doc(cfg(madsim)))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScheduleAction {
+    /// The task was polled.
+    Poll,
+    /// The task was pushed back to the ready queue.
+    Defer,
+    /// The task was moved aside because its node is paused.
+    Park,
+    /// The task was dropped because its node was killed.
+    Discard,
+}
+
+/// The maximum number of times a ready task can be deferred in a row.
+///
+/// See [`Config::reorder_probability`].
+pub const MAX_DEFERRALS: usize = 8;
+
+/// The policy to choose the next task from the ready queue.
+#[cfg_attr(docsrs, doc(cfg(madsim)))]
+#[derive(Debug, Default, Serialize, Deserialize, PartialEq, Eq, Hash, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum Scheduling {
+    /// Choose a random task. This helps to find bugs caused by the ordering of tasks.
+    #[default]
+    Random,
+    /// Choose the earliest woken task, like most production runtimes.
+    Fifo,
+}
+
+const fn default_poll_duration() -> Range<Duration> {
+    Duration::from_nanos(50)..Duration::from_nanos(100)
+}
+
+const fn default_advance_time_on_poll() -> bool {
+    true
 }
 
 /// A unique identifier for a node.
 #[cfg_attr(docsrs, doc(cfg(madsim)))]
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy)]
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy, Serialize, Deserialize)]
 pub struct NodeId(u64);
 
 impl fmt::Display for NodeId {
@@ -49,61 +362,242 @@ impl NodeId {
     pub(crate) const fn zero() -> Self {
         NodeId(0)
     }
+
+    /// Creates a `NodeId` from its raw value.
+    ///
+    /// The main node has ID 0, and created nodes are numbered from 1.
+    pub const fn from_raw(id: u64) -> Self {
+        NodeId(id)
+    }
+
+    /// Returns the raw value of this `NodeId`.
+    pub const fn as_raw(&self) -> u64 {
+        self.0
+    }
+}
+
+/// A unique identifier for a group of nodes.
+///
+/// See [`Handle::create_group`](crate::runtime::Handle::create_group).
+#[cfg_attr(docsrs, doc(cfg(madsim)))]
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy)]
+pub struct GroupId(usize);
+
+impl fmt::Display for GroupId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
 }
 
 pub(crate) struct TaskInfo {
     pub id: Id,
+    /// The name of this task.
+    pub name: Option<String>,
     pub node: Arc<NodeInfo>,
     /// The span of this task.
     span: Span,
+    /// Task-local values.
+    locals: task_local::Locals,
+    /// A flag indicating that the task panicked.
+    panicked: Arc<AtomicBool>,
+    /// A flag indicating that a panic of the task is reported to a handler instead of
+    /// unwinding the runtime.
+    catch_panic: bool,
+    /// The number of times the task has been deferred in a row.
+    deferrals: AtomicUsize,
+    /// Ready tasks with higher priority run first.
+    priority: u8,
+    /// A flag indicating that the task has been aborted by an [`AbortHandle`].
+    aborted: AtomicBool,
 }
 
 pub(crate) struct NodeInfo {
     pub id: NodeId,
     pub name: String,
     /// The number of CPU cores.
-    pub cores: usize,
+    cores: AtomicUsize,
+    /// The offset of the local clock ahead of the global clock.
+    pub clock_skew: Duration,
+    /// The latest local time observed on this node, in nanoseconds since the clock base.
+    pub last_now: AtomicU64,
     /// A flag indicating that the task should be paused.
     paused: AtomicBool,
     /// A flag indicating that the task should no longer be executed.
     killed: AtomicBool,
+    /// The number of alive tasks.
+    task_count: AtomicUsize,
+    /// The maximum number of simultaneously alive tasks.
+    max_task_count: AtomicUsize,
+    /// The limit of alive tasks, beyond which spawning panics.
+    max_tasks: AtomicUsize,
+    /// The IDs and names of alive tasks.
+    alive_tasks: Mutex<BTreeMap<Id, Option<String>>>,
+    /// Alive tasks that will panic at the next poll.
+    injected_panics: Mutex<HashSet<Id>>,
+    /// The wakers of alive tasks, used to drop them when the node is killed.
+    wakers: Mutex<BTreeMap<Id, Waker>>,
+    /// The number of bytes recorded by [`record_alloc`] and [`record_free`].
+    memory: AtomicUsize,
+    /// The maximum number of bytes before the node is killed.
+    memory_limit: AtomicUsize,
+    /// A flag indicating that the node is shutting down.
+    shutting_down: AtomicBool,
+    /// Notified when all tasks of the node are finished.
+    idle: Notify,
+    /// A flag indicating that the initial task has been polled for the first time.
+    ready: AtomicBool,
+    /// Notified when the node becomes ready.
+    ready_notify: Notify,
+    /// The root cancellation token, cancelled when the node is killed.
+    pub token: crate::sync::CancellationToken,
+    /// The slots of the blocking pool, created on the first [`spawn_blocking`].
+    blocking_pool: std::sync::OnceLock<crate::sync::Semaphore>,
+    /// The log level set by [`TaskHandle::set_node_log_level`].
+    log_level: Mutex<Option<LevelFilter>>,
+    /// The cores occupied by [`simulate_cpu_work`] and the tasks waiting for them.
+    cpu: Mutex<CpuState>,
+    /// The ID of the next task, shared by all nodes of the runtime.
+    next_task_id: Arc<AtomicU64>,
     /// The span of this node.
     span: Span,
 }
 
+/// The cores of a node occupied by [`simulate_cpu_work`].
+#[derive(Default)]
+struct CpuState {
+    /// The tasks occupying a core.
+    busy: BTreeSet<Id>,
+    /// The tasks waiting for a free core.
+    waiting: Vec<(Runnable, Arc<TaskInfo>)>,
+}
+
 impl NodeInfo {
-    fn new_task(self: &Arc<Self>) -> Arc<TaskInfo> {
-        let id = Id::new();
+    fn new(
+        id: NodeId,
+        name: String,
+        cores: usize,
+        clock_skew: Duration,
+        next_task_id: Arc<AtomicU64>,
+    ) -> Self {
+        NodeInfo {
+            span: error_span!(parent: None, "node", %id, name),
+            id,
+            name,
+            cores: AtomicUsize::new(cores),
+            clock_skew,
+            last_now: AtomicU64::new(0),
+            paused: AtomicBool::new(false),
+            killed: AtomicBool::new(false),
+            task_count: AtomicUsize::new(0),
+            max_task_count: AtomicUsize::new(0),
+            max_tasks: AtomicUsize::new(usize::MAX),
+            alive_tasks: Default::default(),
+            injected_panics: Default::default(),
+            wakers: Default::default(),
+            memory: AtomicUsize::new(0),
+            memory_limit: AtomicUsize::new(usize::MAX),
+            shutting_down: AtomicBool::new(false),
+            idle: Notify::new(),
+            ready: AtomicBool::new(true),
+            ready_notify: Notify::new(),
+            token: Default::default(),
+            blocking_pool: Default::default(),
+            log_level: Mutex::new(None),
+            cpu: Default::default(),
+            next_task_id,
+        }
+    }
+
+    /// Returns the log level set for this node.
+    pub fn log_level(&self) -> Option<LevelFilter> {
+        *self.log_level.lock()
+    }
+
+    /// Returns the number of CPU cores.
+    pub fn cores(&self) -> usize {
+        self.cores.load(Ordering::SeqCst)
+    }
+
+    /// Returns `true` if all cores are occupied by [`simulate_cpu_work`] of other tasks.
+    fn is_cpu_busy(&self, task: Id) -> bool {
+        let cpu = self.cpu.lock();
+        cpu.busy.len() >= self.cores() && !cpu.busy.contains(&task)
+    }
+
+    /// Returns `true` if the node has been killed.
+    pub fn is_killed(&self) -> bool {
+        self.killed.load(Ordering::SeqCst)
+    }
+
+    fn new_task(
+        self: &Arc<Self>,
+        name: Option<&str>,
+        priority: u8,
+        catch_panic: bool,
+    ) -> Arc<TaskInfo> {
+        let id = Id(self.next_task_id.fetch_add(1, Ordering::SeqCst));
+        // inherit task-local values from the parent task on the same node
+        let locals = match crate::context::try_current_task() {
+            Some(parent) if Arc::ptr_eq(&parent.node, self) => parent.locals.fork(),
+            _ => Default::default(),
+        };
         Arc::new(TaskInfo {
             id,
+            name: name.map(String::from),
             node: self.clone(),
-            span: error_span!(parent: &self.span, "task", %id),
+            span: error_span!(parent: &self.span, "task", %id, name),
+            locals,
+            panicked: Arc::new(AtomicBool::new(false)),
+            catch_panic,
+            deferrals: AtomicUsize::new(0),
+            priority,
+            aborted: AtomicBool::new(false),
         })
     }
 }
 
 impl Executor {
-    pub fn new(rand: GlobalRng) -> Self {
-        let (sender, queue) = mpsc::channel();
-        Executor {
+    pub fn new(rand: GlobalRng, config: Config) -> Rc<Self> {
+        let (sender, queue) = mpsc::channel(|(_, info): &(Runnable, Arc<TaskInfo>)| info.priority);
+        let time = TimeRuntime::new(&rand);
+        let next_task_id = Arc::new(AtomicU64::new(0));
+        let replay = config.replay_trace.clone().unwrap_or_default().into();
+        let executor = Rc::new(Executor {
             queue,
             handle: TaskHandle {
+                time: time.handle().clone(),
+                node_event_hook: Default::default(),
+                node_event_streams: Default::default(),
+                groups: Default::default(),
                 nodes: Arc::new(Mutex::new(HashMap::new())),
                 sender,
                 next_node_id: Arc::new(AtomicU64::new(1)),
-                main_info: Arc::new(NodeInfo {
-                    id: NodeId::zero(),
-                    name: "main".into(),
-                    cores: 1,
-                    paused: AtomicBool::new(false),
-                    killed: AtomicBool::new(false),
-                    span: error_span!("node", id = %NodeId::zero(), name = "main"),
-                }),
+                spawned_total: Default::default(),
+                main_info: Arc::new(NodeInfo::new(
+                    NodeId::zero(),
+                    "main".into(),
+                    1,
+                    Duration::ZERO,
+                    next_task_id.clone(),
+                )),
+                next_task_id,
+                trace: Default::default(),
             },
-            time: TimeRuntime::new(&rand),
+            time,
             rand,
-            time_limit: None,
-        }
+            time_limit: Cell::new(None),
+            config,
+            poll_hook: RefCell::new(None),
+            poll_latency: RefCell::new(None),
+            running: Cell::new(false),
+            replay: RefCell::new(replay),
+        });
+        EXECUTORS.with(|executors| {
+            let mut executors = executors.borrow_mut();
+            executors.retain(|e| e.strong_count() > 0);
+            executors.push(Rc::downgrade(&executor));
+        });
+        executor
     }
 
     pub fn handle(&self) -> &TaskHandle {
@@ -114,14 +608,71 @@ impl Executor {
         self.time.handle()
     }
 
-    pub fn set_time_limit(&mut self, limit: Duration) {
-        self.time_limit = Some(limit);
+    pub fn set_time_limit(&self, limit: Duration) {
+        self.time_limit.set(Some(limit));
+    }
+
+    pub fn set_poll_hook(&self, hook: impl Fn(TaskPollEvent) + 'static) {
+        *self.poll_hook.borrow_mut() = Some(Box::new(hook));
+    }
+
+    pub fn set_poll_latency(&self, latency: impl Fn(&mut dyn RngCore) -> Duration + 'static) {
+        *self.poll_latency.borrow_mut() = Some(Box::new(latency));
     }
 
     pub fn block_on<F: Future>(&self, future: F) -> F::Output {
+        self.block_on_inner(future, None).unwrap()
+    }
+
+    /// Blocks on the future with a time budget for this call only.
+    ///
+    /// The global time limit is ignored during this call.
+    pub fn block_on_timeout<F: Future>(
+        &self,
+        future: F,
+        limit: Duration,
+    ) -> Result<F::Output, Elapsed> {
+        let deadline = self.time.handle().elapsed() + limit;
+        self.block_on_inner(future, Some(deadline))
+    }
+
+    /// Blocks on the future until it completes or the elapsed time reaches `deadline`.
+    fn block_on_inner<F: Future>(
+        &self,
+        future: F,
+        deadline: Option<Duration>,
+    ) -> Result<F::Output, Elapsed> {
+        assert!(
+            !self.running.replace(true),
+            "can not block on a future while the runtime is running"
+        );
+        struct Reset<'a>(&'a Cell<bool>);
+        impl Drop for Reset<'_> {
+            fn drop(&mut self) {
+                self.0.set(false);
+            }
+        }
+        let _reset = Reset(&self.running);
+
+        // the future may borrow from the caller, but the cancelled task only drops it when
+        // it is scheduled again. so the task polls the future through a slot, which is
+        // emptied before returning or unwinding.
+        struct DropSlot<T>(Rc<RefCell<Option<T>>>);
+        impl<T> Drop for DropSlot<T> {
+            fn drop(&mut self) {
+                self.0.borrow_mut().take();
+            }
+        }
+        let slot = DropSlot(Rc::new(RefCell::new(Some(Box::pin(future)))));
+        let slot0 = slot.0.clone();
+        let future = std::future::poll_fn(move |cx| {
+            let mut slot = slot0.borrow_mut();
+            slot.as_mut().expect("future dropped").as_mut().poll(cx)
+        });
+
         // push the future into ready queue.
         let sender = self.handle.sender.clone();
-        let info = self.handle.main_info.new_task();
+        let info = self.handle.main_info.new_task(None, 0, false);
         let (runnable, mut task) = unsafe {
             // Safety: The schedule is not Sync,
             // the task's Waker must be used and dropped on the original thread.
@@ -138,11 +689,27 @@ impl Executor {
         loop {
             self.run_all_ready();
             if let Poll::Ready(val) = Pin::new(&mut task).poll(&mut cx) {
-                return val;
+                return Ok(val);
+            }
+            let going = self.time.advance_to_next_event_until(deadline);
+            match self.time.handle().mode() {
+                ClockMode::Frozen => panic!("clock is frozen, all tasks will block forever"),
+                ClockMode::Manual => {
+                    panic!("auto-advance is disabled, all tasks will block forever")
+                }
+                ClockMode::Paused | ClockMode::Running => {}
+            }
+            if !going {
+                panic!(
+                    "no events, all tasks will block forever\n{}",
+                    self.blocked_tasks()
+                );
             }
-            let going = self.time.advance_to_next_event();
-            assert!(going, "no events, all tasks will block forever");
-            if let Some(limit) = self.time_limit {
+            if let Some(deadline) = deadline {
+                if self.time.handle().elapsed() >= deadline {
+                    return Err(Elapsed);
+                }
+            } else if let Some(limit) = self.time_limit.get() {
                 assert!(
                     self.time.handle().elapsed() < limit,
                     "time limit exceeded: {:?}",
@@ -152,31 +719,203 @@ impl Executor {
         }
     }
 
+    /// Returns an error if any task is still queued or alive.
+    pub fn check_no_pending_tasks(&self) -> Result<(), PendingTasks> {
+        let mut queued = vec![];
+        let push = |queued: &mut Vec<_>, info: &TaskInfo| {
+            if !info.node.killed.load(Ordering::SeqCst) {
+                queued.push((info.id, info.node.id));
+            }
+        };
+        self.queue.for_each(|(_, info)| push(&mut queued, info));
+        let nodes = self.nodes.lock();
+        let mut alive = vec![];
+        let mut count = |info: &NodeInfo| match info.task_count.load(Ordering::SeqCst) {
+            0 => {}
+            n => alive.push((info.id, n)),
+        };
+        count(&self.main_info);
+        let mut ids = nodes.keys().copied().collect::<Vec<_>>();
+        ids.sort_unstable();
+        for id in ids {
+            let node = &nodes[&id];
+            node.paused
+                .iter()
+                .for_each(|(_, info)| push(&mut queued, info));
+            count(&node.info);
+        }
+        if queued.is_empty() && alive.is_empty() {
+            return Ok(());
+        }
+        Err(PendingTasks { queued, alive })
+    }
+
+    /// Returns a report of all alive tasks, with their IDs, nodes and names.
+    fn blocked_tasks(&self) -> String {
+        use std::fmt::Write;
+
+        let mut report = String::from("blocked tasks:");
+        let mut dump = |info: &NodeInfo| {
+            for (id, name) in info.alive_tasks.lock().iter() {
+                write!(report, "\n  task {id} on node {}", info.id).unwrap();
+                if let Some(name) = name {
+                    write!(report, " ({name:?})").unwrap();
+                }
+            }
+        };
+        dump(&self.main_info);
+        let nodes = self.nodes.lock();
+        let mut ids = nodes.keys().copied().collect::<Vec<_>>();
+        ids.sort_unstable();
+        for id in ids {
+            dump(&nodes[&id].info);
+        }
+        report
+    }
+
+    /// Pops the next task of the trace being replayed from the ready queue.
+    ///
+    /// Returns `None` if there is no trace to replay or the ready queue is empty.
+    fn replay_next(&self) -> Option<((Runnable, Arc<TaskInfo>), ScheduleAction)> {
+        let mut replay = self.replay.borrow_mut();
+        let entry = *replay.front()?;
+        if self.queue.is_empty() {
+            return None;
+        }
+        match self.queue.try_recv_by(|(_, info)| info.id == entry.task) {
+            Some(task) => {
+                replay.pop_front();
+                Some((task, entry.action))
+            }
+            None => {
+                warn!(
+                    task = %entry.task,
+                    remaining = replay.len(),
+                    "schedule diverged from the trace, stop replaying"
+                );
+                replay.clear();
+                None
+            }
+        }
+    }
+
     /// Drain all tasks from ready queue and run them.
     fn run_all_ready(&self) {
-        while let Ok((runnable, info)) = self.queue.try_recv_random(&self.rand) {
-            if info.node.killed.load(Ordering::SeqCst) {
-                // killed task: ignore
-                continue;
-            } else if info.node.paused.load(Ordering::SeqCst) {
-                // paused task: push to waiting list
-                let mut nodes = self.nodes.lock();
-                nodes
-                    .get_mut(&info.node.id)
-                    .unwrap()
-                    .paused
-                    .push((runnable, info));
-                continue;
-            }
-            // run the task
-            let _enter = info.span.clone().entered();
-            let _guard = crate::context::enter_task(info);
+        while self.step() {}
+    }
+
+    /// Pop one task from ready queue and run it.
+    ///
+    /// Returns `false` if the ready queue is empty.
+    pub fn step(&self) -> bool {
+        let (task, replayed) = match self.replay_next() {
+            Some((task, action)) => (Ok(task), Some(action)),
+            None => match self.config.scheduling {
+                Scheduling::Random => (self.queue.try_recv_random(&self.rand), None),
+                Scheduling::Fifo => (self.queue.try_recv(), None),
+            },
+        };
+        let (runnable, info) = match task {
+            Ok(task) => task,
+            Err(_) => return false,
+        };
+        let record = |action| {
+            if self.config.record_trace {
+                self.trace.lock().push(ScheduleEntry {
+                    task: info.id,
+                    node: info.node.id,
+                    action,
+                });
+            }
+        };
+        if info.node.killed.load(Ordering::SeqCst) || info.aborted.load(Ordering::SeqCst) {
+            // killed or aborted task: ignore
+            record(ScheduleAction::Discard);
+            return true;
+        } else if info.node.paused.load(Ordering::SeqCst) {
+            // paused task: push to waiting list
+            record(ScheduleAction::Park);
+            let mut nodes = self.nodes.lock();
+            nodes
+                .get_mut(&info.node.id)
+                .unwrap()
+                .paused
+                .push((runnable, info));
+            return true;
+        } else if info.node.is_cpu_busy(info.id) {
+            // all cores are busy: push to waiting list until one is free
+            record(ScheduleAction::Park);
+            let node = info.node.clone();
+            node.cpu.lock().waiting.push((runnable, info));
+            return true;
+        }
+        let p = self.config.reorder_probability;
+        let defer = match replayed {
+            Some(action) => action == ScheduleAction::Defer,
+            None => {
+                p > 0.0
+                    && info.deferrals.load(Ordering::SeqCst) < MAX_DEFERRALS
+                    && self.rand.with(|rng| rng.gen_bool(p))
+            }
+        };
+        if defer {
+            // deferred task: push back to ready queue and pick another
+            record(ScheduleAction::Defer);
+            info.deferrals.fetch_add(1, Ordering::SeqCst);
+            self.handle.sender.send((runnable, info)).unwrap();
+            return true;
+        }
+        record(ScheduleAction::Poll);
+        info.deferrals.store(0, Ordering::SeqCst);
+        if let Some(hook) = &*self.poll_hook.borrow() {
+            hook(TaskPollEvent {
+                task: info.id,
+                node: info.node.id,
+                time: self.time.now_instant(),
+            });
+        }
+        // run the task
+        let cores = info.node.cores() as u32;
+        let catch_panic = info.catch_panic;
+        let _enter = info.span.clone().entered();
+        let _guard = crate::context::enter_task(info);
+        let _budget = coop::budget(self.config.task_budget);
+        if catch_panic {
+            // the panic has been reported to the handler and the task has been closed
+            let _ = std::panic::catch_unwind(AssertUnwindSafe(|| runnable.run()));
+        } else {
             runnable.run();
+        }
 
-            // advance time: 50-100ns
-            let dur = Duration::from_nanos(self.rand.with(|rng| rng.gen_range(50..100)));
+        // advance time: 50-100ns by default
+        let range = &self.config.poll_duration;
+        let poll_latency = self.poll_latency.borrow();
+        if self.config.advance_time_on_poll && (poll_latency.is_some() || !range.is_empty()) {
+            let mut dur = self.rand.with(|rng| match &*poll_latency {
+                Some(latency) => latency(rng),
+                None => rng.gen_range(range.clone()),
+            });
+            if self.config.model_cpu_parallelism {
+                dur /= cores;
+            }
             self.time.advance(dur);
         }
+        true
+    }
+}
+
+impl Drop for Executor {
+    fn drop(&mut self) {
+        // break the reference cycles between tasks and their nodes
+        // the wakers are dropped outside the lock since it may drop the tasks
+        let mut wakers = vec![std::mem::take(&mut *self.main_info.wakers.lock())];
+        let mut waiting = vec![std::mem::take(&mut self.main_info.cpu.lock().waiting)];
+        for node in self.nodes.lock().values() {
+            wakers.push(std::mem::take(&mut *node.info.wakers.lock()));
+            waiting.push(std::mem::take(&mut node.info.cpu.lock().waiting));
+        }
+        drop(wakers);
+        drop(waiting);
     }
 }
 
@@ -195,6 +934,19 @@ pub(crate) struct TaskHandle {
     next_node_id: Arc<AtomicU64>,
     /// Info of the main node.
     main_info: Arc<NodeInfo>,
+    /// The total number of tasks ever spawned.
+    spawned_total: Arc<AtomicU64>,
+    time: TimeHandle,
+    /// A hook called on each lifecycle transition of a node.
+    node_event_hook: Arc<Mutex<Option<NodeEventHook>>>,
+    /// Buffers of the alive [`NodeEvents`] streams.
+    node_event_streams: Arc<Mutex<Vec<Weak<Mutex<NodeEventBuffer>>>>>,
+    /// Groups of nodes indexed by [`GroupId`].
+    groups: Arc<Mutex<Vec<Group>>>,
+    /// The ID of the next task.
+    next_task_id: Arc<AtomicU64>,
+    /// The recorded scheduling decisions.
+    trace: Arc<Mutex<Vec<ScheduleEntry>>>,
 }
 
 struct Node {
@@ -202,41 +954,216 @@ struct Node {
     paused: Vec<(Runnable, Arc<TaskInfo>)>,
     /// A function to spawn the initial task.
     init: Option<InitFn>,
+    /// Whether the node has been killed and not restarted yet.
+    killed: bool,
+    /// The number of times the node has been killed while running.
+    crashes: u64,
+}
+
+struct Group {
+    name: String,
+    nodes: BTreeSet<NodeId>,
 }
 
 pub(crate) type InitFn = Arc<dyn Fn(&TaskNodeHandle)>;
 
+type NodeEventHook = Arc<dyn Fn(NodeEvent) + Send + Sync>;
+
 impl TaskHandle {
+    /// Blocks on the future with the executor of this handle.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the executor has been dropped or was created on another thread,
+    /// or if it is already running.
+    pub fn block_on<F: Future>(&self, future: F) -> F::Output {
+        let executor = EXECUTORS.with(|executors| {
+            (executors.borrow().iter())
+                .filter_map(|e| e.upgrade())
+                .find(|e| Arc::ptr_eq(&e.handle.main_info, &self.main_info))
+        });
+        let executor = executor.expect(
+            "the runtime has been dropped or is driven by another thread, can not block on it",
+        );
+        executor.block_on(future)
+    }
+
     /// Kill all tasks of the node.
     pub fn kill(&self, id: NodeId) {
-        debug!(node = %id, "kill");
+        self.kill_with(id, NodeEventKind::Killed);
+    }
+
+    /// Kill all tasks of the node because it exceeded its memory limit.
+    pub(crate) fn oom_kill(&self, id: NodeId) {
+        self.kill_with(id, NodeEventKind::OomKilled);
+    }
+
+    fn kill_with(&self, id: NodeId, kind: NodeEventKind) {
+        debug!(node = %id, ?kind, "kill");
         let mut nodes = self.nodes.lock();
         let node = nodes.get_mut(&id).expect("node not found");
-        node.paused.clear();
-        let new_info = Arc::new(NodeInfo {
+        let paused = std::mem::take(&mut node.paused);
+        let new_info = Arc::new(NodeInfo::new(
             id,
-            name: node.info.name.clone(),
-            cores: 1,
-            paused: AtomicBool::new(false),
-            killed: AtomicBool::new(false),
-            span: error_span!(parent: None, "node", %id, name = &node.info.name),
-        });
+            node.info.name.clone(),
+            node.info.cores(),
+            node.info.clock_skew,
+            self.next_task_id.clone(),
+        ));
+        let limit = node.info.memory_limit.load(Ordering::SeqCst);
+        new_info.memory_limit.store(limit, Ordering::SeqCst);
+        let max_tasks = node.info.max_tasks.load(Ordering::SeqCst);
+        new_info.max_tasks.store(max_tasks, Ordering::SeqCst);
+        *new_info.log_level.lock() = node.info.log_level();
         let old_info = std::mem::replace(&mut node.info, new_info);
         old_info.killed.store(true, Ordering::SeqCst);
+        if !node.killed {
+            node.crashes += 1;
+        }
+        node.killed = true;
+        drop(nodes);
+        self.time.remove_killed_timers();
+        old_info.token.cancel();
+        // wake up all tasks to move them into the ready queue
+        let wakers = std::mem::take(&mut *old_info.wakers.lock());
+        for waker in wakers.into_values() {
+            waker.wake();
+        }
+        // drop the futures now, outside the locks since their destructors may use the runtime.
+        // the running task, if it is on this node, is dropped by the executor after its poll.
+        let mut tasks = paused;
+        tasks.append(&mut old_info.cpu.lock().waiting);
+        tasks.extend(
+            self.sender
+                .remove_by(|(_, info)| Arc::ptr_eq(&info.node, &old_info)),
+        );
+        tasks.sort_by_key(|(_, info)| info.id);
+        drop(tasks);
+        self.emit_node_event(id, kind);
     }
 
     /// Kill all tasks of the node and restart the initial task.
     pub fn restart(&self, id: NodeId) {
         self.kill(id);
+        self.start(id);
+    }
+
+    /// Spawn the initial task of a killed node.
+    pub fn start(&self, id: NodeId) {
         debug!(node = %id, "restart");
-        let nodes = self.nodes.lock();
-        let node = nodes.get(&id).expect("node not found");
+        let mut nodes = self.nodes.lock();
+        let node = nodes.get_mut(&id).expect("node not found");
+        node.killed = false;
         if let Some(init) = &node.init {
             init(&TaskNodeHandle {
                 sender: self.sender.clone(),
                 info: node.info.clone(),
+                spawned_total: self.spawned_total.clone(),
             });
         }
+        drop(nodes);
+        self.emit_node_event(id, NodeEventKind::Restarted);
+    }
+
+    /// Kill all tasks of the node and restart with a new initial task.
+    ///
+    /// The new initial task replaces the old one in subsequent restarts.
+    pub fn restart_with(&self, id: NodeId, init: InitFn) {
+        let mut nodes = self.nodes.lock();
+        nodes.get_mut(&id).expect("node not found").init = Some(init);
+        drop(nodes);
+        self.restart(id);
+    }
+
+    /// Set a hook called on each lifecycle transition of a node.
+    pub fn set_node_event_hook(&self, hook: impl Fn(NodeEvent) + Send + Sync + 'static) {
+        *self.node_event_hook.lock() = Some(Arc::new(hook));
+    }
+
+    /// Returns a stream of subsequent lifecycle transitions of nodes.
+    pub fn node_events(&self) -> NodeEvents {
+        let buffer = Arc::new(Mutex::new(NodeEventBuffer::default()));
+        let mut streams = self.node_event_streams.lock();
+        streams.retain(|b| b.strong_count() > 0);
+        streams.push(Arc::downgrade(&buffer));
+        NodeEvents { buffer }
+    }
+
+    fn emit_node_event(&self, node: NodeId, kind: NodeEventKind) {
+        let event = NodeEvent {
+            node,
+            kind,
+            time: self.time.global_now_instant(),
+        };
+        let streams = self.node_event_streams.lock().clone();
+        for buffer in streams.iter().filter_map(Weak::upgrade) {
+            let mut buffer = buffer.lock();
+            if buffer.events.len() == NODE_EVENTS_CAPACITY {
+                buffer.events.pop_front();
+                buffer.dropped += 1;
+            }
+            buffer.events.push_back(event.clone());
+            if let Some(waker) = buffer.waker.take() {
+                waker.wake();
+            }
+        }
+        // call the hook without holding the lock, so that it can access the handle
+        let hook = self.node_event_hook.lock().clone();
+        if let Some(hook) = hook {
+            hook(event);
+        }
+    }
+
+    /// Make the task panic at its next poll.
+    ///
+    /// Does nothing if the task has finished.
+    pub fn inject_panic(&self, id: Id) {
+        debug!(task = %id, "inject panic");
+        let nodes = self.nodes.lock();
+        let infos = std::iter::once(&self.main_info).chain(nodes.values().map(|node| &node.info));
+        for info in infos {
+            if info.alive_tasks.lock().contains_key(&id) {
+                info.injected_panics.lock().insert(id);
+                return;
+            }
+        }
+    }
+
+    /// Notify all tasks of the node to shut down, and wait for them to finish.
+    pub async fn shutdown(&self, id: NodeId) {
+        debug!(node = %id, "shutdown");
+        let info = self
+            .nodes
+            .lock()
+            .get(&id)
+            .expect("node not found")
+            .info
+            .clone();
+        info.shutting_down.store(true, Ordering::SeqCst);
+        loop {
+            let idle = info.idle.notified();
+            if info.task_count.load(Ordering::SeqCst) == 0 {
+                return;
+            }
+            idle.await;
+        }
+    }
+
+    /// Set the log level of the node, consulted by [`NodeLogFilter`](crate::runtime::NodeLogFilter).
+    pub fn set_node_log_level(&self, id: NodeId, level: LevelFilter) {
+        let nodes = self.nodes.lock();
+        let node = nodes.get(&id).expect("node not found");
+        *node.info.log_level.lock() = Some(level);
+    }
+
+    /// Takes the scheduling decisions recorded so far.
+    pub fn take_trace(&self) -> Vec<ScheduleEntry> {
+        std::mem::take(&mut *self.trace.lock())
+    }
+
+    /// Returns the number of times the node has been killed while running.
+    pub fn crash_count(&self, id: NodeId) -> u64 {
+        self.nodes.lock().get(&id).expect("node not found").crashes
     }
 
     /// Pause all tasks of the node.
@@ -245,6 +1172,61 @@ impl TaskHandle {
         let nodes = self.nodes.lock();
         let node = nodes.get(&id).expect("node not found");
         node.info.paused.store(true, Ordering::SeqCst);
+        drop(nodes);
+        self.emit_node_event(id, NodeEventKind::Paused);
+    }
+
+    /// Returns `true` if the node is paused.
+    ///
+    /// Returns `false` for the main node or a node that does not exist.
+    pub fn is_paused(&self, id: NodeId) -> bool {
+        let nodes = self.nodes.lock();
+        nodes
+            .get(&id)
+            .is_some_and(|node| node.info.paused.load(Ordering::SeqCst))
+    }
+
+    /// Returns `true` if the node exists and has not been killed since the last restart.
+    ///
+    /// The main node is always alive.
+    pub fn is_alive(&self, id: NodeId) -> bool {
+        if id == NodeId::zero() {
+            return true;
+        }
+        let nodes = self.nodes.lock();
+        nodes.get(&id).is_some_and(|node| !node.killed)
+    }
+
+    /// Set the number of CPU cores of the node.
+    ///
+    /// The new value is observed by subsequent calls to [`std::thread::available_parallelism`]
+    /// from tasks on the node, and is kept after the node is killed or restarted.
+    pub fn set_cores(&self, id: NodeId, cores: usize) {
+        assert_ne!(cores, 0, "cores must be greater than 0");
+        debug!(node = %id, cores, "set cores");
+        let nodes = self.nodes.lock();
+        let node = nodes.get(&id).expect("node not found");
+        node.info.cores.store(cores, Ordering::SeqCst);
+    }
+
+    /// Set the memory limit of the node in bytes.
+    ///
+    /// The node is killed once the memory recorded by [`record_alloc`] exceeds the limit.
+    /// The limit is kept after the node is killed or restarted.
+    pub fn set_memory_limit(&self, id: NodeId, bytes: usize) {
+        let nodes = self.nodes.lock();
+        let node = nodes.get(&id).expect("node not found");
+        node.info.memory_limit.store(bytes, Ordering::SeqCst);
+    }
+
+    /// Set the maximum number of alive tasks of the node.
+    ///
+    /// Spawning a task beyond the limit panics. The limit is kept after the node is killed
+    /// or restarted.
+    pub fn set_max_tasks(&self, id: NodeId, max: usize) {
+        let nodes = self.nodes.lock();
+        let node = nodes.get(&id).expect("node not found");
+        node.info.max_tasks.store(max, Ordering::SeqCst);
     }
 
     /// Resume the execution of the address.
@@ -258,6 +1240,8 @@ impl TaskHandle {
         for (runnable, info) in node.paused.drain(..) {
             self.sender.send((runnable, info)).unwrap();
         }
+        drop(nodes);
+        self.emit_node_event(id, NodeEventKind::Resumed);
     }
 
     /// Create a new node.
@@ -266,21 +1250,24 @@ impl TaskHandle {
         name: Option<String>,
         init: Option<InitFn>,
         cores: Option<usize>,
+        clock_skew: Option<Duration>,
+        paused: bool,
     ) -> TaskNodeHandle {
         let id = NodeId(self.next_node_id.fetch_add(1, Ordering::SeqCst));
         debug!(node = %id, "create");
         let name = name.unwrap_or_else(|| format!("node-{}", id.0));
-        let info = Arc::new(NodeInfo {
-            span: error_span!(parent: None, "node", %id, name),
+        let info = Arc::new(NodeInfo::new(
             id,
             name,
-            cores: cores.unwrap_or(1),
-            paused: AtomicBool::new(false),
-            killed: AtomicBool::new(false),
-        });
+            cores.unwrap_or(1),
+            clock_skew.unwrap_or_default(),
+            self.next_task_id.clone(),
+        ));
+        info.paused.store(paused, Ordering::SeqCst);
         let handle = TaskNodeHandle {
             sender: self.sender.clone(),
             info: info.clone(),
+            spawned_total: self.spawned_total.clone(),
         };
         if let Some(init) = &init {
             init(&handle);
@@ -289,11 +1276,54 @@ impl TaskHandle {
             info,
             paused: vec![],
             init,
+            killed: false,
+            crashes: 0,
         };
         self.nodes.lock().insert(id, node);
+        self.emit_node_event(id, NodeEventKind::Created);
         handle
     }
 
+    /// Returns the IDs of all created nodes in ascending order.
+    ///
+    /// The main node is not included. Killed nodes are still listed since they can be restarted.
+    pub fn list_nodes(&self) -> Vec<NodeId> {
+        let mut ids = self.nodes.lock().keys().copied().collect::<Vec<_>>();
+        ids.sort_unstable();
+        ids
+    }
+
+    /// Returns the total number of alive tasks spawned on all nodes.
+    pub fn total_task_count(&self) -> usize {
+        let nodes = self.nodes.lock();
+        let count = |info: &NodeInfo| info.task_count.load(Ordering::SeqCst);
+        count(&self.main_info) + nodes.values().map(|node| count(&node.info)).sum::<usize>()
+    }
+
+    /// Returns the total number of tasks ever spawned on all nodes.
+    pub fn tasks_spawned_total(&self) -> u64 {
+        self.spawned_total.load(Ordering::SeqCst)
+    }
+
+    /// Returns the number of runnables in the ready queue.
+    pub fn ready_task_count(&self) -> usize {
+        self.sender.len()
+    }
+
+    /// Spawns a new task on the node.
+    ///
+    /// Returns `None` if the node does not exist or has been killed.
+    pub fn spawn_on<F>(&self, id: NodeId, future: F) -> Option<JoinHandle<F::Output>>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        if id != NodeId::zero() && self.nodes.lock().get(&id)?.killed {
+            return None;
+        }
+        Some(self.get_node(id)?.spawn(future))
+    }
+
     /// Get the node handle.
     pub fn get_node(&self, id: NodeId) -> Option<TaskNodeHandle> {
         let info = match id {
@@ -303,24 +1333,70 @@ impl TaskHandle {
         Some(TaskNodeHandle {
             sender: self.sender.clone(),
             info,
+            spawned_total: self.spawned_total.clone(),
         })
     }
-}
 
-/// A handle to spawn tasks on a node.
-#[derive(Clone)]
-pub struct TaskNodeHandle {
-    sender: mpsc::Sender<(Runnable, Arc<TaskInfo>)>,
+    /// Create an empty group of nodes.
+    pub fn create_group(&self, name: &str) -> GroupId {
+        let mut groups = self.groups.lock();
+        groups.push(Group {
+            name: name.into(),
+            nodes: BTreeSet::new(),
+        });
+        GroupId(groups.len() - 1)
+    }
+
+    /// Add a node to the group. A node can be in multiple groups.
+    pub fn add_node_to_group(&self, group: GroupId, node: NodeId) {
+        assert!(
+            self.nodes.lock().contains_key(&node),
+            "node not found: {node}"
+        );
+        let mut groups = self.groups.lock();
+        let group = groups.get_mut(group.0).expect("group not found");
+        group.nodes.insert(node);
+    }
+
+    /// Returns the name of the group, or `None` if the group does not exist.
+    pub fn group_name(&self, group: GroupId) -> Option<String> {
+        self.groups.lock().get(group.0).map(|g| g.name.clone())
+    }
+
+    /// Returns the nodes of the group in ascending order.
+    pub fn group_nodes(&self, group: GroupId) -> Vec<NodeId> {
+        let groups = self.groups.lock();
+        let group = groups.get(group.0).expect("group not found");
+        group.nodes.iter().copied().collect()
+    }
+
+    /// Returns the groups containing the node in ascending order.
+    pub fn node_groups(&self, node: NodeId) -> Vec<GroupId> {
+        let groups = self.groups.lock();
+        (groups.iter().enumerate())
+            .filter(|(_, g)| g.nodes.contains(&node))
+            .map(|(i, _)| GroupId(i))
+            .collect()
+    }
+}
+
+/// A handle to spawn tasks on a node.
+#[derive(Clone)]
+pub struct TaskNodeHandle {
+    sender: mpsc::Sender<(Runnable, Arc<TaskInfo>)>,
     info: Arc<NodeInfo>,
+    spawned_total: Arc<AtomicU64>,
 }
 
 impl TaskNodeHandle {
     fn current() -> Self {
         let info = crate::context::current_task();
-        let sender = crate::context::current(|h| h.task.sender.clone());
+        let (sender, spawned_total) =
+            crate::context::current(|h| (h.task.sender.clone(), h.task.spawned_total.clone()));
         TaskNodeHandle {
             sender,
             info: info.node.clone(),
+            spawned_total,
         }
     }
 
@@ -328,6 +1404,76 @@ impl TaskNodeHandle {
         self.info.id
     }
 
+    /// Returns the number of alive tasks spawned on this node.
+    pub fn task_count(&self) -> usize {
+        self.info.task_count.load(Ordering::SeqCst)
+    }
+
+    /// Returns the IDs of alive tasks spawned on this node in ascending order.
+    pub fn alive_task_ids(&self) -> Vec<Id> {
+        self.info.alive_tasks.lock().keys().copied().collect()
+    }
+
+    /// Returns the IDs and names of alive tasks spawned on this node in ascending order of IDs.
+    pub fn alive_tasks(&self) -> Vec<(Id, Option<String>)> {
+        let tasks = self.info.alive_tasks.lock();
+        tasks.iter().map(|(id, name)| (*id, name.clone())).collect()
+    }
+
+    /// Returns the maximum number of simultaneously alive tasks on this node.
+    ///
+    /// It is reset when the node is killed or restarted.
+    pub fn max_task_count(&self) -> usize {
+        self.info.max_task_count.load(Ordering::SeqCst)
+    }
+
+    /// Returns the number of bytes recorded by [`record_alloc`] and [`record_free`] on this node.
+    ///
+    /// It is reset when the node is killed or restarted.
+    pub fn memory_usage(&self) -> usize {
+        self.info.memory.load(Ordering::SeqCst)
+    }
+
+    /// Waits for the initial task of the node to yield for the first time or complete.
+    ///
+    /// Resolves immediately if the node has no initial task.
+    /// If the node is killed before becoming ready, the future never resolves.
+    pub fn wait_ready(&self) -> impl Future<Output = ()> {
+        let info = self.info.clone();
+        async move {
+            loop {
+                let ready = info.ready_notify.notified();
+                if info.ready.load(Ordering::SeqCst) {
+                    return;
+                }
+                ready.await;
+            }
+        }
+    }
+
+    /// Spawns the initial task of the node.
+    ///
+    /// The node becomes ready after the first poll of the task.
+    pub(crate) fn spawn_init<F>(&self, future: F) -> JoinHandle<F::Output>
+    where
+        F: Future + 'static,
+        F::Output: 'static,
+    {
+        let info = self.info.clone();
+        info.ready.store(false, Ordering::SeqCst);
+        let mut future = Box::pin(future);
+        let mut polled = false;
+        self.spawn_local(std::future::poll_fn(move |cx| {
+            let poll = future.as_mut().poll(cx);
+            if !polled {
+                polled = true;
+                info.ready.store(true, Ordering::SeqCst);
+                info.ready_notify.notify_waiters();
+            }
+            poll
+        }))
+    }
+
     /// Spawns a new asynchronous task, returning a [`JoinHandle`] for it.
     pub fn spawn<F>(&self, future: F) -> JoinHandle<F::Output>
     where
@@ -343,24 +1489,175 @@ impl TaskNodeHandle {
         F: Future + 'static,
         F::Output: 'static,
     {
+        self.spawn_inner(future, None, 0)
+    }
+
+    /// Spawns a new asynchronous task, calling `on_panic` with its [`Id`] if it panics.
+    ///
+    /// The panic is caught instead of propagating to the runtime, and the [`JoinHandle`]
+    /// resolves to a [`JoinError`] that [`is_panic`](JoinError::is_panic).
+    /// This helps to detect detached tasks dying silently.
+    pub fn spawn_with_panic_handler<F>(
+        &self,
+        future: F,
+        on_panic: impl Fn(Id) + Send + 'static,
+    ) -> JoinHandle<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        let (task, info) = self.spawn_task(future, None, 0, Some(Box::new(on_panic)));
+        JoinHandle::new(task, &info)
+    }
+
+    /// Runs the provided closure on a thread where blocking is acceptable.
+    ///
+    /// The closure is called after a simulated time randomly chosen from
+    /// [`Config::blocking_op_duration`], which models the cost of the blocking operation.
+    /// Other tasks can run in the meantime.
+    ///
+    /// If [`Config::blocking_pool_size`] is set, the closure waits for a free slot in the
+    /// blocking pool of the node first.
+    pub fn spawn_blocking<F, R>(&self, f: F) -> JoinHandle<R>
+    where
+        F: FnOnce() -> R + Send + 'static,
+        R: Send + 'static,
+    {
+        let info = self.info.clone();
+        self.spawn(async move {
+            let (range, pool_size, rand, time) = crate::context::current(|h| {
+                let range = h.config.task.blocking_op_duration.clone();
+                let pool_size = h.config.task.blocking_pool_size;
+                (range, pool_size, h.rand.clone(), h.time.clone())
+            });
+            let _permit = match pool_size {
+                Some(size) => {
+                    assert_ne!(size, 0, "blocking pool size must not be zero");
+                    let pool =
+                        (info.blocking_pool).get_or_init(|| crate::sync::Semaphore::new(size));
+                    Some(pool.acquire().await.unwrap())
+                }
+                None => None,
+            };
+            if !range.is_empty() {
+                let dur = rand.with(|rng| rng.gen_range(range));
+                time.sleep(dur).await;
+            }
+            f()
+        })
+    }
+
+    fn spawn_inner<F>(&self, future: F, name: Option<&str>, priority: u8) -> JoinHandle<F::Output>
+    where
+        F: Future + 'static,
+        F::Output: 'static,
+    {
+        let (task, info) = self.spawn_task(future, name, priority, None);
+        JoinHandle::new(task, &info)
+    }
+
+    /// Spawns a new asynchronous task without a [`JoinHandle`].
+    ///
+    /// This makes it explicit that the task is never joined, such as a background loop.
+    /// It is slightly cheaper than dropping the `JoinHandle` returned by [`spawn`](Self::spawn).
+    pub fn spawn_detached<F>(&self, future: F)
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        let (task, _) = self.spawn_task(future, None, 0, None);
+        task.detach();
+    }
+
+    /// Schedules a new task on this node.
+    ///
+    /// If `on_panic` is set, it is called when the task panics, and the panic only closes
+    /// the task instead of unwinding the runtime.
+    fn spawn_task<F>(
+        &self,
+        future: F,
+        name: Option<&str>,
+        priority: u8,
+        on_panic: Option<Box<dyn Fn(Id) + Send>>,
+    ) -> (async_task::Task<F::Output>, Arc<TaskInfo>)
+    where
+        F: Future + 'static,
+        F::Output: 'static,
+    {
+        let max = self.info.max_tasks.load(Ordering::SeqCst);
+        if self.info.task_count.load(Ordering::SeqCst) >= max {
+            panic!(
+                "node {} ({:?}) exceeded the limit of {max} alive tasks",
+                self.info.id, self.info.name
+            );
+        }
         let sender = self.sender.clone();
-        let info = self.info.new_task();
+        let info = self.info.new_task(name, priority, on_panic.is_some());
         let id = info.id;
-        trace!(%id, "spawn task");
+        trace!(%id, name = info.name, "spawn task");
+
+        // record whether the task panicked to distinguish it from cancellation
+        let panicked = info.panicked.clone();
+        // the counter is decreased when the future is dropped
+        let counter = TaskCountGuard::new(&self.info, id, info.name.clone());
+        self.spawned_total.fetch_add(1, Ordering::SeqCst);
+        let node = self.info.clone();
+        let future = async move {
+            let _counter = counter;
+            let mut future = std::pin::pin!(future);
+            let future = std::future::poll_fn(|cx| {
+                if node.injected_panics.lock().remove(&id) {
+                    panic!("injected panic");
+                }
+                future.as_mut().poll(cx)
+            });
+            match AssertUnwindSafe(future).catch_unwind().await {
+                Ok(output) => output,
+                Err(payload) => {
+                    panicked.store(true, Ordering::SeqCst);
+                    if let Some(on_panic) = on_panic {
+                        on_panic(id);
+                    }
+                    std::panic::resume_unwind(payload)
+                }
+            }
+        };
 
+        let info0 = info.clone();
         let (runnable, task) = unsafe {
             // Safety: The schedule is not Sync,
             // the task's Waker must be used and dropped on the original thread.
             async_task::spawn_unchecked(future, move |runnable| {
                 trace!(%id, "wake task");
-                let _ = sender.send((runnable, info.clone()));
+                let _ = sender.send((runnable, info0.clone()));
             })
         };
+        self.info.wakers.lock().insert(id, runnable.waker());
         runnable.schedule();
+        (task, info)
+    }
+}
 
-        JoinHandle {
-            id,
-            task: Mutex::new(Some(task.fallible())),
+/// Counts an alive task of a node.
+struct TaskCountGuard(Arc<NodeInfo>, Id);
+
+impl TaskCountGuard {
+    fn new(node: &Arc<NodeInfo>, id: Id, name: Option<String>) -> Self {
+        let count = node.task_count.fetch_add(1, Ordering::SeqCst) + 1;
+        node.max_task_count.fetch_max(count, Ordering::SeqCst);
+        node.alive_tasks.lock().insert(id, name);
+        TaskCountGuard(node.clone(), id)
+    }
+}
+
+impl Drop for TaskCountGuard {
+    fn drop(&mut self) {
+        self.0.alive_tasks.lock().remove(&self.1);
+        self.0.injected_panics.lock().remove(&self.1);
+        let waker = self.0.wakers.lock().remove(&self.1);
+        drop(waker);
+        if self.0.task_count.fetch_sub(1, Ordering::SeqCst) == 1 {
+            self.0.idle.notify_waiters();
         }
     }
 }
@@ -392,20 +1689,318 @@ where
     R: Send + 'static,
 {
     let handle = TaskNodeHandle::current();
-    handle.spawn(async move { f() })
+    handle.spawn_blocking(f)
 }
 
-/// An opaque ID that uniquely identifies a task relative to all other currently running tasks.
-#[derive(Clone, Copy, Debug, Hash, Eq, PartialEq)]
-pub struct Id(u64);
+/// Runs the provided blocking closure on the current task.
+///
+/// The simulated time is advanced by a duration randomly chosen from
+/// [`Config::blocking_op_duration`] to model the cost of the blocking operation.
+/// Unlike a multi-threaded runtime, no other task can make progress while the closure
+/// is running, just like a single-threaded runtime.
+pub fn block_in_place<F, R>(f: F) -> R
+where
+    F: FnOnce() -> R,
+{
+    let (range, rand, time) = crate::context::current(|h| {
+        let range = h.config.task.blocking_op_duration.clone();
+        (range, h.rand.clone(), h.time.clone())
+    });
+    if !range.is_empty() {
+        let dur = rand.with(|rng| rng.gen_range(range));
+        time.advance_clock(dur);
+    }
+    f()
+}
+
+/// Returns a poll latency distribution that draws uniformly from `range`.
+///
+/// This is the default behavior with [`Config::poll_duration`].
+/// See [`Runtime::set_poll_latency`](crate::runtime::Runtime::set_poll_latency).
+pub fn uniform_latency(range: Range<Duration>) -> impl Fn(&mut dyn RngCore) -> Duration {
+    move |rng| {
+        if range.is_empty() {
+            return Duration::ZERO;
+        }
+        rng.gen_range(range.clone())
+    }
+}
+
+/// Returns a poll latency distribution following a log-normal distribution.
+///
+/// The median of the latency is `median`, and `sigma` is the standard deviation of its
+/// logarithm. A larger `sigma` gives a heavier tail, which is useful to model occasional
+/// long stalls such as GC pauses or page faults.
+/// See [`Runtime::set_poll_latency`](crate::runtime::Runtime::set_poll_latency).
+///
+/// # Panics
+///
+/// This function panics if `sigma` is negative or not finite.
+pub fn lognormal_latency(median: Duration, sigma: f64) -> impl Fn(&mut dyn RngCore) -> Duration {
+    assert!(
+        sigma.is_finite() && sigma >= 0.0,
+        "sigma must be finite and non-negative"
+    );
+    move |rng| {
+        // Box-Muller transform
+        let u1: f64 = 1.0 - rng.gen::<f64>();
+        let u2: f64 = rng.gen();
+        let z = (-2.0 * u1.ln()).sqrt() * (2.0 * std::f64::consts::PI * u2).cos();
+        let secs = median.as_secs_f64() * (sigma * z).exp();
+        Duration::try_from_secs_f64(secs).unwrap_or(Duration::MAX)
+    }
+}
+
+/// Records that `bytes` of memory are allocated by the current node.
+///
+/// If the memory usage of the node exceeds its [limit](crate::runtime::NodeBuilder::memory_limit),
+/// the node is killed. The current task keeps running until it yields.
+pub fn record_alloc(bytes: usize) {
+    let info = crate::context::current_task();
+    let node = &info.node;
+    let usage = node.memory.fetch_add(bytes, Ordering::SeqCst) + bytes;
+    if usage > node.memory_limit.load(Ordering::SeqCst) && !node.killed.load(Ordering::SeqCst) {
+        warn!(node = %node.id, usage, "out of memory");
+        crate::runtime::Handle::current().oom_kill(node.id);
+    }
+}
+
+/// Records that `bytes` of memory are freed by the current node.
+pub fn record_free(bytes: usize) {
+    let info = crate::context::current_task();
+    let _ = info
+        .node
+        .memory
+        .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |m| {
+            Some(m.saturating_sub(bytes))
+        });
+}
+
+/// Simulates that the current task keeps a CPU core of its node busy for `duration`.
+///
+/// Simulated time advances by `duration` as if the task was running a computation without
+/// yielding. Unlike [`sleep`](crate::time::sleep), the core stays occupied meanwhile: on a
+/// single-core node, no other task of the node runs until the work is done, so the work
+/// delays the co-located tasks. On a node with `n` cores, other tasks of the node keep
+/// running as long as fewer than `n` tasks are simulating CPU work. Tasks on other nodes
+/// are not affected.
+///
+/// # Example
+///
+/// ```
+/// use madsim::{runtime::Runtime, task, time::{sleep, Duration, Instant}};
+///
+/// let rt = Runtime::new();
+/// let node = rt.create_node().build();
+/// rt.block_on(async move {
+///     let t0 = Instant::now();
+///     node.spawn(task::simulate_cpu_work(Duration::from_millis(5)));
+///     // the timer fires after 1ms, but the task can only run when the core is free
+///     let latency = node.spawn(async move {
+///         sleep(Duration::from_millis(1)).await;
+///         t0.elapsed()
+///     });
+///     assert!(latency.await.unwrap() >= Duration::from_millis(5));
+/// });
+/// ```
+pub async fn simulate_cpu_work(duration: Duration) {
+    let info = crate::context::current_task();
+    debug!(?duration, "simulate cpu work");
+    info.node.cpu.lock().busy.insert(info.id);
+    let _guard = CpuGuard(info);
+    crate::time::sleep(duration).await;
+}
+
+/// Frees the core occupied by [`simulate_cpu_work`] on drop.
+struct CpuGuard(Arc<TaskInfo>);
+
+impl Drop for CpuGuard {
+    fn drop(&mut self) {
+        let mut cpu = self.0.node.cpu.lock();
+        cpu.busy.remove(&self.0.id);
+        let waiting = std::mem::take(&mut cpu.waiting);
+        drop(cpu);
+        // the waiting tasks are dropped if the runtime has been dropped
+        let Some(sender) = crate::context::try_current(|h| h.task.sender.clone()) else {
+            return;
+        };
+        for task in waiting {
+            let _ = sender.send(task);
+        }
+    }
+}
 
-impl Id {
-    fn new() -> Self {
-        static NEXT_TASK_ID: AtomicU64 = AtomicU64::new(0);
-        Id(NEXT_TASK_ID.fetch_add(1, Ordering::SeqCst))
+/// Returns `true` if the node of the current task is shutting down.
+///
+/// Tasks should finish their work as soon as possible after it returns `true`.
+/// See [`Handle::shutdown`](crate::runtime::Handle::shutdown) for more details.
+pub fn is_node_shutting_down() -> bool {
+    let info = crate::context::current_task();
+    info.node.shutting_down.load(Ordering::SeqCst)
+}
+
+/// Waits for the first of the tasks to complete.
+///
+/// Returns the output of the completed task, its index in `handles`, and the remaining
+/// handles. If several tasks have completed, one of them is chosen by the global random
+/// number generator. This is the deterministic counterpart of
+/// `futures::future::select_all`.
+///
+/// # Panics
+///
+/// This function panics if `handles` is empty.
+///
+/// # Example
+///
+/// ```
+/// use madsim::{runtime::Runtime, task, time::{sleep, Duration}};
+///
+/// Runtime::new().block_on(async {
+///     let handles = (0..3u64)
+///         .map(|i| task::spawn(async move {
+///             sleep(Duration::from_secs(3 - i)).await;
+///             i
+///         }))
+///         .collect();
+///     let (output, index, remaining) = task::select_any(handles).await;
+///     assert_eq!(output.unwrap(), 2);
+///     assert_eq!(index, 2);
+///     assert_eq!(remaining.len(), 2);
+/// });
+/// ```
+pub async fn select_any<T>(
+    mut handles: Vec<JoinHandle<T>>,
+) -> (Result<T, JoinError>, usize, Vec<JoinHandle<T>>) {
+    assert!(
+        !handles.is_empty(),
+        "select_any requires at least one handle"
+    );
+    let (output, index) = std::future::poll_fn(|cx| {
+        let finished = (handles.iter().enumerate())
+            .filter(|(_, handle)| handle.is_finished())
+            .map(|(i, _)| i)
+            .collect::<Vec<_>>();
+        if let Some(&i) = crate::rand::choose(&finished) {
+            let output = std::task::ready!(Pin::new(&mut handles[i]).poll(cx));
+            return Poll::Ready((output, i));
+        }
+        for (i, handle) in handles.iter_mut().enumerate() {
+            if let Poll::Ready(output) = Pin::new(handle).poll(cx) {
+                return Poll::Ready((output, i));
+            }
+        }
+        Poll::Pending
+    })
+    .await;
+    handles.remove(index);
+    (output, index, handles)
+}
+
+/// Error returned by [`Runtime::assert_no_pending_tasks`](crate::runtime::Runtime::assert_no_pending_tasks).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PendingTasks {
+    /// The tasks still in the ready queue and the nodes they belong to.
+    pub queued: Vec<(Id, NodeId)>,
+    /// The nodes with alive tasks and the number of them.
+    pub alive: Vec<(NodeId, usize)>,
+}
+
+impl fmt::Display for PendingTasks {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "pending tasks:")?;
+        for (id, node) in &self.queued {
+            write!(f, " task {id} queued on node {node};")?;
+        }
+        for (node, count) in &self.alive {
+            write!(f, " {count} tasks alive on node {node};")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for PendingTasks {}
+
+/// Returns the [`Id`] of the currently running task.
+///
+/// # Panics
+///
+/// This function panics if called from outside a task.
+pub fn id() -> Id {
+    try_id().expect("can't get a task id when not inside a task")
+}
+
+/// Returns the [`Id`] of the currently running task, or `None` if called outside a task.
+pub fn try_id() -> Option<Id> {
+    crate::context::try_current_task().map(|info| info.id)
+}
+
+/// Returns the [`NodeId`] of the node the current task is running on.
+///
+/// # Panics
+///
+/// This function panics if called from outside a task.
+pub fn current_node() -> NodeId {
+    crate::context::current_node()
+}
+
+/// Calls `f` with the global random number generator.
+///
+/// The values drawn are part of the same deterministic stream as [`rand::thread_rng`],
+/// so they are reproducible for the same seed. The generator is locked while `f` runs,
+/// so `f` must not draw random values by other means.
+///
+/// [`rand::thread_rng`]: crate::rand::thread_rng
+///
+/// # Panics
+///
+/// This function panics if called outside the context of a Madsim runtime.
+///
+/// # Example
+///
+/// ```
+/// use madsim::{rand::Rng, runtime::Runtime, task};
+///
+/// Runtime::new().block_on(async {
+///     let timeout = task::with_node_rng(|r| r.gen_range(150..300));
+///     assert!((150..300).contains(&timeout));
+/// });
+/// ```
+pub fn with_node_rng<R>(f: impl FnOnce(&mut dyn RngCore) -> R) -> R {
+    crate::context::current(|h| h.rand.clone()).with(|rng| f(rng))
+}
+
+/// Returns a deterministic identifier of the simulated thread running the current task.
+///
+/// Each node is simulated as a single thread, so all tasks on the same node share the
+/// same thread ID. Use it instead of `std::thread::current().id()`, which is assigned by
+/// the standard library and can not be made deterministic.
+///
+/// # Panics
+///
+/// This function panics if called from outside a task.
+pub fn current_thread_id() -> ThreadId {
+    ThreadId(current_node().0 + 1)
+}
+
+/// A unique identifier for a simulated thread.
+///
+/// See [`current_thread_id`].
+#[derive(Clone, Copy, Debug, Hash, Eq, PartialEq, PartialOrd, Ord)]
+pub struct ThreadId(u64);
+
+impl ThreadId {
+    /// Returns the raw value of this `ThreadId`. It is never zero.
+    pub const fn as_u64(&self) -> u64 {
+        self.0
     }
 }
 
+/// An opaque ID that uniquely identifies a task relative to all other currently running tasks.
+///
+/// IDs are assigned in spawn order within a runtime, so they are deterministic.
+#[derive(Clone, Copy, Debug, Hash, Eq, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct Id(u64);
+
 impl fmt::Display for Id {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         self.0.fmt(f)
@@ -416,15 +2011,112 @@ impl fmt::Display for Id {
 #[derive(Debug)]
 pub struct JoinHandle<T> {
     id: Id,
-    task: Mutex<Option<FallibleTask<T>>>,
+    name: Option<String>,
+    info: Weak<TaskInfo>,
+    task: Arc<Mutex<Option<FallibleTask<T>>>>,
+    /// A flag indicating that the task panicked.
+    panicked: Arc<AtomicBool>,
 }
 
 impl<T> JoinHandle<T> {
+    fn new(task: async_task::Task<T>, info: &Arc<TaskInfo>) -> Self {
+        JoinHandle {
+            id: info.id,
+            name: info.name.clone(),
+            info: Arc::downgrade(info),
+            task: Arc::new(Mutex::new(Some(task.fallible()))),
+            panicked: info.panicked.clone(),
+        }
+    }
+
     /// Abort the task associated with the handle.
     pub fn abort(&self) {
         self.task.lock().take();
     }
 
+    /// Checks if the task associated with this `JoinHandle` has finished.
+    ///
+    /// An aborted task is also considered finished.
+    pub fn is_finished(&self) -> bool {
+        match self.task.lock().as_ref() {
+            Some(task) => task.is_finished(),
+            None => true,
+        }
+    }
+
+    /// Returns a task ID that uniquely identifies this task relative to other currently spawned tasks.
+    pub fn id(&self) -> Id {
+        self.id
+    }
+
+    /// Returns a new `AbortHandle` that can be used to remotely abort this task.
+    pub fn abort_handle(&self) -> AbortHandle {
+        AbortHandle {
+            id: self.id,
+            info: self.info.clone(),
+        }
+    }
+
+    /// Detaches the task explicitly, so that it keeps running in the background.
+    ///
+    /// Unlike dropping the handle, this never warns under
+    /// [`Config::strict_join_handles`].
+    pub fn detach(self) {
+        if let Some(task) = self.task.lock().take() {
+            task.detach();
+        }
+    }
+
+    /// Aborts the task and waits until its future has been dropped.
+    ///
+    /// Unlike [`abort`](Self::abort), which only signals the cancellation, the returned
+    /// future resolves after the drop glue of the task has run, so resources it held are
+    /// released by then. Returns an error only if the task panicked.
+    ///
+    /// The task is dropped the next time the executor picks it from the ready queue.
+    /// This happens in the same pass that drains the ready queue as the abort, before the
+    /// clock jumps to the next timer, though the poll latency of other tasks may still
+    /// advance time slightly. If the node of the task is paused, it is dropped only after
+    /// the node is resumed.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use madsim::{runtime::Runtime, task, time::{sleep, Duration}};
+    /// use std::sync::Arc;
+    ///
+    /// Runtime::new().block_on(async {
+    ///     let resource = Arc::new(());
+    ///     let r = resource.clone();
+    ///     let handle = task::spawn(async move {
+    ///         let _r = r;
+    ///         sleep(Duration::from_secs(10)).await;
+    ///     });
+    ///     sleep(Duration::from_secs(1)).await;
+    ///     handle.abort_and_join().await.unwrap();
+    ///     assert_eq!(Arc::strong_count(&resource), 1);
+    /// });
+    /// ```
+    pub fn abort_and_join(self) -> impl Future<Output = Result<(), JoinError>> {
+        let task = self.task.lock().take();
+        let id = self.id;
+        let name = self.name.clone();
+        let panicked = self.panicked.clone();
+        async move {
+            if let Some(task) = task {
+                task.cancel().await;
+            }
+            if panicked.load(Ordering::SeqCst) {
+                return Err(JoinError {
+                    id,
+                    name,
+                    is_panic: true,
+                });
+            }
+            Ok(())
+        }
+    }
+
     /// Cancel the task when this handle is dropped.
     #[doc(hidden)]
     pub fn cancel_on_drop(self) -> FallibleTask<T> {
@@ -439,29 +2131,103 @@ impl<T> Future for JoinHandle<T> {
         self: std::pin::Pin<&mut Self>,
         cx: &mut std::task::Context<'_>,
     ) -> std::task::Poll<Self::Output> {
-        std::pin::Pin::new(self.task.lock().as_mut().unwrap())
-            .poll(cx)
-            .map(|res| {
-                res.ok_or(JoinError {
+        let coop = std::task::ready!(coop::poll_proceed(cx));
+        let mut lock = self.task.lock();
+        let task = match lock.as_mut() {
+            Some(task) => task,
+            // the task has been aborted
+            None => {
+                coop.made_progress();
+                return Poll::Ready(Err(JoinError {
                     id: self.id,
-                    is_panic: true, // TODO: decide cancelled or panic
-                })
+                    name: self.name.clone(),
+                    is_panic: false,
+                }));
+            }
+        };
+        std::pin::Pin::new(task).poll(cx).map(|res| {
+            coop.made_progress();
+            res.ok_or_else(|| JoinError {
+                id: self.id,
+                name: self.name.clone(),
+                is_panic: self.panicked.load(Ordering::SeqCst),
             })
+        })
     }
 }
 
 impl<T> Drop for JoinHandle<T> {
     fn drop(&mut self) {
-        if let Some(task) = self.task.lock().take() {
-            task.detach();
+        let Some(task) = self.task.lock().take() else {
+            return;
+        };
+        let strict = crate::context::try_current(|h| h.config.task.strict_join_handles);
+        if strict == Some(true) && !task.is_finished() {
+            warn!(
+                id = %self.id,
+                name = ?self.name,
+                "JoinHandle dropped before the task completed, call `detach` if it is intended"
+            );
+        }
+        task.detach();
+    }
+}
+
+/// An owned permission to abort a spawned task, without awaiting its completion.
+#[derive(Clone)]
+pub struct AbortHandle {
+    id: Id,
+    info: Weak<TaskInfo>,
+}
+
+impl AbortHandle {
+    /// Abort the task associated with the handle.
+    ///
+    /// Awaiting the corresponding [`JoinHandle`] will return a cancelled [`JoinError`].
+    /// The future of the task is dropped the next time the executor picks it from the
+    /// ready queue.
+    pub fn abort(&self) {
+        let Some(info) = self.info.upgrade() else {
+            return;
+        };
+        info.aborted.store(true, Ordering::SeqCst);
+        // wake up the task to drop it in the executor
+        let waker = info.node.wakers.lock().get(&self.id).cloned();
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+
+    /// Checks if the task associated with this `AbortHandle` has finished.
+    ///
+    /// An aborted task is also considered finished.
+    pub fn is_finished(&self) -> bool {
+        match self.info.upgrade() {
+            Some(info) => {
+                info.aborted.load(Ordering::SeqCst)
+                    || !info.node.alive_tasks.lock().contains_key(&self.id)
+            }
+            None => true,
         }
     }
+
+    /// Returns a task ID that uniquely identifies this task relative to other currently spawned tasks.
+    pub fn id(&self) -> Id {
+        self.id
+    }
+}
+
+impl fmt::Debug for AbortHandle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AbortHandle").field("id", &self.id).finish()
+    }
 }
 
 /// Task failed to execute to completion.
 #[derive(Debug)]
 pub struct JoinError {
     id: Id,
+    name: Option<String>,
     is_panic: bool,
 }
 
@@ -484,9 +2250,13 @@ impl JoinError {
 
 impl fmt::Display for JoinError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "task {}", self.id)?;
+        if let Some(name) = &self.name {
+            write!(f, " ({})", name)?;
+        }
         match self.is_panic {
-            false => write!(f, "task {} was cancelled", self.id),
-            true => write!(f, "task {} panicked", self.id),
+            false => write!(f, " was cancelled"),
+            true => write!(f, " panicked"),
         }
     }
 }
@@ -518,8 +2288,9 @@ unsafe extern "C" fn sched_getaffinity(
 ) -> libc::c_int {
     if let Some(info) = crate::context::try_current_task() {
         assert_eq!(cpusetsize, std::mem::size_of::<libc::cpu_set_t>());
-        assert!(cpusetsize * 8 >= info.node.cores as _);
-        for i in 0..info.node.cores {
+        let cores = info.node.cores();
+        assert!(cpusetsize * 8 >= cores as _);
+        for i in 0..cores {
             libc::CPU_SET(i, &mut *cpuset);
         }
         return 0;
@@ -546,7 +2317,7 @@ unsafe extern "C" fn sched_getaffinity(
 unsafe extern "C" fn sysconf(name: libc::c_int) -> libc::c_long {
     if name == libc::_SC_NPROCESSORS_ONLN {
         if let Some(info) = crate::context::try_current_task() {
-            return info.node.cores as _;
+            return info.node.cores() as _;
         }
     }
     lazy_static::lazy_static! {
@@ -563,10 +2334,14 @@ unsafe extern "C" fn sysconf(name: libc::c_int) -> libc::c_long {
 mod tests {
     use super::*;
     use crate::{
-        runtime::{Handle, Runtime},
+        runtime::{Handle, NodeHandle, Runtime},
         time,
     };
-    use std::{collections::HashSet, sync::atomic::AtomicUsize, time::Duration};
+    use std::{
+        collections::{HashMap, HashSet},
+        sync::atomic::AtomicUsize,
+        time::Duration,
+    };
 
     #[test]
     fn spawn_in_block_on() {
@@ -618,19 +2393,140 @@ mod tests {
     }
 
     #[test]
-    fn restart() {
+    fn kill_drops_tasks() {
+        struct Guard(Arc<AtomicUsize>);
+        impl Drop for Guard {
+            fn drop(&mut self) {
+                self.0.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+
         let runtime = Runtime::new();
+        let node1 = runtime.create_node().build();
+        let node2 = runtime.create_node().build();
+        let dropped = Arc::new(AtomicUsize::new(0));
+        let guard = || Guard(dropped.clone());
 
-        let flag = Arc::new(AtomicUsize::new(0));
+        runtime.block_on(async {
+            let handle = Handle::current();
+            // waiting for a timer
+            let g = guard();
+            node1.spawn(async move {
+                time::sleep(Duration::from_secs(10)).await;
+                drop(g);
+            });
+            // waiting forever
+            let g = guard();
+            node1.spawn(async move {
+                std::future::pending::<()>().await;
+                drop(g);
+            });
+            time::sleep(Duration::from_secs(1)).await;
+            // queued but not polled yet
+            let g = guard();
+            node1.spawn(async move { drop(g) });
+            handle.kill(node1.id());
+            assert_eq!(dropped.load(Ordering::SeqCst), 3);
+
+            // paused
+            handle.pause(node2.id());
+            let g = guard();
+            node2.spawn(async move { drop(g) });
+            time::sleep(Duration::from_secs(1)).await;
+            assert_eq!(dropped.load(Ordering::SeqCst), 3);
+            handle.kill(node2.id());
+            assert_eq!(dropped.load(Ordering::SeqCst), 4);
+        });
+    }
 
-        let flag_ = flag.clone();
-        let node = runtime
-            .create_node()
-            .init(move || {
-                let flag = flag_.clone();
-                async move {
-                    // set flag to 0, then +2 every 2s
-                    flag.store(0, Ordering::SeqCst);
+    #[test]
+    fn simulate_cpu_work() {
+        let runtime = Runtime::new();
+        let node1 = runtime.create_node().build();
+        let node2 = runtime.create_node().build();
+        let node3 = runtime.create_node().cores(2).build();
+        runtime.block_on(async move {
+            // measures the delay of a 1ms sleep on each node while one task is busy
+            let t0 = time::Instant::now();
+            let latency = |node: &crate::runtime::NodeHandle| {
+                node.spawn(async move {
+                    time::sleep(Duration::from_millis(1)).await;
+                    t0.elapsed()
+                })
+            };
+            let busy = Duration::from_millis(5);
+            node1.spawn(super::simulate_cpu_work(busy));
+            node3.spawn(super::simulate_cpu_work(busy));
+            time::sleep(Duration::from_micros(1)).await;
+            let l1 = latency(&node1);
+            let l2 = latency(&node2);
+            let l3 = latency(&node3);
+            assert!(l1.await.unwrap() >= busy);
+            // other nodes and free cores are not affected
+            assert!(l2.await.unwrap() < Duration::from_millis(2));
+            assert!(l3.await.unwrap() < Duration::from_millis(2));
+        });
+    }
+
+    #[test]
+    fn kill_at() {
+        let runtime = Runtime::new();
+        let node = runtime.create_node().build();
+
+        let flag = Arc::new(AtomicUsize::new(0));
+        let flag_ = flag.clone();
+        node.spawn(async move {
+            loop {
+                time::sleep(Duration::from_secs(2)).await;
+                flag_.fetch_add(2, Ordering::SeqCst);
+            }
+        });
+
+        runtime.block_on(async move {
+            let t0 = time::Instant::now();
+            Handle::current().kill_at(node.id(), t0 + Duration::from_secs(3));
+
+            time::sleep_until(t0 + Duration::from_secs(5)).await;
+            assert_eq!(flag.load(Ordering::SeqCst), 2);
+        });
+    }
+
+    #[test]
+    fn kill_at_spawns_no_task() {
+        let runtime = Runtime::new();
+        let node = runtime.create_node().build();
+        node.spawn(async { std::future::pending::<()>().await });
+
+        runtime.block_on(async move {
+            let handle = Handle::current();
+            // let the task on the node park
+            time::sleep(Duration::from_millis(1)).await;
+            let tasks = handle.total_task_count();
+            let t0 = time::Instant::now();
+            handle.kill_at(node.id(), t0 + Duration::from_secs(1));
+            assert_eq!(handle.total_task_count(), tasks);
+            assert_eq!(handle.ready_task_count(), 0);
+
+            time::sleep(Duration::from_secs(2)).await;
+            assert!(!handle.is_alive(node.id()));
+            assert_eq!(handle.total_task_count(), tasks - 1);
+        });
+    }
+
+    #[test]
+    fn restart() {
+        let runtime = Runtime::new();
+
+        let flag = Arc::new(AtomicUsize::new(0));
+
+        let flag_ = flag.clone();
+        let node = runtime
+            .create_node()
+            .init(move || {
+                let flag = flag_.clone();
+                async move {
+                    // set flag to 0, then +2 every 2s
+                    flag.store(0, Ordering::SeqCst);
                     loop {
                         time::sleep(Duration::from_secs(2)).await;
                         flag.fetch_add(2, Ordering::SeqCst);
@@ -655,6 +2551,332 @@ mod tests {
         });
     }
 
+    #[test]
+    fn join_error_cancelled() {
+        let runtime = Runtime::new();
+        let node = runtime.create_node().build();
+        runtime.block_on(async move {
+            // aborted task
+            let handle = spawn(time::sleep(Duration::from_secs(10)));
+            handle.abort();
+            let err = handle.await.unwrap_err();
+            assert!(err.is_cancelled());
+            assert!(!err.is_panic());
+
+            // killed task
+            let handle = node.spawn(time::sleep(Duration::from_secs(10)));
+            time::sleep(Duration::from_secs(1)).await;
+            Handle::current().kill(node.id());
+            let err = handle.await.unwrap_err();
+            assert!(err.is_cancelled());
+            assert_eq!(err.to_string(), format!("task {} was cancelled", err.id()));
+        });
+    }
+
+    #[test]
+    fn join_handle_is_finished() {
+        let runtime = Runtime::new();
+        runtime.block_on(async move {
+            let handle = spawn(time::sleep(Duration::from_secs(2)));
+            time::sleep(Duration::from_secs(1)).await;
+            assert!(!handle.is_finished());
+            time::sleep(Duration::from_secs(2)).await;
+            assert!(handle.is_finished());
+            handle.await.unwrap();
+
+            let handle = spawn(time::sleep(Duration::from_secs(2)));
+            handle.abort();
+            assert!(handle.is_finished());
+        });
+    }
+
+    #[test]
+    fn spawn_named_task() {
+        let runtime = Runtime::new();
+        runtime.block_on(async move {
+            let handle = Builder::new()
+                .name("sleeper")
+                .spawn(time::sleep(Duration::from_secs(10)))
+                .unwrap();
+            handle.abort();
+            let err = handle.await.unwrap_err();
+            assert_eq!(
+                err.to_string(),
+                format!("task {} (sleeper) was cancelled", err.id())
+            );
+        });
+    }
+
+    #[test]
+    fn abort_handle() {
+        let runtime = Runtime::new();
+        runtime.block_on(async move {
+            let handle = spawn(time::sleep(Duration::from_secs(10)));
+            let abort_handle = handle.abort_handle();
+            assert_eq!(abort_handle.id(), handle.id);
+            spawn(async move {
+                time::sleep(Duration::from_secs(1)).await;
+                assert!(!abort_handle.is_finished());
+                abort_handle.abort();
+                assert!(abort_handle.is_finished());
+            });
+            let err = handle.await.unwrap_err();
+            assert!(err.is_cancelled());
+        });
+    }
+
+    #[test]
+    fn abort_handle_after_join_handle_dropped() {
+        struct Guard(Arc<AtomicBool>);
+        impl Drop for Guard {
+            fn drop(&mut self) {
+                self.0.store(true, Ordering::SeqCst);
+            }
+        }
+
+        let runtime = Runtime::new();
+        runtime.block_on(async move {
+            let dropped = Arc::new(AtomicBool::new(false));
+            let guard = Guard(dropped.clone());
+            let handle = spawn(async move {
+                let _guard = guard;
+                time::sleep(Duration::from_secs(10)).await;
+            });
+            let abort_handle = handle.abort_handle();
+            drop(handle);
+            time::sleep(Duration::from_secs(1)).await;
+            assert!(!abort_handle.is_finished());
+            abort_handle.abort();
+            assert!(abort_handle.is_finished());
+            // the future is dropped before the clock advances
+            time::sleep(Duration::from_millis(1)).await;
+            assert!(dropped.load(Ordering::SeqCst));
+
+            // a task that completes on its own is also finished
+            let handle = spawn(time::sleep(Duration::from_secs(1)));
+            let abort_handle = handle.abort_handle();
+            drop(handle);
+            assert!(!abort_handle.is_finished());
+            time::sleep(Duration::from_secs(2)).await;
+            assert!(abort_handle.is_finished());
+        });
+    }
+
+    #[test]
+    fn abort_handle_of_local_task() {
+        let runtime = Runtime::new();
+        runtime.block_on(async move {
+            // the future is `!Send`
+            let handle = spawn_local(async {
+                let rc = std::rc::Rc::new(1);
+                time::sleep(Duration::from_secs(10)).await;
+                *rc
+            });
+            let abort_handle = handle.abort_handle();
+            spawn(async move { abort_handle.abort() });
+            assert!(handle.await.unwrap_err().is_cancelled());
+        });
+    }
+
+    #[test]
+    fn abort_and_join() {
+        struct Guard(Arc<AtomicUsize>);
+        impl Drop for Guard {
+            fn drop(&mut self) {
+                self.0.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+
+        let runtime = Runtime::new();
+        runtime.block_on(async move {
+            let dropped = Arc::new(AtomicUsize::new(0));
+            let guard = Guard(dropped.clone());
+            let handle = spawn(async move {
+                let _guard = guard;
+                time::sleep(Duration::from_secs(10)).await;
+            });
+            time::sleep(Duration::from_secs(1)).await;
+            let t0 = time::Instant::now();
+            handle.abort_and_join().await.unwrap();
+            assert_eq!(dropped.load(Ordering::SeqCst), 1);
+            // the task is dropped without waiting for any timer
+            assert!(t0.elapsed() < Duration::from_millis(1));
+
+            // a finished task
+            let handle = spawn(async {});
+            time::sleep(Duration::from_secs(1)).await;
+            handle.abort_and_join().await.unwrap();
+        });
+    }
+
+    #[test]
+    fn shutdown() {
+        let runtime = Runtime::new();
+        let flag = Arc::new(AtomicUsize::new(0));
+        let flag_ = flag.clone();
+        let node = runtime
+            .create_node()
+            .init(move || {
+                let flag = flag_.clone();
+                async move {
+                    while !is_node_shutting_down() {
+                        time::sleep(Duration::from_secs(1)).await;
+                    }
+                    // flush data before exiting
+                    time::sleep(Duration::from_secs(1)).await;
+                    flag.store(1, Ordering::SeqCst);
+                }
+            })
+            .build();
+        let node1 = runtime.create_node().build();
+        node1.spawn(async move {
+            // ignore the shutdown signal
+            time::sleep(Duration::from_secs(100)).await;
+            unreachable!();
+        });
+
+        runtime.block_on(async move {
+            let t0 = time::Instant::now();
+            let handle = Handle::current();
+            handle.shutdown(node.id(), Duration::from_secs(10)).await;
+            assert_eq!(flag.load(Ordering::SeqCst), 1);
+            assert!(t0.elapsed() < Duration::from_secs(3));
+
+            handle.shutdown(node1.id(), Duration::from_secs(10)).await;
+            assert!(t0.elapsed() >= Duration::from_secs(10));
+            time::sleep(Duration::from_secs(100)).await;
+        });
+    }
+
+    #[test]
+    fn restart_with() {
+        let runtime = Runtime::new();
+
+        let flag = Arc::new(AtomicUsize::new(0));
+        let node = runtime.create_node().build();
+
+        runtime.block_on(async move {
+            // install an initial task on a node created without one
+            let flag_ = flag.clone();
+            Handle::current().restart_with(node.id(), move || {
+                let flag = flag_.clone();
+                async move {
+                    flag.store(1, Ordering::SeqCst);
+                }
+            });
+            time::sleep(Duration::from_secs(1)).await;
+            assert_eq!(flag.load(Ordering::SeqCst), 1);
+
+            // upgrade
+            let flag_ = flag.clone();
+            Handle::current().restart_with(node.id(), move || {
+                let flag = flag_.clone();
+                async move {
+                    flag.fetch_add(2, Ordering::SeqCst);
+                }
+            });
+            time::sleep(Duration::from_secs(1)).await;
+            assert_eq!(flag.load(Ordering::SeqCst), 3);
+
+            // plain restart runs the new version
+            Handle::current().restart(node.id());
+            time::sleep(Duration::from_secs(1)).await;
+            assert_eq!(flag.load(Ordering::SeqCst), 5);
+        });
+    }
+
+    #[test]
+    fn spawn_with_panic_handler() {
+        let runtime = Runtime::new();
+        let node = runtime.create_node().build();
+        runtime.block_on(async move {
+            let (tx, rx) = std::sync::mpsc::channel();
+            let handle = node.spawn_with_panic_handler(
+                async {
+                    time::sleep(Duration::from_secs(1)).await;
+                    panic!("background task died");
+                },
+                move |id| tx.send(id).unwrap(),
+            );
+            let id = handle.id();
+            // the handler is called even if nobody awaits the handle
+            time::sleep(Duration::from_secs(2)).await;
+            assert_eq!(rx.try_recv(), Ok(id));
+            assert!(handle.is_finished());
+            // the task is dropped as soon as it panics
+            assert_eq!(node.task_count(), 0);
+            assert!(handle.await.unwrap_err().is_panic());
+
+            // injected panics are reported as well
+            let (tx, rx) = std::sync::mpsc::channel();
+            let handle = node.spawn_with_panic_handler(
+                async {
+                    loop {
+                        time::sleep(Duration::from_secs(1)).await;
+                    }
+                },
+                move |id| tx.send(id).unwrap(),
+            );
+            Handle::current().inject_panic(handle.id());
+            assert!(handle.await.unwrap_err().is_panic());
+            assert_eq!(rx.try_recv().map(|_| ()), Ok(()));
+
+            // no panic, no call
+            let handle = node.spawn_with_panic_handler(async { 1 }, |_| unreachable!());
+            assert_eq!(handle.await.unwrap(), 1);
+        });
+    }
+
+    #[test]
+    fn inject_panic() {
+        let runtime = Runtime::new();
+        let node = runtime.create_node().build();
+        let result = std::panic::catch_unwind(AssertUnwindSafe(|| {
+            runtime.block_on(async move {
+                let handle = node.spawn(async {
+                    loop {
+                        time::sleep(Duration::from_secs(1)).await;
+                    }
+                });
+                let id = handle.id();
+                // a detached task takes down the runtime like a real panic
+                handle.detach();
+                Handle::current().inject_panic(id);
+                time::sleep(Duration::from_secs(10)).await;
+            })
+        }));
+        let payload = result.unwrap_err();
+        assert_eq!(payload.downcast_ref::<&str>(), Some(&"injected panic"));
+    }
+
+    #[test]
+    fn inject_panic_into_finished_task() {
+        let runtime = Runtime::new();
+        runtime.block_on(async {
+            let injected = || {
+                crate::context::current_task()
+                    .node
+                    .injected_panics
+                    .lock()
+                    .len()
+            };
+            let handle = spawn(async {});
+            let id = handle.id();
+            handle.await.unwrap();
+            Handle::current().inject_panic(id);
+            assert_eq!(injected(), 0);
+
+            // the injection is forgotten when the task is aborted before its next poll
+            let handle = spawn(std::future::pending::<()>());
+            time::sleep(Duration::from_secs(1)).await;
+            Handle::current().inject_panic(handle.id());
+            assert_eq!(injected(), 1);
+            handle.abort();
+            time::sleep(Duration::from_secs(1)).await;
+            assert_eq!(injected(), 0);
+        });
+    }
+
     #[test]
     fn pause_resume() {
         let runtime = Runtime::new();
@@ -700,7 +2922,7 @@ mod tests {
                     tasks.push(spawn(async move {
                         for j in 0..5 {
                             tx.send(i * 10 + j).unwrap();
-                            tokio::task::yield_now().await;
+                            yield_now().await;
                         }
                     }));
                 }
@@ -714,20 +2936,1118 @@ mod tests {
     }
 
     #[test]
-    fn deterministic_std_thread_available_parallelism() {
+    fn yield_for() {
+        let runtime = Runtime::new();
+        runtime.block_on(async {
+            let value = Arc::new(AtomicUsize::new(0));
+            let v = value.clone();
+            let t0 = time::Instant::now();
+            // a read-modify-write descheduled between the read and the write
+            let rmw = spawn(async move {
+                let x = v.load(Ordering::SeqCst);
+                super::yield_for(Duration::from_secs(1)).await;
+                v.store(x + 1, Ordering::SeqCst);
+            });
+            time::sleep(Duration::from_millis(500)).await;
+            value.fetch_add(1, Ordering::SeqCst);
+            rmw.await.unwrap();
+            // the concurrent update is lost
+            assert_eq!(value.load(Ordering::SeqCst), 1);
+            assert!(t0.elapsed() >= Duration::from_secs(1));
+        });
+    }
+
+    #[test]
+    fn replay_trace() {
+        let run = |seed: u64, config: crate::Config| {
+            let runtime = Runtime::with_seed_and_config(seed, config);
+            let seq = runtime.block_on(async {
+                let (tx, rx) = std::sync::mpsc::channel();
+                let tasks = (0..3)
+                    .map(|i| {
+                        let tx = tx.clone();
+                        spawn(async move {
+                            for j in 0..5 {
+                                tx.send(i * 10 + j).unwrap();
+                                yield_now().await;
+                            }
+                        })
+                    })
+                    .collect::<Vec<_>>();
+                drop(tx);
+                futures_util::future::join_all(tasks).await;
+                rx.into_iter().collect::<Vec<_>>()
+            });
+            (seq, runtime.handle().take_trace())
+        };
+        let mut config = crate::Config::default();
+        config.task.record_trace = true;
+        config.task.reorder_probability = 0.5;
+        let (seq, trace) = run(1, config.clone());
+        assert!(trace.iter().any(|e| e.action == ScheduleAction::Defer));
+
+        // the schedule is reproduced under another seed
+        config.task.replay_trace = Some(trace.clone());
+        let parsed: crate::Config = config.to_string().parse().unwrap();
+        assert_eq!(parsed, config);
+        let (seq2, trace2) = run(2, config.clone());
+        assert_eq!(seq2, seq);
+        assert_eq!(trace2, trace);
+
+        // without replay, the other seed diverges
+        config.task.replay_trace = None;
+        assert_ne!(run(2, config).0, seq);
+    }
+
+    #[test]
+    fn zero_poll_duration() {
+        let mut config = crate::Config::default();
+        config.task.poll_duration = Duration::ZERO..Duration::ZERO;
+        let runtime = Runtime::with_seed_and_config(0, config);
+        runtime.block_on(async {
+            let tasks = (0..3)
+                .map(|_| {
+                    spawn(async move {
+                        for _ in 0..5 {
+                            yield_now().await;
+                        }
+                    })
+                })
+                .collect::<Vec<_>>();
+            futures_util::future::join_all(tasks).await;
+            assert_eq!(time::TimeHandle::current().elapsed(), Duration::ZERO);
+        });
+    }
+
+    #[test]
+    fn no_advance_time_on_poll() {
+        let mut config = crate::Config::default();
+        config.task.advance_time_on_poll = false;
+        let mut runtime = Runtime::with_seed_and_config(0, config);
+        runtime.set_poll_latency(|_| Duration::from_millis(1));
+        runtime.block_on(async {
+            let tasks = (0..3)
+                .map(|i| {
+                    spawn(async move {
+                        for _ in 0..5 {
+                            yield_now().await;
+                        }
+                        time::sleep(Duration::from_secs(i)).await;
+                    })
+                })
+                .collect::<Vec<_>>();
+            futures_util::future::join_all(tasks).await;
+            // the clock jumps to the last timer at 2s
+            let elapsed = time::TimeHandle::current().elapsed();
+            assert_eq!(elapsed, Duration::from_secs(2) + Duration::from_nanos(50));
+        });
+    }
+
+    #[test]
+    fn join_set() {
         let runtime = Runtime::new();
+        let node = runtime.create_node().build();
+        let node = runtime.handle().task.get_node(node.id()).unwrap();
         runtime.block_on(async move {
-            // available_parallelism is 1 by default
-            assert_eq!(std::thread::available_parallelism().unwrap().get(), 1);
+            let mut set = JoinSet::new();
+            for i in 0..4 {
+                set.spawn(async move {
+                    time::sleep(Duration::from_secs(i)).await;
+                    i
+                });
+            }
+            set.spawn_on(time::sleep(Duration::from_secs(10)).map(|_| 10), &node);
+            let abort = set.spawn(time::sleep(Duration::from_secs(20)).map(|_| 20));
+            assert_eq!(set.len(), 6);
+
+            for i in 0..4 {
+                assert_eq!(set.join_next().await.unwrap().unwrap(), i);
+            }
+            abort.abort();
+            assert!(set.join_next().await.unwrap().unwrap_err().is_cancelled());
+            assert_eq!(set.join_next().await.unwrap().unwrap(), 10);
+            assert!(set.join_next().await.is_none());
+
+            set.spawn(time::sleep(Duration::from_secs(10)).map(|_| 10));
+            set.abort_all();
+            assert!(set.join_next().await.unwrap().unwrap_err().is_cancelled());
+            assert!(set.is_empty());
         });
-        let f1 = runtime.create_node().build().spawn(async move {
-            // available_parallelism is 1 by default
-            assert_eq!(std::thread::available_parallelism().unwrap().get(), 1);
+    }
+
+    #[test]
+    fn task_local() {
+        crate::task_local! {
+            static KEY: u32;
+        }
+
+        let runtime = Runtime::new();
+        let node = runtime.create_node().build();
+        runtime.block_on(async move {
+            assert!(KEY.try_with(|_| ()).is_err());
+            KEY.scope(1, async move {
+                assert_eq!(KEY.get(), 1);
+                // inherited by the child task on the same node
+                let v = spawn(async { KEY.get() }).await.unwrap();
+                assert_eq!(v, 1);
+                // not visible on another node
+                let v = node.spawn(async { KEY.try_with(|v| *v) }).await.unwrap();
+                assert!(v.is_err());
+                // nested scope
+                KEY.sync_scope(2, || assert_eq!(KEY.get(), 2));
+                assert_eq!(KEY.get(), 1);
+            })
+            .await;
+            assert!(KEY.try_with(|_| ()).is_err());
         });
-        let f2 = runtime.create_node().cores(128).build().spawn(async move {
-            assert_eq!(std::thread::available_parallelism().unwrap().get(), 128);
+    }
+
+    #[test]
+    fn task_local_refcell() {
+        // neither `Clone` nor `Sync`
+        struct Counter(std::cell::RefCell<u32>);
+        crate::task_local! {
+            static COUNTER: Counter;
+        }
+
+        let runtime = Runtime::new();
+        runtime.block_on(COUNTER.scope(Counter(Default::default()), async {
+            COUNTER.with(|c| *c.0.borrow_mut() += 1);
+            // the child task shares the value with its parent
+            spawn(async { COUNTER.with(|c| *c.0.borrow_mut() += 1) })
+                .await
+                .unwrap();
+            time::sleep(Duration::from_secs(1)).await;
+            COUNTER.with(|c| assert_eq!(*c.0.borrow(), 2));
+        }));
+    }
+
+    #[test]
+    fn task_count() {
+        let runtime = Runtime::new();
+        let node = runtime.create_node().build();
+        runtime.block_on(async move {
+            let handle = Handle::current();
+            let tasks = (0..10)
+                .map(|i| node.spawn(time::sleep(Duration::from_secs(i))))
+                .collect::<Vec<_>>();
+            spawn(time::sleep(Duration::from_secs(20)));
+            assert_eq!(node.task_count(), 10);
+            assert_eq!(handle.total_task_count(), 11);
+
+            for task in tasks.into_iter().take(5) {
+                task.await.unwrap();
+            }
+            assert_eq!(node.task_count(), 5);
+            assert_eq!(handle.total_task_count(), 6);
+
+            // the counter is reset after the node is killed
+            handle.kill(node.id());
+            assert_eq!(handle.get_node(node.id()).unwrap().task_count(), 0);
+            assert_eq!(handle.total_task_count(), 1);
         });
-        runtime.block_on(f1).unwrap();
-        runtime.block_on(f2).unwrap();
+    }
+
+    #[test]
+    fn ready_task_count() {
+        let runtime = Runtime::new();
+        runtime.block_on(async move {
+            let handle = Handle::current();
+            assert_eq!(handle.ready_task_count(), 0);
+            // tasks waking themselves up are queued at most once each
+            for _ in 0..10 {
+                spawn(async {
+                    for _ in 0..100 {
+                        yield_now().await;
+                    }
+                });
+            }
+            for _ in 0..50 {
+                assert!(handle.ready_task_count() <= 10);
+                yield_now().await;
+            }
+        });
+    }
+
+    #[test]
+    fn task_id() {
+        let runtime = Runtime::new();
+        assert_eq!(try_id(), None);
+        runtime.block_on(async move {
+            let main_id = id();
+            assert_eq!(try_id(), Some(main_id));
+            let task = spawn(async { id() });
+            let task_id = task.id();
+            assert_ne!(task_id, main_id);
+            assert_eq!(task.await.unwrap(), task_id);
+            assert_eq!(id(), main_id);
+        });
+    }
+
+    #[test]
+    fn current_node() {
+        let runtime = Runtime::new();
+        let node = runtime.create_node().build();
+        assert_eq!(node.id(), NodeId::from_raw(node.id().as_raw()));
+        runtime.block_on(async move {
+            assert_eq!(super::current_node(), NodeId::from_raw(0));
+            let id = node.spawn(async { super::current_node() }).await.unwrap();
+            assert_eq!(id, node.id());
+            assert_eq!(id.as_raw(), 1);
+        });
+    }
+
+    #[test]
+    fn no_pending_tasks() {
+        let runtime = Runtime::new();
+        let node = runtime.create_node().build();
+        assert_eq!(runtime.assert_no_pending_tasks(), Ok(()));
+
+        // detached task still alive after block_on returns
+        runtime.block_on(async {
+            node.spawn(time::sleep(Duration::from_secs(1)));
+        });
+        let err = runtime.assert_no_pending_tasks().unwrap_err();
+        assert_eq!(err.queued, []);
+        assert_eq!(err.alive, [(node.id(), 1)]);
+
+        // queued task never polled
+        let id = node.spawn(async {}).id();
+        let err = runtime.assert_no_pending_tasks().unwrap_err();
+        assert_eq!(err.queued, [(id, node.id())]);
+        assert_eq!(err.alive, [(node.id(), 2)]);
+
+        runtime.block_on(async { time::sleep(Duration::from_secs(2)).await });
+        assert_eq!(runtime.assert_no_pending_tasks(), Ok(()));
+    }
+
+    #[test]
+    fn create_paused() {
+        let runtime = Runtime::new();
+        let flag = Arc::new(AtomicUsize::new(0));
+        let flag_ = flag.clone();
+        let node = runtime
+            .create_node()
+            .paused(true)
+            .init(move || {
+                let flag = flag_.clone();
+                async move {
+                    flag.fetch_add(1, Ordering::SeqCst);
+                }
+            })
+            .build();
+        runtime.block_on(async move {
+            time::sleep(Duration::from_secs(1)).await;
+            assert_eq!(flag.load(Ordering::SeqCst), 0);
+
+            Handle::current().resume(node.id());
+            node.wait_ready().await;
+            assert_eq!(flag.load(Ordering::SeqCst), 1);
+        });
+    }
+
+    #[test]
+    fn max_task_count() {
+        let runtime = Runtime::new();
+        let node = runtime.create_node().build();
+        runtime.block_on(async move {
+            let handle = Handle::current();
+            for _ in 0..3 {
+                let tasks = (0..5)
+                    .map(|i| node.spawn(time::sleep(Duration::from_secs(i))))
+                    .collect::<Vec<_>>();
+                futures_util::future::join_all(tasks).await;
+            }
+            assert_eq!(node.task_count(), 0);
+            assert_eq!(node.max_task_count(), 5);
+            assert_eq!(handle.tasks_spawned_total(), 15);
+
+            // the high-water mark is reset after restart
+            handle.restart(node.id());
+            let node = handle.get_node(node.id()).unwrap();
+            assert_eq!(node.max_task_count(), 0);
+            assert_eq!(handle.tasks_spawned_total(), 15);
+        });
+    }
+
+    #[test]
+    fn spawn_on() {
+        let runtime = Runtime::new();
+        let node = runtime.create_node().build();
+        runtime.block_on(async move {
+            let handle = Handle::current();
+            let id = handle.spawn_on(node.id(), async { super::current_node() });
+            assert_eq!(id.unwrap().await.unwrap(), node.id());
+            assert!(handle.spawn_on(NodeId::from_raw(100), async {}).is_none());
+
+            // the task is killed with the node
+            let task = handle.spawn_on(node.id(), time::sleep(Duration::from_secs(1)));
+            handle.kill(node.id());
+            assert!(task.unwrap().await.unwrap_err().is_cancelled());
+            assert!(handle.spawn_on(node.id(), async {}).is_none());
+
+            handle.restart(node.id());
+            assert!(handle.spawn_on(node.id(), async {}).is_some());
+        });
+    }
+
+    #[test]
+    fn current_thread_id() {
+        let runtime = Runtime::new();
+        let node = runtime.create_node().build();
+        runtime.block_on(async move {
+            let main = super::current_thread_id();
+            assert_eq!(
+                spawn(async { super::current_thread_id() }).await.unwrap(),
+                main
+            );
+            let id = node
+                .spawn(async { super::current_thread_id() })
+                .await
+                .unwrap();
+            assert_ne!(id, main);
+            assert_eq!(id.as_u64(), node.id().as_raw() + 1);
+        });
+    }
+
+    #[test]
+    fn with_node_rng() {
+        let draw = |seed, node_rng: bool| {
+            let runtime = Runtime::with_seed_and_config(seed, crate::Config::default());
+            let node = runtime.create_node().build();
+            runtime.block_on(async move {
+                node.spawn(async move {
+                    (0..4)
+                        .map(|_| {
+                            if node_rng {
+                                super::with_node_rng(|r| r.gen_range(150..300))
+                            } else {
+                                crate::rand::thread_rng().gen_range(150..300)
+                            }
+                        })
+                        .collect::<Vec<u64>>()
+                })
+                .await
+                .unwrap()
+            })
+        };
+        let mut seqs = HashSet::new();
+        for seed in 0..10 {
+            let values = draw(seed, true);
+            assert!(values.iter().all(|v| (150..300).contains(v)));
+            // the values are drawn from the same stream as `thread_rng`
+            assert_eq!(values, draw(seed, false));
+            seqs.insert(values);
+        }
+        assert_eq!(seqs.len(), 10);
+    }
+
+    #[test]
+    fn node_state() {
+        let runtime = Runtime::new();
+        let node = runtime.create_node().build();
+        let handle = runtime.handle();
+        let id = node.id();
+        assert!(handle.is_alive(NodeId::zero()));
+        assert!(!handle.is_paused(NodeId::zero()));
+        assert!(!handle.is_alive(NodeId::from_raw(100)));
+
+        assert!(handle.is_alive(id) && !handle.is_paused(id));
+        handle.pause(id);
+        assert!(handle.is_paused(id));
+        handle.resume(id);
+        assert!(!handle.is_paused(id));
+        handle.kill(id);
+        assert!(!handle.is_alive(id));
+        handle.restart(id);
+        assert!(handle.is_alive(id));
+    }
+
+    #[test]
+    fn alive_tasks() {
+        let runtime = Runtime::new();
+        let node = runtime.create_node().build();
+        runtime.block_on(async move {
+            let node = Handle::current().task.get_node(node.id()).unwrap();
+            let t1 = Builder::new()
+                .name("worker")
+                .spawn_on(time::sleep(Duration::from_secs(1)), &node)
+                .unwrap();
+            let t2 = node.spawn(time::sleep(Duration::from_secs(2)));
+            assert_eq!(node.alive_task_ids(), [t1.id(), t2.id()]);
+            assert_eq!(
+                node.alive_tasks(),
+                [(t1.id(), Some("worker".into())), (t2.id(), None)]
+            );
+            t1.await.unwrap();
+            assert_eq!(node.alive_task_ids(), [t2.id()]);
+            t2.abort();
+            time::sleep(Duration::from_millis(1)).await;
+            assert_eq!(node.alive_task_ids(), []);
+        });
+    }
+
+    #[test]
+    fn wait_ready() {
+        let runtime = Runtime::new();
+        let flag = Arc::new(AtomicUsize::new(0));
+        let flag_ = flag.clone();
+        let node = runtime
+            .create_node()
+            .init(move || {
+                let flag = flag_.clone();
+                async move {
+                    time::sleep(Duration::from_secs(1)).await;
+                    flag.fetch_add(1, Ordering::SeqCst);
+                    // the node is ready once the initial task yields
+                    time::sleep(Duration::from_secs(1)).await;
+                    flag.fetch_add(1, Ordering::SeqCst);
+                    futures_util::future::pending::<()>().await;
+                }
+            })
+            .build();
+        let empty = runtime.create_node().build();
+        runtime.block_on(async move {
+            empty.wait_ready().await;
+            node.wait_ready().await;
+            assert_eq!(flag.load(Ordering::SeqCst), 0);
+
+            // wait again after restart
+            let handle = Handle::current();
+            time::sleep(Duration::from_secs(3)).await;
+            assert_eq!(flag.load(Ordering::SeqCst), 2);
+            handle.restart(node.id());
+            handle.get_node(node.id()).unwrap().wait_ready().await;
+            assert_eq!(flag.load(Ordering::SeqCst), 2);
+        });
+    }
+
+    #[test]
+    fn step() {
+        let runtime = Runtime::new();
+        let node = runtime.create_node().build();
+        let flag = Arc::new(AtomicUsize::new(0));
+        let flag_ = flag.clone();
+        node.spawn(async move {
+            loop {
+                flag_.fetch_add(1, Ordering::SeqCst);
+                yield_now().await;
+            }
+        });
+        for i in 1..=3 {
+            assert!(runtime.step());
+            assert_eq!(flag.load(Ordering::SeqCst), i);
+        }
+    }
+
+    #[test]
+    fn fifo_scheduling() {
+        let mut config = crate::Config::default();
+        config.task.scheduling = Scheduling::Fifo;
+        let runtime = Runtime::with_seed_and_config(0, config);
+        let seq = runtime.block_on(async {
+            let (tx, rx) = std::sync::mpsc::channel();
+            let mut tasks = vec![];
+            for i in 0..3 {
+                let tx = tx.clone();
+                tasks.push(spawn(async move {
+                    for j in 0..3 {
+                        tx.send(i * 10 + j).unwrap();
+                        yield_now().await;
+                    }
+                }));
+            }
+            drop(tx);
+            futures_util::future::join_all(tasks).await;
+            rx.into_iter().collect::<Vec<_>>()
+        });
+        assert_eq!(seq, [0, 10, 20, 1, 11, 21, 2, 12, 22]);
+    }
+
+    #[test]
+    fn reorder_probability() {
+        let run = |seed: u64, p: f64| {
+            let mut config = crate::Config::default();
+            config.task.scheduling = Scheduling::Fifo;
+            config.task.reorder_probability = p;
+            let runtime = Runtime::with_seed_and_config(seed, config);
+            runtime.block_on(async {
+                let (tx, rx) = std::sync::mpsc::channel();
+                let mut tasks = vec![];
+                for i in 0..3 {
+                    let tx = tx.clone();
+                    tasks.push(spawn(async move {
+                        for j in 0..3 {
+                            tx.send(i * 10 + j).unwrap();
+                            yield_now().await;
+                        }
+                    }));
+                }
+                drop(tx);
+                futures_util::future::join_all(tasks).await;
+                rx.into_iter().collect::<Vec<_>>()
+            })
+        };
+        let fifo = [0, 10, 20, 1, 11, 21, 2, 12, 22];
+        assert_eq!(run(0, 0.0), fifo);
+        // tasks are deferred a limited number of times, so they always finish
+        assert_eq!(run(0, 1.0).len(), 9);
+        // deterministic for the same seed
+        assert_eq!(run(1, 0.5), run(1, 0.5));
+        assert!((0..10).any(|seed| run(seed, 0.5) != fifo));
+    }
+
+    #[test]
+    fn task_budget() {
+        let run = |budget: Option<u32>| {
+            let mut config = crate::Config::default();
+            config.task.scheduling = Scheduling::Fifo;
+            config.task.task_budget = budget;
+            let runtime = Runtime::with_seed_and_config(0, config);
+            runtime.block_on(async {
+                let (tx, rx) = std::sync::mpsc::channel();
+                let mut tasks = vec![];
+                for i in 0..2 {
+                    let tx = tx.clone();
+                    tasks.push(spawn(async move {
+                        for _ in 0..10 {
+                            tx.send(i).unwrap();
+                            consume_budget().await;
+                        }
+                    }));
+                }
+                drop(tx);
+                futures_util::future::join_all(tasks).await;
+                rx.into_iter().collect::<Vec<_>>()
+            })
+        };
+        // a task never yields without budget
+        assert_eq!(run(None), [[0; 10], [1; 10]].concat());
+        // forced to yield after 4 units are consumed
+        let seq = run(Some(4));
+        assert_eq!(seq[..6], [0, 0, 0, 0, 0, 1]);
+        assert_eq!(seq.len(), 20);
+    }
+
+    #[test]
+    fn task_budget_ready_channel() {
+        let run = |budget: Option<u32>| {
+            let mut config = crate::Config::default();
+            config.task.scheduling = Scheduling::Fifo;
+            config.task.task_budget = budget;
+            let runtime = Runtime::with_seed_and_config(0, config);
+            runtime.block_on(async {
+                let (tx, mut rx) = crate::sync::mpsc::channel(10);
+                for i in 0..10 {
+                    tx.try_send(i).unwrap();
+                }
+                drop(tx);
+                let (log_tx, log_rx) = std::sync::mpsc::channel();
+                let log_tx0 = log_tx.clone();
+                // drains a channel that is always ready
+                let drain = spawn(async move {
+                    while let Some(i) = rx.recv().await {
+                        log_tx0.send(i).unwrap();
+                    }
+                });
+                let other = spawn(async move { log_tx.send(-1).unwrap() });
+                drain.await.unwrap();
+                other.await.unwrap();
+                log_rx.into_iter().collect::<Vec<_>>()
+            })
+        };
+        // the draining task never yields without budget
+        assert_eq!(run(None), [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, -1]);
+        // forced to yield after 4 values are received
+        assert_eq!(run(Some(4)), [0, 1, 2, 3, -1, 4, 5, 6, 7, 8, 9]);
+    }
+
+    #[test]
+    fn block_in_place() {
+        let mut config = crate::Config::default();
+        config.task.blocking_op_duration = Duration::from_secs(1)..Duration::from_secs(2);
+        let runtime = Runtime::with_seed_and_config(0, config);
+        runtime.block_on(async {
+            let t0 = time::Instant::now();
+            let sleep = spawn(time::sleep(Duration::from_millis(500)));
+            assert_eq!(super::block_in_place(|| 1), 1);
+            let elapsed = t0.elapsed();
+            assert!(elapsed >= Duration::from_secs(1) && elapsed < Duration::from_secs(2));
+
+            // the timer expired during the blocking operation fires after it
+            sleep.await.unwrap();
+            assert!(t0.elapsed() - elapsed < Duration::from_millis(1));
+        });
+    }
+
+    #[test]
+    fn node_event_hook() {
+        let runtime = Runtime::new();
+        let events = Arc::new(Mutex::new(vec![]));
+        let events0 = events.clone();
+        runtime.set_node_event_hook(move |event| events0.lock().push((event.node, event.kind)));
+        let node = runtime.create_node().build();
+        let id = node.id();
+        runtime.block_on(async move {
+            let handle = Handle::current();
+            handle.pause(id);
+            handle.resume(id);
+            handle.restart(id);
+            handle.kill(id);
+        });
+        use NodeEventKind::*;
+        assert_eq!(
+            *events.lock(),
+            [
+                (id, Created),
+                (id, Paused),
+                (id, Resumed),
+                (id, Killed),
+                (id, Restarted),
+                (id, Killed)
+            ]
+        );
+    }
+
+    #[test]
+    fn node_events_drop_oldest() {
+        use futures_util::StreamExt;
+
+        let runtime = Runtime::new();
+        let node = runtime.create_node().build();
+        let id = node.id();
+        runtime.block_on(async move {
+            let handle = Handle::current();
+            let mut events = handle.node_events();
+            for _ in 0..NODE_EVENTS_CAPACITY {
+                handle.pause(id);
+            }
+            handle.resume(id);
+            assert_eq!(events.dropped(), 1);
+            for _ in 1..NODE_EVENTS_CAPACITY {
+                assert_eq!(events.next().await.unwrap().kind, NodeEventKind::Paused);
+            }
+            assert_eq!(events.next().await.unwrap().kind, NodeEventKind::Resumed);
+
+            // the stream waits for new events
+            let task = spawn(async move { events.next().await.unwrap().kind });
+            time::sleep(Duration::from_secs(1)).await;
+            handle.kill(id);
+            assert_eq!(task.await.unwrap(), NodeEventKind::Killed);
+        });
+    }
+
+    #[test]
+    #[should_panic(expected = "node 1 (\"server\") exceeded the limit of 3 alive tasks")]
+    fn max_tasks() {
+        let runtime = Runtime::new();
+        let node = runtime.create_node().name("server").max_tasks(3).build();
+        runtime.block_on(async move {
+            node.spawn(async {
+                // finished tasks are not counted
+                spawn(async {}).await.unwrap();
+                for _ in 0..3 {
+                    spawn(time::sleep(Duration::from_secs(1))).detach();
+                }
+            })
+            .await
+            .unwrap();
+        });
+    }
+
+    #[test]
+    fn oom_kill() {
+        let runtime = Runtime::new();
+        let events = Arc::new(Mutex::new(vec![]));
+        let events0 = events.clone();
+        runtime.set_node_event_hook(move |event| events0.lock().push(event.kind));
+        let node = runtime.create_node().memory_limit(90).build();
+        let id = node.id();
+        runtime.block_on(async move {
+            let task = node.spawn(async {
+                record_alloc(60);
+                record_free(20);
+                time::sleep(Duration::from_secs(1)).await;
+                record_alloc(60);
+                time::sleep(Duration::from_secs(1)).await;
+                unreachable!("the node should be killed");
+            });
+            time::sleep(Duration::from_millis(500)).await;
+            assert_eq!(node.memory_usage(), 40);
+            assert!(task.await.unwrap_err().is_cancelled());
+            assert!(!Handle::current().is_alive(id));
+            // the usage is reset but the limit is kept
+            let node = Handle::current().get_node(id).unwrap();
+            assert_eq!(node.memory_usage(), 0);
+        });
+        assert_eq!(
+            *events.lock(),
+            [NodeEventKind::Created, NodeEventKind::OomKilled]
+        );
+    }
+
+    #[test]
+    fn deadlock_report() {
+        let runtime = Runtime::new();
+        let node = runtime.create_node().build();
+        let result = std::panic::catch_unwind(AssertUnwindSafe(|| {
+            runtime.block_on(async move {
+                let (tx, rx) = tokio::sync::oneshot::channel::<()>();
+                let task = node
+                    .spawn(async move { Builder::new().name("waiter").spawn(rx).unwrap().await });
+                task.await.unwrap().unwrap().unwrap();
+                drop(tx);
+            })
+        }));
+        let payload = result.unwrap_err();
+        let msg = payload.downcast_ref::<String>().unwrap();
+        assert!(msg.starts_with("no events, all tasks will block forever"));
+        assert_eq!(msg.matches("on node 1").count(), 2, "{msg}");
+        assert!(msg.contains("(\"waiter\")"), "{msg}");
+    }
+
+    #[test]
+    fn task_poll_hook() {
+        let trace = || {
+            let mut runtime = Runtime::with_seed_and_config(1, crate::Config::default());
+            let trace = Arc::new(Mutex::new(vec![]));
+            let trace0 = trace.clone();
+            runtime.set_task_poll_hook(move |event| trace0.lock().push(event));
+            runtime.block_on(async {
+                let tasks = (0..3).map(|_| spawn(yield_now())).collect::<Vec<_>>();
+                futures_util::future::join_all(tasks).await;
+            });
+            let trace = trace.lock();
+            trace
+                .iter()
+                .map(|e| (e.task, e.node, e.time))
+                .collect::<Vec<_>>()
+        };
+        let trace1 = trace();
+        assert!(trace1.iter().all(|(_, node, _)| *node == NodeId::zero()));
+        assert!(trace1.windows(2).all(|w| w[0].2 <= w[1].2));
+        // the main task is polled first, then each spawned task twice for `yield_now`
+        let main = trace1[0].0;
+        let mut polls = HashMap::<Id, usize>::new();
+        for (task, _, _) in trace1.iter().filter(|(task, _, _)| *task != main) {
+            *polls.entry(*task).or_default() += 1;
+        }
+        assert_eq!(polls.len(), 3);
+        assert!(polls.values().all(|&n| n == 2));
+        // task IDs are assigned in spawn order within a runtime
+        assert_eq!(trace1, trace());
+    }
+
+    #[test]
+    fn poll_latency() {
+        let mut runtime = Runtime::new();
+        runtime.set_poll_latency(|_| Duration::from_millis(1));
+        runtime.block_on(async {
+            let t0 = Instant::now();
+            for _ in 0..10 {
+                yield_now().await;
+            }
+            assert_eq!(t0.elapsed(), Duration::from_millis(10));
+        });
+
+        let elapsed = |seed| {
+            let mut runtime = Runtime::with_seed_and_config(seed, crate::Config::default());
+            runtime.set_poll_latency(lognormal_latency(Duration::from_micros(10), 2.0));
+            runtime.block_on(async {
+                let t0 = Instant::now();
+                for _ in 0..100 {
+                    yield_now().await;
+                }
+                t0.elapsed()
+            })
+        };
+        assert_eq!(elapsed(1), elapsed(1));
+        assert!(elapsed(1) > Duration::from_micros(100));
+    }
+
+    #[test]
+    fn lognormal_latency_distribution() {
+        use rand::SeedableRng;
+        let mut rng = rand::rngs::SmallRng::seed_from_u64(1);
+        let median = Duration::from_micros(10);
+        let fixed = lognormal_latency(median, 0.0);
+        assert!((0..10).all(|_| fixed(&mut rng) == median));
+
+        let latency = lognormal_latency(median, 2.0);
+        let mut samples = (0..1001).map(|_| latency(&mut rng)).collect::<Vec<_>>();
+        samples.sort();
+        assert!(samples[500] > median / 2 && samples[500] < median * 2);
+        // a heavy tail of long stalls
+        assert!(samples[1000] > median * 100);
+    }
+
+    #[test]
+    fn clock_skew() {
+        let runtime = Runtime::new();
+        let node = runtime
+            .create_node()
+            .clock_skew(Duration::from_secs(5))
+            .build();
+        runtime.block_on(async move {
+            let t0 = time::Instant::now();
+            let (t1, t2) = node
+                .spawn(async {
+                    let t1 = time::Instant::now();
+                    time::sleep_until(t1 + Duration::from_secs(1)).await;
+                    (t1, time::Instant::now())
+                })
+                .await
+                .unwrap();
+            let t3 = time::Instant::now();
+            assert!(t1 - t0 >= Duration::from_secs(5));
+            assert!(t1 - t0 < Duration::from_secs(6));
+            assert!(t2 - t1 >= Duration::from_secs(1));
+            // the node-visible clock does not affect the global clock
+            assert!(t3 - t0 >= Duration::from_secs(1));
+            assert!(t3 - t0 < Duration::from_secs(2));
+        });
+    }
+
+    #[test]
+    fn model_cpu_parallelism() {
+        let elapsed = |model_cpu_parallelism: bool| {
+            let mut config = crate::Config::default();
+            config.task.model_cpu_parallelism = model_cpu_parallelism;
+            config.task.poll_duration = Duration::from_nanos(100)..Duration::from_nanos(101);
+            let runtime = Runtime::with_seed_and_config(0, config);
+            let node = runtime.create_node().cores(4).build();
+            runtime
+                .block_on(node.spawn(async {
+                    for _ in 0..99 {
+                        yield_now().await;
+                    }
+                }))
+                .unwrap();
+            runtime.elapsed()
+        };
+        // 100 polls on the node, and 2 polls on the main node
+        assert_eq!(elapsed(false), Duration::from_nanos(100 * 100 + 2 * 100));
+        assert_eq!(elapsed(true), Duration::from_nanos(100 * 25 + 2 * 100));
+    }
+
+    #[test]
+    fn spawn_blocking_duration() {
+        let mut config = crate::Config::default();
+        config.task.blocking_op_duration = Duration::from_millis(10)..Duration::from_millis(20);
+        let runtime = Runtime::with_seed_and_config(0, config);
+        runtime.block_on(async {
+            let t0 = time::Instant::now();
+            let flag = Arc::new(AtomicUsize::new(0));
+            let flag_ = flag.clone();
+            let handle = spawn_blocking(move || flag_.store(1, Ordering::SeqCst));
+            // other tasks can run in the meantime
+            spawn(async {}).await.unwrap();
+            assert_eq!(flag.load(Ordering::SeqCst), 0);
+            handle.await.unwrap();
+            assert_eq!(flag.load(Ordering::SeqCst), 1);
+            let elapsed = t0.elapsed();
+            assert!(elapsed >= Duration::from_millis(10));
+            assert!(elapsed < Duration::from_millis(21));
+        });
+    }
+
+    #[test]
+    fn priority() {
+        for scheduling in [Scheduling::Random, Scheduling::Fifo] {
+            let mut config = crate::Config::default();
+            config.task.scheduling = scheduling;
+            let runtime = Runtime::with_seed_and_config(0, config);
+            runtime.block_on(async {
+                let order = Arc::new(Mutex::new(vec![]));
+                let handles = (0..4u8)
+                    .map(|i| {
+                        let order = order.clone();
+                        let priority = if i == 3 { 1 } else { 0 };
+                        Builder::new()
+                            .priority(priority)
+                            .spawn(async move {
+                                for _ in 0..10 {
+                                    order.lock().push(i);
+                                    yield_now().await;
+                                }
+                            })
+                            .unwrap()
+                    })
+                    .collect::<Vec<_>>();
+                for handle in handles {
+                    handle.await.unwrap();
+                }
+                // the high-priority task is never preempted by others
+                assert_eq!(order.lock()[..10], [3; 10]);
+            });
+        }
+    }
+
+    #[test]
+    fn blocking_pool_size() {
+        let mut config = crate::Config::default();
+        config.task.blocking_op_duration = Duration::from_millis(10)..Duration::from_millis(11);
+        config.task.blocking_pool_size = Some(2);
+        let runtime = Runtime::with_seed_and_config(0, config);
+        runtime.block_on(async {
+            let t0 = time::Instant::now();
+            let running = Arc::new(AtomicUsize::new(0));
+            let handles = (0..5)
+                .map(|_| {
+                    let running = running.clone();
+                    spawn_blocking(move || {
+                        assert!(running.fetch_add(1, Ordering::SeqCst) < 2);
+                        running.fetch_sub(1, Ordering::SeqCst);
+                    })
+                })
+                .collect::<Vec<_>>();
+            for handle in handles {
+                handle.await.unwrap();
+            }
+            // 5 closures run in 3 rounds
+            let elapsed = t0.elapsed();
+            assert!(elapsed >= Duration::from_millis(30));
+            assert!(elapsed < Duration::from_millis(34));
+        });
+    }
+
+    #[test]
+    fn set_cores() {
+        let runtime = Runtime::new();
+        let node = runtime.create_node().cores(2).build();
+        let handle = runtime.handle().clone();
+        let parallelism = |node: &NodeHandle| {
+            runtime
+                .block_on(node.spawn(async { std::thread::available_parallelism().unwrap().get() }))
+                .unwrap()
+        };
+        assert_eq!(parallelism(&node), 2);
+        handle.set_cores(node.id(), 8);
+        assert_eq!(parallelism(&node), 8);
+
+        // the number of cores is kept after restart
+        handle.restart(node.id());
+        let node = handle.get_node(node.id()).unwrap();
+        assert_eq!(parallelism(&node), 8);
+    }
+
+    #[test]
+    fn deterministic_std_thread_available_parallelism() {
+        let runtime = Runtime::new();
+        runtime.block_on(async move {
+            // available_parallelism is 1 by default
+            assert_eq!(std::thread::available_parallelism().unwrap().get(), 1);
+        });
+        let f1 = runtime.create_node().build().spawn(async move {
+            // available_parallelism is 1 by default
+            assert_eq!(std::thread::available_parallelism().unwrap().get(), 1);
+        });
+        let f2 = runtime.create_node().cores(128).build().spawn(async move {
+            assert_eq!(std::thread::available_parallelism().unwrap().get(), 128);
+        });
+        runtime.block_on(f1).unwrap();
+        runtime.block_on(f2).unwrap();
+    }
+
+    #[test]
+    fn select_any_tie_break() {
+        let winner = |seed| {
+            let runtime = Runtime::with_seed_and_config(seed, crate::Config::default());
+            runtime.block_on(async {
+                let handles = (0..8)
+                    .map(|i| {
+                        spawn(async move {
+                            time::sleep(Duration::from_secs(1)).await;
+                            i
+                        })
+                    })
+                    .collect();
+                // all tasks complete at the same time
+                time::sleep(Duration::from_secs(2)).await;
+                let (output, index, remaining) = select_any(handles).await;
+                assert_eq!(output.unwrap(), index);
+                // the remaining handles are the others in their original order
+                let mut others = vec![];
+                for handle in remaining {
+                    others.push(handle.await.unwrap());
+                }
+                assert_eq!(others, (0..8).filter(|&i| i != index).collect::<Vec<_>>());
+                index
+            })
+        };
+        let mut winners = HashSet::new();
+        for seed in 0..50 {
+            let index = winner(seed);
+            assert_eq!(index, winner(seed));
+            winners.insert(index);
+        }
+        // any of the tasks completing at the same time can win
+        assert_eq!(winners, (0..8).collect());
+    }
+
+    #[test]
+    fn spawn_detached() {
+        let runtime = Runtime::new();
+        let node = runtime.create_node().build();
+        let flag = Arc::new(AtomicUsize::new(0));
+        let flag0 = flag.clone();
+        node.spawn_detached(async move {
+            loop {
+                time::sleep(Duration::from_secs(1)).await;
+                flag0.fetch_add(1, Ordering::SeqCst);
+            }
+        });
+        runtime.block_on(async move {
+            time::sleep(Duration::from_millis(3500)).await;
+            assert_eq!(flag.load(Ordering::SeqCst), 3);
+            assert_eq!(node.task_count(), 1);
+            Handle::current().kill(node.id());
+            time::sleep(Duration::from_secs(1)).await;
+            assert_eq!(node.task_count(), 0);
+        });
+    }
+
+    #[test]
+    fn strict_join_handles() {
+        #[derive(Clone, Default)]
+        struct Buf(Arc<std::sync::Mutex<Vec<u8>>>);
+        impl io::Write for Buf {
+            fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+                self.0.lock().unwrap().write(buf)
+            }
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let buf = Buf::default();
+        let buf0 = buf.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .with_writer(move || buf0.clone())
+            .with_ansi(false)
+            .finish();
+        let mut config = crate::Config::default();
+        config.task.strict_join_handles = true;
+        tracing::subscriber::with_default(subscriber, || {
+            // create spans with the subscriber
+            let runtime = Runtime::with_seed_and_config(1, config);
+            runtime.block_on(async {
+                // completed or explicitly detached tasks do not warn
+                let finished = spawn(async {});
+                time::sleep(Duration::from_secs(1)).await;
+                drop(finished);
+                spawn(std::future::pending::<()>()).detach();
+                assert!(buf.0.lock().unwrap().is_empty());
+
+                let forgotten = Builder::new()
+                    .name("forgotten")
+                    .spawn(std::future::pending::<()>())
+                    .unwrap();
+                drop(forgotten);
+            });
+        });
+        let output = String::from_utf8(buf.0.lock().unwrap().clone()).unwrap();
+        assert!(output.contains("JoinHandle dropped before the task completed"));
+        assert!(output.contains("forgotten"));
     }
 }
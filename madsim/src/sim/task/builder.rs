@@ -0,0 +1,107 @@
+use super::{JoinHandle, TaskNodeHandle};
+use std::{future::Future, io};
+
+/// Factory which is used to configure the properties of a new task.
+///
+/// # Example
+///
+/// ```
+/// use madsim::{runtime::Runtime, task};
+///
+/// Runtime::new().block_on(async {
+///     let handle = task::Builder::new()
+///         .name("worker")
+///         .spawn(async { 1 })
+///         .unwrap();
+///     assert_eq!(handle.await.unwrap(), 1);
+/// });
+/// ```
+#[derive(Default, Debug)]
+pub struct Builder<'a> {
+    name: Option<&'a str>,
+    priority: u8,
+}
+
+impl<'a> Builder<'a> {
+    /// Creates a new task builder.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Assigns a name to the task which will be spawned.
+    ///
+    /// The name is recorded in the tracing span of the task and in [`JoinError`](super::JoinError).
+    pub fn name(&self, name: &'a str) -> Self {
+        Self {
+            name: Some(name),
+            priority: self.priority,
+        }
+    }
+
+    /// Assigns a priority to the task which will be spawned.
+    ///
+    /// Ready tasks with higher priority always run before those with lower priority,
+    /// and ties are broken by the [scheduling policy](crate::task::Scheduling).
+    /// The default priority is 0, the lowest one.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use madsim::{runtime::Runtime, task};
+    /// use std::sync::{Arc, Mutex};
+    ///
+    /// Runtime::new().block_on(async {
+    ///     let order = Arc::new(Mutex::new(vec![]));
+    ///     let handles = (0..3u8)
+    ///         .map(|i| {
+    ///             let order = order.clone();
+    ///             task::Builder::new()
+    ///                 .priority(i)
+    ///                 .spawn(async move { order.lock().unwrap().push(i) })
+    ///                 .unwrap()
+    ///         })
+    ///         .collect::<Vec<_>>();
+    ///     for handle in handles {
+    ///         handle.await.unwrap();
+    ///     }
+    ///     assert_eq!(*order.lock().unwrap(), [2, 1, 0]);
+    /// });
+    /// ```
+    pub fn priority(&self, priority: u8) -> Self {
+        Self {
+            name: self.name,
+            priority,
+        }
+    }
+
+    /// Spawns a task with this builder's settings on the current node.
+    pub fn spawn<F>(self, future: F) -> io::Result<JoinHandle<F::Output>>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        self.spawn_on(future, &TaskNodeHandle::current())
+    }
+
+    /// Spawns a task with this builder's settings on the provided node.
+    pub fn spawn_on<F>(
+        self,
+        future: F,
+        handle: &TaskNodeHandle,
+    ) -> io::Result<JoinHandle<F::Output>>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        Ok(handle.spawn_inner(future, self.name, self.priority))
+    }
+
+    /// Spawns a `!Send` task on the current node with this builder's settings.
+    pub fn spawn_local<F>(self, future: F) -> io::Result<JoinHandle<F::Output>>
+    where
+        F: Future + 'static,
+        F::Output: 'static,
+    {
+        Ok(TaskNodeHandle::current().spawn_inner(future, self.name, self.priority))
+    }
+}
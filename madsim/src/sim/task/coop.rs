@@ -0,0 +1,119 @@
+//! Cooperative scheduling.
+//!
+//! A task that keeps awaiting ready futures never returns to the executor,
+//! which starves other tasks. Like tokio, each task is given a budget at the
+//! beginning of a poll. Every time a leaf future is ready, one unit is consumed.
+//! Once the budget is exhausted, leaf futures return `Pending` and the task is
+//! rescheduled.
+
+use std::{
+    cell::Cell,
+    future::poll_fn,
+    task::{Context, Poll},
+    time::Duration,
+};
+
+thread_local! {
+    static BUDGET: Cell<Option<u32>> = const { Cell::new(None) };
+}
+
+/// Sets the budget of the current poll. `None` means unconstrained.
+pub(crate) fn budget(budget: Option<u32>) -> ResetGuard {
+    ResetGuard(BUDGET.with(|b| b.replace(budget)))
+}
+
+pub(crate) struct ResetGuard(Option<u32>);
+
+impl Drop for ResetGuard {
+    fn drop(&mut self) {
+        BUDGET.with(|b| b.set(self.0));
+    }
+}
+
+/// Consumes one unit of budget. Returns `Pending` and wakes the task if the budget is exhausted.
+///
+/// The unit is given back when the returned guard is dropped, unless the caller made progress.
+pub(crate) fn poll_proceed(cx: &mut Context<'_>) -> Poll<RestoreOnPending> {
+    BUDGET.with(|b| match b.get() {
+        None => Poll::Ready(RestoreOnPending(Cell::new(false))),
+        Some(0) => {
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        }
+        Some(n) => {
+            b.set(Some(n - 1));
+            Poll::Ready(RestoreOnPending(Cell::new(true)))
+        }
+    })
+}
+
+/// Gives the unit of budget consumed by [`poll_proceed`] back if the future is still pending.
+pub(crate) struct RestoreOnPending(Cell<bool>);
+
+impl RestoreOnPending {
+    /// Keeps the unit consumed, since the future is ready.
+    pub(crate) fn made_progress(&self) {
+        self.0.set(false);
+    }
+}
+
+impl Drop for RestoreOnPending {
+    fn drop(&mut self) {
+        if self.0.get() {
+            BUDGET.with(|b| b.set(b.get().map(|n| n + 1)));
+        }
+    }
+}
+
+/// Consumes a unit of budget and returns control to the scheduler if the budget is exhausted.
+///
+/// This has no effect unless [`Config::task_budget`](super::Config::task_budget) is set.
+pub async fn consume_budget() {
+    poll_fn(|cx| poll_proceed(cx).map(|coop| coop.made_progress())).await
+}
+
+/// Yields execution back to the scheduler.
+///
+/// The current task is pushed back to the ready queue, where it competes with the other
+/// ready tasks to be picked next by the global random number generator. So each yield is
+/// a scheduling point of the deterministic model, unlike `tokio::task::yield_now`, which
+/// may defer the wakeup.
+pub async fn yield_now() {
+    let mut yielded = false;
+    poll_fn(|cx| {
+        if yielded {
+            return Poll::Ready(());
+        }
+        yielded = true;
+        tracing::trace!("yield");
+        cx.waker().wake_by_ref();
+        Poll::Pending
+    })
+    .await
+}
+
+/// Yields execution back to the scheduler, and stays descheduled until the simulated clock
+/// has advanced by `duration`.
+///
+/// The task is woken by a timer, just like [`sleep`](crate::time::sleep), but the call
+/// reads as a forced deschedule window rather than a wait. It is handy for widening a race
+/// window deterministically. With a zero duration, it is the same as [`yield_now`].
+///
+/// # Example
+///
+/// ```
+/// use madsim::{runtime::Runtime, task, time::{Duration, Instant}};
+///
+/// Runtime::new().block_on(async {
+///     let t0 = Instant::now();
+///     task::yield_for(Duration::from_millis(10)).await;
+///     assert!(t0.elapsed() >= Duration::from_millis(10));
+/// });
+/// ```
+pub async fn yield_for(duration: Duration) {
+    if duration.is_zero() {
+        return yield_now().await;
+    }
+    tracing::trace!(?duration, "yield");
+    crate::time::sleep(duration).await
+}
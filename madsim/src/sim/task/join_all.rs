@@ -0,0 +1,131 @@
+use rand::seq::SliceRandom;
+use std::{
+    fmt,
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+/// Creates a future which represents a collection of the outputs of the futures given.
+///
+/// Unlike [`futures::future::join_all`], which polls the pending futures in the order given,
+/// they are polled in a random order chosen by the deterministic random number generator.
+/// So a future given first gets no head start over the others when several are ready.
+///
+/// The outputs are returned in the same order as the futures given.
+///
+/// [`futures::future::join_all`]: https://docs.rs/futures/0.3/futures/future/fn.join_all.html
+///
+/// # Example
+///
+/// ```
+/// use madsim::{runtime::Runtime, task};
+///
+/// Runtime::new().block_on(async {
+///     let handles = (0..3).map(|i| task::spawn(async move { i }));
+///     let outputs = task::join_all(handles).await;
+///     let outputs = outputs.into_iter().map(Result::unwrap).collect::<Vec<_>>();
+///     assert_eq!(outputs, [0, 1, 2]);
+/// });
+/// ```
+pub fn join_all<I>(iter: I) -> JoinAll<I::Item>
+where
+    I: IntoIterator,
+    I::Item: Future,
+{
+    let futures = iter
+        .into_iter()
+        .map(|f| Some(Box::pin(f)))
+        .collect::<Vec<_>>();
+    let outputs = futures.iter().map(|_| None).collect();
+    JoinAll { futures, outputs }
+}
+
+/// Future returned by [`join_all`].
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct JoinAll<F: Future> {
+    futures: Vec<Option<Pin<Box<F>>>>,
+    outputs: Vec<Option<F::Output>>,
+}
+
+// the futures are boxed and the outputs are never pinned
+impl<F: Future> Unpin for JoinAll<F> {}
+
+impl<F: Future> Future for JoinAll<F> {
+    type Output = Vec<F::Output>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = &mut *self;
+        let mut pending = (0..this.futures.len())
+            .filter(|&i| this.futures[i].is_some())
+            .collect::<Vec<_>>();
+        if pending.len() > 1 {
+            pending.shuffle(&mut crate::rand::thread_rng());
+        }
+        let mut all_done = true;
+        for i in pending {
+            let future = this.futures[i].as_mut().unwrap();
+            match future.as_mut().poll(cx) {
+                Poll::Ready(output) => {
+                    this.outputs[i] = Some(output);
+                    this.futures[i] = None;
+                }
+                Poll::Pending => all_done = false,
+            }
+        }
+        if !all_done {
+            return Poll::Pending;
+        }
+        let outputs = std::mem::take(&mut this.outputs);
+        Poll::Ready(outputs.into_iter().map(Option::unwrap).collect())
+    }
+}
+
+impl<F: Future> fmt::Debug for JoinAll<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("JoinAll")
+            .field("len", &self.futures.len())
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runtime::Runtime;
+    use std::{
+        collections::HashSet,
+        sync::{Arc, Mutex},
+    };
+
+    #[test]
+    fn random_poll_order() {
+        let run = |seed: u64| {
+            let runtime = Runtime::with_seed_and_config(seed, crate::Config::default());
+            runtime.block_on(async {
+                let log = Arc::new(Mutex::new(vec![]));
+                let futures = (0..10).map(|i| {
+                    let log = log.clone();
+                    async move {
+                        log.lock().unwrap().push(i);
+                        i
+                    }
+                });
+                assert_eq!(join_all(futures).await, (0..10).collect::<Vec<_>>());
+                let log = log.lock().unwrap().clone();
+                log
+            })
+        };
+        let mut orders = HashSet::new();
+        for seed in 0..10 {
+            let order = run(seed);
+            assert_eq!(order, run(seed));
+            // every future is polled exactly once
+            let mut polled = order.clone();
+            polled.sort();
+            assert_eq!(polled, (0..10).collect::<Vec<_>>());
+            orders.insert(order);
+        }
+        assert_eq!(orders.len(), 10);
+    }
+}
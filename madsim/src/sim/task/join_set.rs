@@ -0,0 +1,158 @@
+use super::{AbortHandle, JoinError, JoinHandle, TaskNodeHandle};
+use futures_util::future::poll_fn;
+use std::{
+    fmt,
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+/// A collection of tasks spawned on the runtime.
+///
+/// A `JoinSet` can be used to await the completion of some or all of the tasks
+/// in the set. The set is not ordered, and the tasks will be returned in the
+/// order they complete.
+///
+/// All of the tasks must have the same return type `T`.
+///
+/// When the `JoinSet` is dropped, all tasks in the `JoinSet` are immediately aborted.
+///
+/// # Example
+///
+/// ```
+/// use madsim::{runtime::Runtime, task::JoinSet};
+///
+/// Runtime::new().block_on(async {
+///     let mut set = JoinSet::new();
+///     for i in 0..10 {
+///         set.spawn(async move { i });
+///     }
+///     let mut seen = [false; 10];
+///     while let Some(res) = set.join_next().await {
+///         seen[res.unwrap()] = true;
+///     }
+///     assert!(seen.iter().all(|&b| b));
+/// });
+/// ```
+pub struct JoinSet<T> {
+    tasks: Vec<JoinHandle<T>>,
+}
+
+impl<T> JoinSet<T> {
+    /// Create a new `JoinSet`.
+    pub fn new() -> Self {
+        JoinSet { tasks: Vec::new() }
+    }
+
+    /// Returns the number of tasks currently in the `JoinSet`.
+    pub fn len(&self) -> usize {
+        self.tasks.len()
+    }
+
+    /// Returns whether the `JoinSet` is empty.
+    pub fn is_empty(&self) -> bool {
+        self.tasks.is_empty()
+    }
+}
+
+impl<T: Send + 'static> JoinSet<T> {
+    /// Spawn the provided task on the `JoinSet`, returning an [`AbortHandle`]
+    /// that can be used to remotely cancel the task.
+    pub fn spawn<F>(&mut self, task: F) -> AbortHandle
+    where
+        F: Future<Output = T> + Send + 'static,
+    {
+        self.insert(super::spawn(task))
+    }
+
+    /// Spawn the provided task on the provided node and store it in this `JoinSet`,
+    /// returning an [`AbortHandle`] that can be used to remotely cancel the task.
+    pub fn spawn_on<F>(&mut self, task: F, handle: &TaskNodeHandle) -> AbortHandle
+    where
+        F: Future<Output = T> + Send + 'static,
+    {
+        self.insert(handle.spawn(task))
+    }
+
+    /// Spawn the provided `!Send` task on the `JoinSet`, returning an [`AbortHandle`]
+    /// that can be used to remotely cancel the task.
+    ///
+    /// The output must still be `Send`, since the `AbortHandle` may drop it on another thread.
+    pub fn spawn_local<F>(&mut self, task: F) -> AbortHandle
+    where
+        F: Future<Output = T> + 'static,
+    {
+        self.insert(super::spawn_local(task))
+    }
+
+    fn insert(&mut self, handle: JoinHandle<T>) -> AbortHandle {
+        let abort = handle.abort_handle();
+        self.tasks.push(handle);
+        abort
+    }
+}
+
+impl<T> JoinSet<T> {
+    /// Waits until one of the tasks in the set completes and returns its output.
+    ///
+    /// Returns `None` if the set is empty.
+    pub async fn join_next(&mut self) -> Option<Result<T, JoinError>> {
+        poll_fn(|cx| self.poll_join_next(cx)).await
+    }
+
+    /// Polls for one of the tasks in the set to complete.
+    ///
+    /// If this returns `Poll::Ready(Some(_))`, then the task that completed is removed from the set.
+    pub fn poll_join_next(&mut self, cx: &mut Context<'_>) -> Poll<Option<Result<T, JoinError>>> {
+        if self.tasks.is_empty() {
+            return Poll::Ready(None);
+        }
+        for i in 0..self.tasks.len() {
+            if let Poll::Ready(res) = Pin::new(&mut self.tasks[i]).poll(cx) {
+                self.tasks.remove(i);
+                return Poll::Ready(Some(res));
+            }
+        }
+        Poll::Pending
+    }
+
+    /// Aborts all tasks and waits for them to finish shutting down.
+    pub async fn shutdown(&mut self) {
+        self.abort_all();
+        while self.join_next().await.is_some() {}
+    }
+
+    /// Aborts all tasks on this `JoinSet`.
+    ///
+    /// This does not remove the tasks from the `JoinSet`.
+    pub fn abort_all(&mut self) {
+        for task in &self.tasks {
+            task.abort();
+        }
+    }
+
+    /// Removes all tasks from this `JoinSet` without aborting them.
+    pub fn detach_all(&mut self) {
+        for task in self.tasks.drain(..) {
+            task.detach();
+        }
+    }
+}
+
+impl<T> Default for JoinSet<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Drop for JoinSet<T> {
+    fn drop(&mut self) {
+        self.abort_all();
+    }
+}
+
+impl<T> fmt::Debug for JoinSet<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("JoinSet").field("len", &self.len()).finish()
+    }
+}
@@ -0,0 +1,243 @@
+use super::TaskInfo;
+use spin::Mutex;
+use std::{
+    any::Any,
+    collections::HashMap,
+    fmt,
+    future::Future,
+    marker::PhantomData,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+/// Declares a new task-local key of type [`LocalKey`].
+///
+/// Unlike tokio, the values of task-local keys are inherited by the tasks
+/// spawned on the same node. Tasks on other nodes do not see them.
+/// An inherited value is shared rather than copied, so the parent and its children
+/// observe the changes made through interior mutability.
+///
+/// # Example
+///
+/// ```
+/// use madsim::{runtime::Runtime, task};
+///
+/// madsim::task_local! {
+///     static REQUEST_ID: u32;
+/// }
+///
+/// Runtime::new().block_on(REQUEST_ID.scope(1, async {
+///     assert_eq!(REQUEST_ID.get(), 1);
+///     let id = task::spawn(async { REQUEST_ID.get() }).await.unwrap();
+///     assert_eq!(id, 1);
+/// }));
+/// ```
+#[macro_export]
+#[cfg_attr(docsrs, doc(cfg(madsim)))]
+macro_rules! task_local {
+    // empty (base case for the recursion)
+    () => {};
+
+    ($(#[$attr:meta])* $vis:vis static $name:ident: $t:ty; $($rest:tt)*) => {
+        $(#[$attr])*
+        $vis static $name: $crate::task::LocalKey<$t> = $crate::task::LocalKey::new();
+        $crate::task_local!($($rest)*);
+    };
+
+    ($(#[$attr:meta])* $vis:vis static $name:ident: $t:ty) => {
+        $(#[$attr])*
+        $vis static $name: $crate::task::LocalKey<$t> = $crate::task::LocalKey::new();
+    };
+}
+
+/// A key for task-local data.
+///
+/// This type is generated by the [`task_local!`](crate::task_local) macro.
+pub struct LocalKey<T: 'static> {
+    // make the key non-zero-sized so that each static has a unique address
+    _unique: u8,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T: 'static> LocalKey<T> {
+    #[doc(hidden)]
+    pub const fn new() -> Self {
+        LocalKey {
+            _unique: 0,
+            _marker: PhantomData,
+        }
+    }
+
+    fn id(&'static self) -> usize {
+        self as *const Self as usize
+    }
+}
+
+impl<T: Send + 'static> LocalKey<T> {
+    /// Sets a value `T` as the task-local value for the future `F`.
+    ///
+    /// On completion of `scope`, the task-local will be dropped, unless it is still
+    /// shared with child tasks.
+    pub fn scope<F: Future>(&'static self, value: T, future: F) -> TaskLocalFuture<T, F> {
+        TaskLocalFuture {
+            key: self,
+            slot: Some(Arc::new(Mutex::new(Box::new(value)))),
+            future,
+        }
+    }
+
+    /// Sets a value `T` as the task-local value for the closure `F`.
+    ///
+    /// On completion of `sync_scope`, the task-local will be dropped, unless it is still
+    /// shared with child tasks.
+    pub fn sync_scope<F, R>(&'static self, value: T, f: F) -> R
+    where
+        F: FnOnce() -> R,
+    {
+        self.enter(&Arc::new(Mutex::new(Box::new(value))), f)
+    }
+
+    /// Accesses the current task-local and runs the provided closure.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if not called within the context
+    /// of a future containing a task-local with the corresponding key.
+    pub fn with<F, R>(&'static self, f: F) -> R
+    where
+        F: FnOnce(&T) -> R,
+    {
+        self.try_with(f)
+            .expect("cannot access a task-local storage value without setting it first")
+    }
+
+    /// Accesses the current task-local and runs the provided closure.
+    ///
+    /// If the task-local with the associated key is not present, this
+    /// method will return an `AccessError`.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if the same key is accessed again within the closure.
+    pub fn try_with<F, R>(&'static self, f: F) -> Result<R, AccessError>
+    where
+        F: FnOnce(&T) -> R,
+    {
+        let info = crate::context::try_current_task().ok_or(AccessError)?;
+        // release the map to allow accessing other task-locals in the closure
+        let value = info.locals.0.lock().get(&self.id()).cloned();
+        let value = value.ok_or(AccessError)?;
+        let value = value
+            .try_lock()
+            .expect("task-local value is already being accessed");
+        Ok(f(value.downcast_ref::<T>().unwrap()))
+    }
+
+    /// Makes `value` the task-local value of the current task during the call of `f`.
+    fn enter<F, R>(&'static self, value: &Value, f: F) -> R
+    where
+        F: FnOnce() -> R,
+    {
+        struct Guard<'a> {
+            id: usize,
+            prev: Option<Value>,
+            info: &'a TaskInfo,
+        }
+
+        impl Drop for Guard<'_> {
+            fn drop(&mut self) {
+                let mut locals = self.info.locals.0.lock();
+                match self.prev.take() {
+                    Some(prev) => locals.insert(self.id, prev),
+                    None => locals.remove(&self.id),
+                };
+            }
+        }
+
+        let info = crate::context::current_task();
+        let prev = info.locals.0.lock().insert(self.id(), value.clone());
+        let _guard = Guard {
+            id: self.id(),
+            prev,
+            info: &info,
+        };
+        f()
+    }
+}
+
+impl<T: Clone + Send + 'static> LocalKey<T> {
+    /// Returns a copy of the task-local value.
+    pub fn get(&'static self) -> T {
+        self.with(|v| v.clone())
+    }
+}
+
+impl<T: 'static> fmt::Debug for LocalKey<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad("LocalKey { .. }")
+    }
+}
+
+/// A future that sets a value `T` of a task local for the future `F` during its execution.
+///
+/// This future is returned by [`LocalKey::scope`].
+pub struct TaskLocalFuture<T: 'static, F> {
+    key: &'static LocalKey<T>,
+    slot: Option<Value>,
+    future: F,
+}
+
+impl<T: Send + 'static, F: Future> Future for TaskLocalFuture<T, F> {
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // Safety: `future` is never moved, and `slot` is not structurally pinned.
+        let this = unsafe { self.get_unchecked_mut() };
+        let future = unsafe { Pin::new_unchecked(&mut this.future) };
+        let value = (this.slot.as_ref()).expect("`TaskLocalFuture` polled after completion");
+        let output = this.key.enter(value, || future.poll(cx));
+        if output.is_ready() {
+            this.slot = None;
+        }
+        output
+    }
+}
+
+impl<T: 'static, F> fmt::Debug for TaskLocalFuture<T, F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TaskLocalFuture").finish_non_exhaustive()
+    }
+}
+
+/// An error returned by [`LocalKey::try_with`].
+#[derive(Clone, Copy, Eq, PartialEq)]
+pub struct AccessError;
+
+impl fmt::Debug for AccessError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AccessError").finish()
+    }
+}
+
+impl fmt::Display for AccessError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt("task-local value not set", f)
+    }
+}
+
+impl std::error::Error for AccessError {}
+
+/// A task-local value, shared by the tasks that inherited it.
+type Value = Arc<Mutex<Box<dyn Any + Send>>>;
+
+/// Task-local values of a task.
+#[derive(Default)]
+pub(crate) struct Locals(Mutex<HashMap<usize, Value>>);
+
+impl Locals {
+    /// Share the values with a child task.
+    pub fn fork(&self) -> Self {
+        Locals(Mutex::new(self.0.lock().clone()))
+    }
+}
@@ -0,0 +1,145 @@
+use super::*;
+
+/// A policy of exponential backoff used by [`retry_with_backoff`].
+///
+/// The delay before the `n`-th retry is `min(base * 2^n, max)`, reduced by a random
+/// fraction of up to `jitter` drawn from the global random number generator.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Backoff {
+    /// The delay before the first retry.
+    pub base: Duration,
+    /// The maximum delay between retries.
+    pub max: Duration,
+    /// The maximum fraction of the delay to be randomly subtracted, in `[0, 1]`.
+    pub jitter: f64,
+    /// The maximum number of retries.
+    pub max_retries: usize,
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Backoff {
+            base: Duration::from_millis(10),
+            max: Duration::from_secs(10),
+            jitter: 0.5,
+            max_retries: 10,
+        }
+    }
+}
+
+impl Backoff {
+    /// Returns the delay before the `n`-th retry, starting from 0.
+    fn delay(&self, n: usize) -> Duration {
+        let exp = 2u32.checked_pow(n as u32).unwrap_or(u32::MAX);
+        let delay = self.base.saturating_mul(exp).min(self.max);
+        if self.jitter == 0.0 {
+            return delay;
+        }
+        let fraction = crate::rand::thread_rng().gen_range(0.0..self.jitter);
+        delay.mul_f64(1.0 - fraction)
+    }
+}
+
+/// Calls `op` until it succeeds, sleeping between attempts according to the `policy`.
+///
+/// Returns the first success, or the last error if all retries fail.
+/// The delays are scheduled on the simulated clock and the jitter is drawn from the
+/// global random number generator, so the whole retry sequence is reproducible.
+///
+/// # Panics
+///
+/// This function panics if `policy.jitter` is not in `[0, 1]`.
+///
+/// # Example
+///
+/// ```
+/// use madsim::{runtime::Runtime, time::{retry_with_backoff, Backoff}};
+///
+/// Runtime::new().block_on(async {
+///     let mut attempts = 0;
+///     let result = retry_with_backoff(Backoff::default(), || {
+///         attempts += 1;
+///         let ok = attempts == 3;
+///         async move { if ok { Ok(()) } else { Err("failed") } }
+///     })
+///     .await;
+///     assert_eq!(result, Ok(()));
+/// });
+/// ```
+pub async fn retry_with_backoff<T, E, F, Fut>(policy: Backoff, mut op: F) -> Result<T, E>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+{
+    assert!(
+        (0.0..=1.0).contains(&policy.jitter),
+        "jitter must be in [0, 1]"
+    );
+    let mut retries = 0;
+    loop {
+        match op().await {
+            Ok(value) => return Ok(value),
+            Err(e) if retries >= policy.max_retries => return Err(e),
+            Err(_) => {}
+        }
+        sleep(policy.delay(retries)).await;
+        retries += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runtime::Runtime;
+
+    /// Returns the elapsed time of each attempt.
+    fn attempts(seed: u64, policy: Backoff) -> Vec<Duration> {
+        let runtime = Runtime::with_seed_and_config(seed, crate::Config::default());
+        runtime.block_on(async move {
+            let t0 = Instant::now();
+            let mut attempts = vec![];
+            let result: Result<(), ()> = retry_with_backoff(policy, || {
+                attempts.push(t0.elapsed());
+                async { Err(()) }
+            })
+            .await;
+            assert!(result.is_err());
+            attempts
+        })
+    }
+
+    #[test]
+    fn exponential() {
+        let policy = Backoff {
+            base: Duration::from_secs(1),
+            max: Duration::from_secs(5),
+            jitter: 0.0,
+            max_retries: 5,
+        };
+        let attempts = attempts(1, policy)
+            .into_iter()
+            .map(|t| t.as_secs())
+            .collect::<Vec<_>>();
+        assert_eq!(attempts, [0, 1, 3, 7, 12, 17]);
+    }
+
+    #[test]
+    fn jitter() {
+        let policy = Backoff {
+            base: Duration::from_secs(1),
+            max: Duration::from_secs(60),
+            jitter: 0.5,
+            max_retries: 5,
+        };
+        let attempts1 = attempts(1, policy.clone());
+        assert_eq!(attempts1, attempts(1, policy.clone()));
+        assert_ne!(attempts1, attempts(2, policy));
+        let millis = attempts1.iter().map(|t| t.as_millis()).collect::<Vec<_>>();
+        assert_eq!(millis, [0, 828, 2417, 5245, 10120, 24269]);
+        for (i, w) in attempts1.windows(2).enumerate() {
+            let delay = w[1] - w[0];
+            let max = Duration::from_secs(1 << i);
+            assert!(delay > max / 2 && delay < max + Duration::from_millis(1));
+        }
+    }
+}
@@ -0,0 +1,334 @@
+//! A queue of delayed elements.
+//!
+//! See [`DelayQueue`] for more details.
+
+use super::*;
+use crate::rand::Rng;
+use futures_util::{ready, Stream};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+    pin::Pin,
+    task::{Context, Poll, Waker},
+};
+
+/// A queue of delayed elements.
+///
+/// Once an element is inserted into the `DelayQueue`, it is yielded once the
+/// specified deadline has been reached. Elements expiring at the same instant are
+/// yielded in a random order determined by the global random number generator.
+///
+/// This is a deterministic version of `tokio_util::time::DelayQueue`.
+///
+/// # Example
+///
+/// ```
+/// use futures_util::StreamExt;
+/// use madsim::{runtime::Runtime, time::{DelayQueue, Duration}};
+///
+/// Runtime::new().block_on(async {
+///     let mut queue = DelayQueue::new();
+///     queue.insert("b", Duration::from_secs(2));
+///     queue.insert("a", Duration::from_secs(1));
+///     assert_eq!(queue.next().await.unwrap().into_inner(), "a");
+///     assert_eq!(queue.next().await.unwrap().into_inner(), "b");
+///     assert!(queue.next().await.is_none());
+/// });
+/// ```
+pub struct DelayQueue<T> {
+    /// The elements and their deadlines.
+    entries: HashMap<Key, (T, Instant)>,
+    /// The keys of elements ordered by deadline.
+    wheel: BTreeMap<Instant, Vec<Key>>,
+    /// The next key to allocate.
+    next_key: u64,
+    /// Future that completes when the earliest element expires.
+    delay: Option<Pin<Box<Sleep>>>,
+    /// The waker of the task polling the queue.
+    waker: Option<Waker>,
+}
+
+/// Token to a value stored in a [`DelayQueue`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Key(u64);
+
+/// An entry yielded from a [`DelayQueue`] once its deadline is reached.
+#[derive(Debug)]
+pub struct Expired<T> {
+    data: T,
+    deadline: Instant,
+    key: Key,
+}
+
+impl<T> Expired<T> {
+    /// Returns a reference to the inner value.
+    pub fn get_ref(&self) -> &T {
+        &self.data
+    }
+
+    /// Returns a mutable reference to the inner value.
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.data
+    }
+
+    /// Consumes `self` and returns the inner value.
+    pub fn into_inner(self) -> T {
+        self.data
+    }
+
+    /// Returns the deadline that the expiration was set to.
+    pub fn deadline(&self) -> Instant {
+        self.deadline
+    }
+
+    /// Returns the key that the expiration is indexed by.
+    pub fn key(&self) -> Key {
+        self.key
+    }
+}
+
+impl<T> Default for DelayQueue<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> DelayQueue<T> {
+    /// Creates a new, empty, `DelayQueue`.
+    pub fn new() -> Self {
+        DelayQueue {
+            entries: HashMap::new(),
+            wheel: BTreeMap::new(),
+            next_key: 0,
+            delay: None,
+            waker: None,
+        }
+    }
+
+    /// Inserts `value` into the queue set to expire after the requested duration elapses.
+    pub fn insert(&mut self, value: T, timeout: Duration) -> Key {
+        self.insert_at(value, Instant::now() + timeout)
+    }
+
+    /// Inserts `value` into the queue set to expire at a specific instant.
+    pub fn insert_at(&mut self, value: T, when: Instant) -> Key {
+        let key = Key(self.next_key);
+        self.next_key += 1;
+        self.entries.insert(key, (value, when));
+        self.wheel.entry(when).or_default().push(key);
+        self.wake();
+        key
+    }
+
+    /// Removes the item associated with `key` from the queue.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if `key` is not contained by the queue.
+    #[track_caller]
+    pub fn remove(&mut self, key: &Key) -> Expired<T> {
+        self.try_remove(key).expect("invalid key")
+    }
+
+    /// Attempts to remove the item associated with `key` from the queue.
+    ///
+    /// Returns `None` if `key` is not contained by the queue.
+    pub fn try_remove(&mut self, key: &Key) -> Option<Expired<T>> {
+        let (data, deadline) = self.entries.remove(key)?;
+        self.remove_from_wheel(*key, deadline);
+        Some(Expired {
+            data,
+            deadline,
+            key: *key,
+        })
+    }
+
+    /// Sets the delay of the item associated with `key` to expire after `timeout`.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if `key` is not contained by the queue.
+    #[track_caller]
+    pub fn reset(&mut self, key: &Key, timeout: Duration) {
+        self.reset_at(key, Instant::now() + timeout);
+    }
+
+    /// Sets the delay of the item associated with `key` to expire at `when`.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if `key` is not contained by the queue.
+    #[track_caller]
+    pub fn reset_at(&mut self, key: &Key, when: Instant) {
+        let (_, deadline) = self.entries.get_mut(key).expect("invalid key");
+        let old = std::mem::replace(deadline, when);
+        self.remove_from_wheel(*key, old);
+        self.wheel.entry(when).or_default().push(*key);
+        self.wake();
+    }
+
+    /// Returns the deadline of the item associated with `key`.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if `key` is not contained by the queue.
+    #[track_caller]
+    pub fn deadline(&self, key: &Key) -> Instant {
+        self.entries.get(key).expect("invalid key").1
+    }
+
+    /// Clears the queue, removing all items.
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.wheel.clear();
+        self.delay = None;
+    }
+
+    /// Returns the number of elements in the queue.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns `true` if there are no items in the queue.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Attempts to pull out the next value of the delay queue, registering the current task
+    /// for wakeup if the value is not yet available, and returning `None` if the queue is
+    /// exhausted.
+    pub fn poll_expired(&mut self, cx: &mut Context<'_>) -> Poll<Option<Expired<T>>> {
+        self.waker = Some(cx.waker().clone());
+        loop {
+            let Some((&when, keys)) = self.wheel.first_key_value() else {
+                return Poll::Ready(None);
+            };
+            if when <= Instant::now() {
+                // break ties between elements expiring at the same instant
+                let i = crate::rand::thread_rng().gen_range(0..keys.len());
+                let key = keys[i];
+                return Poll::Ready(self.try_remove(&key));
+            }
+            match &mut self.delay {
+                Some(delay) => delay.as_mut().reset(when),
+                None => self.delay = Some(Box::pin(sleep_until(when))),
+            }
+            ready!(self.delay.as_mut().unwrap().as_mut().poll(cx));
+        }
+    }
+
+    fn remove_from_wheel(&mut self, key: Key, deadline: Instant) {
+        let keys = self.wheel.get_mut(&deadline).expect("entry not in wheel");
+        keys.retain(|k| *k != key);
+        if keys.is_empty() {
+            self.wheel.remove(&deadline);
+        }
+    }
+
+    /// Wakes the task polling the queue, since the earliest deadline may have changed.
+    fn wake(&mut self) {
+        if let Some(waker) = self.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+impl<T> Unpin for DelayQueue<T> {}
+
+impl<T> Stream for DelayQueue<T> {
+    type Item = Expired<T>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.get_mut().poll_expired(cx)
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for DelayQueue<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DelayQueue")
+            .field("entries", &self.entries)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runtime::Runtime;
+    use futures_util::StreamExt;
+    use std::collections::HashSet;
+
+    #[test]
+    fn expire_in_order() {
+        let runtime = Runtime::new();
+        runtime.block_on(async {
+            let t0 = Instant::now();
+            let mut queue = DelayQueue::new();
+            queue.insert(3, Duration::from_secs(3));
+            queue.insert(1, Duration::from_secs(1));
+            let key = queue.insert(0, Duration::from_secs(2));
+            queue.insert(2, Duration::from_secs(2));
+            assert_eq!(queue.len(), 4);
+            assert_eq!(queue.remove(&key).into_inner(), 0);
+
+            for i in 1..=3 {
+                let expired = queue.next().await.unwrap();
+                assert_eq!(expired.into_inner(), i);
+                assert!(t0.elapsed() >= Duration::from_secs(i));
+                assert!(t0.elapsed() < Duration::from_secs(i) + Duration::from_millis(1));
+            }
+            assert!(queue.next().await.is_none());
+            assert!(queue.is_empty());
+        });
+    }
+
+    #[test]
+    fn insert_while_waiting() {
+        let runtime = Runtime::new();
+        runtime.block_on(async {
+            let queue = Arc::new(Mutex::new(DelayQueue::new()));
+            queue.lock().insert("late", Duration::from_secs(10));
+            let queue0 = queue.clone();
+            crate::task::spawn(async move {
+                sleep(Duration::from_secs(1)).await;
+                queue0.lock().insert("early", Duration::from_secs(1));
+            });
+            let next = futures_util::future::poll_fn(|cx| queue.lock().poll_expired(cx));
+            assert_eq!(next.await.unwrap().into_inner(), "early");
+        });
+    }
+
+    #[test]
+    fn tie_break() {
+        let run = |seed| {
+            let runtime = Runtime::with_seed_and_config(seed, crate::Config::default());
+            runtime.block_on(async {
+                let mut queue = DelayQueue::new();
+                let t0 = Instant::now();
+                for i in 0..10 {
+                    queue.insert_at(i, t0 + Duration::from_secs(1 + i / 5));
+                }
+                let mut order = vec![];
+                while let Some(expired) = queue.next().await {
+                    // each element is yielded right at its deadline
+                    assert_eq!(t0.elapsed().as_secs(), 1 + expired.get_ref() / 5);
+                    order.push(expired.into_inner());
+                }
+                order
+            })
+        };
+        let mut orders = HashSet::new();
+        for seed in 0..10 {
+            let order = run(seed);
+            assert_eq!(order, run(seed));
+            // ties are broken within the same deadline only
+            let (mut early, mut late) = (order[..5].to_vec(), order[5..].to_vec());
+            early.sort();
+            late.sort();
+            assert_eq!(early, [0, 1, 2, 3, 4]);
+            assert_eq!(late, [5, 6, 7, 8, 9]);
+            orders.insert(order);
+        }
+        assert_eq!(orders.len(), 10);
+    }
+}
@@ -186,3 +186,52 @@ impl Interval {
         self.period
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{runtime::Runtime, time::timeout};
+    use std::{cell::Cell, rc::Rc};
+
+    #[test]
+    fn tick_count() {
+        let runtime = Runtime::new();
+        runtime.block_on(async {
+            let count = Rc::new(Cell::new(0));
+            let count0 = count.clone();
+            let mut interval = interval(Duration::from_secs(1));
+            let ticks = async move {
+                loop {
+                    interval.tick().await;
+                    count0.set(count0.get() + 1);
+                }
+            };
+            timeout(Duration::from_millis(10_500), ticks)
+                .await
+                .unwrap_err();
+            // the first tick completes immediately
+            assert_eq!(count.get(), 11);
+        });
+    }
+
+    #[test]
+    fn missed_tick_behavior() {
+        let next_tick = |behavior| {
+            let runtime = Runtime::new();
+            runtime.block_on(async move {
+                let t0 = Instant::now();
+                let mut interval = interval(Duration::from_secs(1));
+                interval.set_missed_tick_behavior(behavior);
+                interval.tick().await;
+                crate::time::sleep(Duration::from_millis(3_500)).await;
+                // the missed tick completes immediately
+                assert_eq!(interval.tick().await, t0 + Duration::from_secs(1));
+                interval.tick().await - t0
+            })
+        };
+        assert_eq!(next_tick(MissedTickBehavior::Burst), Duration::from_secs(2));
+        assert_eq!(next_tick(MissedTickBehavior::Skip), Duration::from_secs(4));
+        let delay = next_tick(MissedTickBehavior::Delay);
+        assert!(delay > Duration::from_millis(4_500) && delay < Duration::from_millis(4_600));
+    }
+}
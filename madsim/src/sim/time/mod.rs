@@ -4,19 +4,31 @@
 
 use crate::rand::{GlobalRng, Rng};
 use futures_util::{select_biased, FutureExt};
-use naive_timer::Timer;
 use spin::Mutex;
 #[doc(no_inline)]
 pub use std::time::{Duration, Instant};
-use std::{future::Future, sync::Arc, time::SystemTime};
-
+use std::{
+    future::Future,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc,
+    },
+    time::SystemTime,
+};
+
+mod backoff;
+pub mod delay_queue;
 pub mod error;
 mod interval;
 mod sleep;
 mod system_time;
+mod timer;
 
+pub use self::backoff::{retry_with_backoff, Backoff};
+pub use self::delay_queue::DelayQueue;
 pub use self::interval::{interval, interval_at, Interval, MissedTickBehavior};
 pub use self::sleep::{sleep, sleep_until, Sleep};
+use self::timer::Timer;
 
 pub(crate) struct TimeRuntime {
     handle: TimeHandle,
@@ -33,6 +45,10 @@ impl TimeRuntime {
         let handle = TimeHandle {
             timer: Arc::new(Mutex::new(Timer::default())),
             clock: ClockHandle::new(base_time),
+            frozen: Arc::new(AtomicBool::new(false)),
+            auto_advance: Arc::new(AtomicBool::new(true)),
+            paused: Arc::new(AtomicBool::new(false)),
+            advance_count: Arc::new(AtomicU64::new(0)),
         };
         TimeRuntime { handle }
     }
@@ -41,41 +57,105 @@ impl TimeRuntime {
         &self.handle
     }
 
-    /// Advances time to the closest timer event. Returns true if succeed.
-    pub fn advance_to_next_event(&self) -> bool {
-        let mut timer = self.handle.timer.lock();
-        if let Some(mut time) = timer.next() {
-            // WARN: in some platform such as M1 macOS,
-            //       let t0: Instant;
-            //       let t1: Instant;
-            //       t0 + (t1 - t0) < t1 !!
-            // we should add eps to make sure 'now >= deadline' and avoid deadlock
-            time += Duration::from_nanos(50);
-            timer.expire(time);
-            self.handle.clock.set_elapsed(time);
-            true
-        } else {
-            false
+    /// Advances time to the closest timer event, but no further than `deadline`.
+    /// Returns true if succeed.
+    ///
+    /// If the closest event is after `deadline`, or there is no event at all,
+    /// the clock stops at `deadline` without firing any timer.
+    /// Always returns false unless the clock [jumps when idle](ClockMode::jumps_when_idle).
+    pub fn advance_to_next_event_until(&self, deadline: Option<Duration>) -> bool {
+        if !self.handle.mode().jumps_when_idle() {
+            return false;
         }
+        self.handle.advance_count.fetch_add(1, Ordering::Relaxed);
+        let mut timer = self.handle.timer.lock();
+        // WARN: in some platform such as M1 macOS,
+        //       let t0: Instant;
+        //       let t1: Instant;
+        //       t0 + (t1 - t0) < t1 !!
+        // we should add eps to make sure 'now >= deadline' and avoid deadlock
+        let next = timer.next().map(|time| time + Duration::from_nanos(50));
+        let time = match (next, deadline) {
+            (Some(time), Some(deadline)) => time.min(deadline),
+            (Some(time), None) => time,
+            (None, Some(deadline)) => deadline,
+            (None, None) => return false,
+        };
+        // the clock may have advanced beyond the timer, never go back
+        let time = time.max(self.handle.clock.elapsed());
+        let callbacks = timer.expire(time);
+        self.handle.clock.set_elapsed(time);
+        drop(timer);
+        self.handle.call_callbacks(callbacks);
+        true
     }
 
-    /// Advances time.
+    /// Advances time without firing timers.
+    /// Does nothing unless the clock [advances on polls](ClockMode::advances_on_poll).
     pub fn advance(&self, duration: Duration) {
-        self.handle.clock.advance(duration);
+        self.handle.advance_clock(duration);
     }
 
-    #[allow(dead_code)]
     /// Get the current time.
     pub fn now_instant(&self) -> Instant {
         self.handle.now_instant()
     }
 }
 
+/// The effective mode of the clock.
+///
+/// It is derived from three independent flags: [frozen](TimeHandle::freeze),
+/// [auto-advance](crate::runtime::Runtime::set_auto_advance) and [paused](pause).
+/// The most restrictive flag that is set wins, in the order of the variants below.
+/// Since the flags are kept separately, clearing one of them falls back to the mode given
+/// by the others. For example, unfreezing a clock that is also paused leaves it paused.
+///
+/// | mode      | advances on polls | jumps to the next timer when idle | [`advance`] |
+/// |-----------|-------------------|-----------------------------------|-------------|
+/// | `Frozen`  | no                | no, the runtime panics            | no          |
+/// | `Manual`  | no                | no, the runtime panics            | yes         |
+/// | `Paused`  | no                | yes                               | yes         |
+/// | `Running` | yes               | yes                               | yes         |
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ClockMode {
+    /// The clock is frozen, whatever the other flags are.
+    Frozen,
+    /// Auto-advance is disabled, whether or not time is paused.
+    Manual,
+    /// Time is paused.
+    Paused,
+    /// No flag is set. This is the default.
+    Running,
+}
+
+impl ClockMode {
+    /// Returns `true` if the clock advances while tasks are being polled.
+    pub fn advances_on_poll(self) -> bool {
+        self == ClockMode::Running
+    }
+
+    /// Returns `true` if the clock jumps to the next timer when all tasks are blocked.
+    pub fn jumps_when_idle(self) -> bool {
+        matches!(self, ClockMode::Paused | ClockMode::Running)
+    }
+
+    /// Returns `true` if the clock can be advanced manually.
+    pub fn advances_manually(self) -> bool {
+        self != ClockMode::Frozen
+    }
+}
+
 /// Handle to a shared time source.
 #[derive(Clone)]
 pub struct TimeHandle {
     timer: Arc<Mutex<Timer>>,
     clock: ClockHandle,
+    frozen: Arc<AtomicBool>,
+    auto_advance: Arc<AtomicBool>,
+    /// Whether time is [paused](pause), so that it only jumps to the next timer when idle.
+    paused: Arc<AtomicBool>,
+    /// The number of times the clock advanced to the next event.
+    advance_count: Arc<AtomicU64>,
 }
 
 impl TimeHandle {
@@ -90,13 +170,52 @@ impl TimeHandle {
     }
 
     /// Return the current time.
+    ///
+    /// The time is observed from the node of the current task.
+    /// It never goes backwards on the same node. See [`now_monotonic`](Self::now_monotonic).
     pub fn now_instant(&self) -> Instant {
-        self.clock.now_instant()
+        self.now_monotonic()
+    }
+
+    /// Return the current time observed from the node of the current task,
+    /// which is guaranteed to be no earlier than any time previously observed on the node.
+    ///
+    /// This is the maximum of the skewed local time and the last observed value,
+    /// so `later.duration_since(earlier)` never panics within a node.
+    pub fn now_monotonic(&self) -> Instant {
+        let Some(task) = crate::context::try_current_task() else {
+            return self.clock.now_instant();
+        };
+        let base = self.clock.base_instant();
+        let now = self.clock.now_instant() + task.node.clock_skew;
+        let nanos = (now - base).as_nanos() as u64;
+        let last = task.node.last_now.fetch_max(nanos, Ordering::SeqCst);
+        base + Duration::from_nanos(last.max(nanos))
     }
 
     /// Return the current time.
+    ///
+    /// The time is observed from the node of the current task.
     pub fn now_time(&self) -> SystemTime {
-        self.clock.now_time()
+        self.clock.now_time() + clock_skew()
+    }
+
+    /// Set the system time at the start of the simulation.
+    ///
+    /// The current system time becomes `epoch` plus the elapsed time.
+    /// By default, the epoch is a random time in 2022 determined by the seed.
+    pub fn set_system_epoch(&self, epoch: SystemTime) {
+        self.clock.set_base_time(epoch);
+    }
+
+    /// Returns the system time at the start of the simulation.
+    pub fn system_epoch(&self) -> SystemTime {
+        self.clock.base_time()
+    }
+
+    /// Return the current time of the global clock, regardless of the current node.
+    pub(crate) fn global_now_instant(&self) -> Instant {
+        self.clock.now_instant()
     }
 
     /// Returns the amount of time elapsed since this handle was created.
@@ -104,9 +223,55 @@ impl TimeHandle {
         self.clock.elapsed()
     }
 
+    /// Converts an `Instant` to the number of nanoseconds since the start of the simulation.
+    ///
+    /// The result is deterministic across runs with the same seed, so it can be used to
+    /// serialize instants. Instants earlier than the start of the simulation map to 0.
+    /// Use [`nanos_to_instant`](Self::nanos_to_instant) to convert it back.
+    pub fn instant_to_nanos(&self, instant: Instant) -> u64 {
+        let nanos = instant
+            .saturating_duration_since(self.clock.base_instant())
+            .as_nanos();
+        nanos.try_into().unwrap_or(u64::MAX)
+    }
+
+    /// Converts the number of nanoseconds since the start of the simulation to an `Instant`.
+    ///
+    /// This is the inverse of [`instant_to_nanos`](Self::instant_to_nanos).
+    pub fn nanos_to_instant(&self, nanos: u64) -> Instant {
+        self.clock.base_instant() + Duration::from_nanos(nanos)
+    }
+
+    /// Freezes the simulation clock.
+    ///
+    /// While frozen, time does not advance and pending timers do not fire,
+    /// but ready tasks on all nodes keep running.
+    /// This models a stop-the-world pause such as a debugger breakpoint.
+    /// Freezing takes precedence over [`pause`] and disabled auto-advance,
+    /// which apply again once the clock is unfrozen.
+    ///
+    /// # Deadlock
+    ///
+    /// If every task is blocked on a timer while the clock is frozen,
+    /// no one is left to call [`unfreeze`](Self::unfreeze) and the runtime panics
+    /// because all tasks will block forever.
+    pub fn freeze(&self) {
+        self.frozen.store(true, Ordering::SeqCst);
+    }
+
+    /// Unfreezes the simulation clock.
+    pub fn unfreeze(&self) {
+        self.frozen.store(false, Ordering::SeqCst);
+    }
+
+    /// Returns `true` if the simulation clock is frozen.
+    pub fn is_frozen(&self) -> bool {
+        self.frozen.load(Ordering::SeqCst)
+    }
+
     /// Waits until `duration` has elapsed.
     pub fn sleep(&self, duration: Duration) -> Sleep {
-        self.sleep_until(self.clock.now_instant() + duration)
+        self.sleep_until(self.now_instant() + duration)
     }
 
     /// Waits until `deadline` is reached.
@@ -133,21 +298,165 @@ impl TimeHandle {
         }
     }
 
+    /// Advances time by `duration` and fires all timers that are due.
+    ///
+    /// The tasks woken by the timers run when the current task yields.
+    /// This works even if auto-advance is disabled by
+    /// [`Runtime::set_auto_advance`](crate::runtime::Runtime::set_auto_advance),
+    /// but does nothing if the clock is [frozen](Self::freeze).
+    pub fn advance(&self, duration: Duration) {
+        if !self.mode().advances_manually() {
+            return;
+        }
+        let mut timer = self.timer.lock();
+        self.clock.advance(duration);
+        let callbacks = timer.expire(self.clock.elapsed());
+        drop(timer);
+        self.call_callbacks(callbacks);
+    }
+
+    /// Calls `f` at each multiple of `interval` in simulated time since the start of the simulation.
+    ///
+    /// `f` receives the instant of the tick. The ticks are scheduled on the timer, so that
+    /// `f` observes the state of the simulation at those instants. This is useful to
+    /// sample metrics, such as queue depths, over simulated time.
+    ///
+    /// Watchers run forever but do not keep the simulation going on their own:
+    /// if no other timer is pending, the runtime still reports that all tasks are blocked.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if `interval` is zero.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use madsim::{runtime::Runtime, time::{sleep, Duration, TimeHandle}};
+    /// use std::sync::{Arc, Mutex};
+    ///
+    /// let rt = Runtime::new();
+    /// let ticks = Arc::new(Mutex::new(vec![]));
+    /// rt.block_on(async {
+    ///     let t0 = madsim::time::Instant::now();
+    ///     let ticks = ticks.clone();
+    ///     TimeHandle::current().add_watcher(Duration::from_secs(1), move |t| {
+    ///         ticks.lock().unwrap().push(t - t0);
+    ///     });
+    ///     sleep(Duration::from_millis(3500)).await;
+    /// });
+    /// assert_eq!(ticks.lock().unwrap().len(), 3);
+    /// ```
+    pub fn add_watcher(&self, interval: Duration, f: impl Fn(Instant) + Send + Sync + 'static) {
+        assert!(!interval.is_zero(), "interval must be non-zero");
+        let base = self.clock.base_instant();
+        let ticks = self.clock.elapsed().as_nanos() / interval.as_nanos() + 1;
+        let first = Duration::from_nanos((interval.as_nanos() * ticks) as u64);
+        let watcher = Arc::new(move |deadline| f(base + deadline));
+        self.timer.lock().add_watcher(first, interval, watcher);
+    }
+
+    fn call_callbacks(&self, callbacks: Vec<timer::Callback>) {
+        for callback in callbacks {
+            callback();
+        }
+    }
+
+    /// Advances time without firing timers.
+    /// Does nothing unless the clock [advances on polls](ClockMode::advances_on_poll).
+    pub(crate) fn advance_clock(&self, duration: Duration) {
+        if !self.mode().advances_on_poll() {
+            return;
+        }
+        self.clock.advance(duration);
+    }
+
+    /// Returns the number of timer events fired so far.
+    pub(crate) fn timer_event_count(&self) -> u64 {
+        self.timer.lock().fired()
+    }
+
+    /// Returns the number of times the runtime tried to advance to the next timer event.
+    pub(crate) fn advance_count(&self) -> u64 {
+        self.advance_count.load(Ordering::Relaxed)
+    }
+
+    /// Enables or disables advancing time automatically.
+    pub(crate) fn set_auto_advance(&self, enabled: bool) {
+        self.auto_advance.store(enabled, Ordering::SeqCst);
+    }
+
+    /// Returns `true` if time advances automatically.
+    pub(crate) fn is_auto_advance(&self) -> bool {
+        self.auto_advance.load(Ordering::SeqCst)
+    }
+
+    /// Returns the effective mode of the clock.
+    pub(crate) fn mode(&self) -> ClockMode {
+        if self.is_frozen() {
+            ClockMode::Frozen
+        } else if !self.is_auto_advance() {
+            ClockMode::Manual
+        } else if self.is_paused() {
+            ClockMode::Paused
+        } else {
+            ClockMode::Running
+        }
+    }
+
+    /// Pauses or resumes time.
+    fn set_paused(&self, paused: bool) {
+        self.paused.store(paused, Ordering::SeqCst);
+    }
+
+    /// Returns `true` if time is paused.
+    fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::SeqCst)
+    }
+
+    /// Add a timer at the `deadline` observed from the node of the current task.
+    ///
+    /// The timer is owned by the node. Once the node is killed, it fires immediately
+    /// so that the executor can drop the task, and the clock never advances to it.
     pub(crate) fn add_timer_at(
         &self,
         deadline: Instant,
         callback: impl FnOnce() + Send + Sync + 'static,
+    ) {
+        let task = crate::context::try_current_task();
+        let mut timer = self.timer.lock();
+        let deadline = (deadline - self.clock.base_instant()).saturating_sub(clock_skew());
+        timer.add(deadline, task.as_ref().map(|t| &t.node), callback);
+    }
+
+    /// Add a timer at the `deadline` observed from the global clock.
+    ///
+    /// Unlike [`add_timer_at`](Self::add_timer_at), the timer is not owned by any node,
+    /// so it still fires at the deadline if the node of the current task is killed.
+    pub(crate) fn add_global_timer_at(
+        &self,
+        deadline: Instant,
+        callback: impl FnOnce() + Send + Sync + 'static,
     ) {
         let mut timer = self.timer.lock();
-        timer.add(deadline - self.clock.base_instant(), |_| callback());
+        timer.add(deadline - self.clock.base_instant(), None, callback);
     }
 
     pub(crate) fn add_timer(&self, dur: Duration, callback: impl FnOnce() + Send + Sync + 'static) {
-        self.add_timer_at(self.clock.now_instant() + dur, callback);
+        let mut timer = self.timer.lock();
+        timer.add(self.clock.elapsed() + dur, None, callback);
+    }
+
+    /// Fire and remove the timers added by tasks of killed nodes.
+    pub(crate) fn remove_killed_timers(&self) {
+        self.timer.lock().remove_killed();
     }
 }
 
 /// Require a `Future` to complete before the specified duration has elapsed.
+///
+/// The deadline is measured on the simulated clock. The future is always polled before
+/// the deadline is checked, so a zero `duration` polls the future exactly once and times
+/// out if it is still pending. On timeout, the future is dropped.
 pub fn timeout<T: Future>(
     duration: Duration,
     future: T,
@@ -156,6 +465,82 @@ pub fn timeout<T: Future>(
     handle.timeout(duration, future)
 }
 
+/// Pauses time.
+///
+/// The clock no longer advances while tasks are being polled. Use [`advance`] to move the
+/// clock forward. Like the paused clock of tokio, if all tasks are blocked, the clock still
+/// jumps to the next timer, so a task sleeping while time is paused wakes up at its deadline
+/// without the time spent in polls. The runtime panics only if no timer is pending.
+///
+/// Unlike [`TimeHandle::freeze`], timers still fire. A frozen clock or disabled auto-advance
+/// takes precedence over pausing, and time stays paused when they are lifted.
+///
+/// # Panics
+///
+/// This function panics if called outside of a runtime or if time is already paused.
+pub fn pause() {
+    let handle = TimeHandle::current();
+    assert!(!handle.is_paused(), "time is already paused");
+    handle.set_paused(true);
+}
+
+/// Resumes time.
+///
+/// The clock advances automatically again.
+///
+/// # Panics
+///
+/// This function panics if called outside of a runtime or if time is not paused.
+pub fn resume() {
+    let handle = TimeHandle::current();
+    assert!(handle.is_paused(), "time is not paused");
+    handle.set_paused(false);
+}
+
+/// Advances time by `duration`, firing all timers that are due.
+///
+/// This function yields once so that the tasks woken by the timers get a chance to run.
+/// It is usually used together with [`pause`].
+pub async fn advance(duration: Duration) {
+    TimeHandle::current().advance(duration);
+    crate::task::yield_now().await;
+}
+
+/// Sets the system time at the start of the simulation.
+///
+/// Afterwards, [`SystemTime::now`] returns `epoch` plus the simulated time elapsed since
+/// the runtime was created, plus the clock skew of the current node. This makes wall-clock reads, such as
+/// certificate validity checks, reproducible regardless of the seed.
+///
+/// # Panics
+///
+/// This function panics if called outside of a runtime.
+///
+/// # Example
+///
+/// ```
+/// use madsim::{runtime::Runtime, time::{self, Duration}};
+/// use std::time::SystemTime;
+///
+/// Runtime::new().block_on(async {
+///     let epoch = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+///     time::set_system_epoch(epoch);
+///     time::sleep(Duration::from_secs(1)).await;
+///     assert!(SystemTime::now() >= epoch + Duration::from_secs(1));
+///     assert!(SystemTime::now() < epoch + Duration::from_secs(2));
+/// });
+/// ```
+pub fn set_system_epoch(epoch: SystemTime) {
+    TimeHandle::current().set_system_epoch(epoch);
+}
+
+/// Returns the clock skew of the current node.
+fn clock_skew() -> Duration {
+    crate::context::try_current_task()
+        .map(|task| task.node.clock_skew)
+        .unwrap_or_default()
+}
+
 #[derive(Clone)]
 struct ClockHandle {
     inner: Arc<Mutex<Clock>>,
@@ -197,6 +582,16 @@ impl ClockHandle {
         inner.advance += duration;
     }
 
+    fn base_time(&self) -> SystemTime {
+        let inner = self.inner.lock();
+        inner.base_time
+    }
+
+    fn set_base_time(&self, time: SystemTime) {
+        let mut inner = self.inner.lock();
+        inner.base_time = time;
+    }
+
     fn base_instant(&self) -> Instant {
         let inner = self.inner.lock();
         inner.base_instant
@@ -242,4 +637,343 @@ mod tests {
             );
         });
     }
+
+    #[test]
+    fn timeout_zero() {
+        let runtime = Runtime::new();
+        runtime.block_on(async {
+            let t0 = Instant::now();
+            assert_eq!(timeout(Duration::ZERO, async { 1 }).await, Ok(1));
+            assert!(timeout(Duration::ZERO, std::future::pending::<()>())
+                .await
+                .is_err());
+            // a future that becomes ready on the second poll
+            assert!(timeout(Duration::ZERO, crate::task::yield_now())
+                .await
+                .is_err());
+            assert!(t0.elapsed() < Duration::from_millis(1));
+        });
+    }
+
+    #[test]
+    fn timeout_drop() {
+        struct Guard(Arc<AtomicBool>);
+        impl Drop for Guard {
+            fn drop(&mut self) {
+                self.0.store(true, Ordering::SeqCst);
+            }
+        }
+
+        let runtime = Runtime::new();
+        runtime.block_on(async {
+            let dropped = Arc::new(AtomicBool::new(false));
+            let guard = Guard(dropped.clone());
+            let t0 = Instant::now();
+            let future = async move {
+                let _guard = guard;
+                sleep(Duration::from_secs(10)).await;
+            };
+            assert!(timeout(Duration::from_secs(1), future).await.is_err());
+            assert!(dropped.load(Ordering::SeqCst));
+            let elapsed = t0.elapsed();
+            assert!(elapsed >= Duration::from_secs(1));
+            assert!(elapsed < Duration::from_secs(1) + Duration::from_millis(1));
+        });
+    }
+
+    #[test]
+    fn now_monotonic() {
+        let runtime = Runtime::new();
+        let node = runtime
+            .create_node()
+            .clock_skew(Duration::from_secs(5))
+            .build();
+        let f = node.spawn(async {
+            let handle = TimeHandle::current();
+            let mut last = handle.now_monotonic();
+            for i in 0..100 {
+                if i % 10 == 0 {
+                    sleep(Duration::from_millis(i)).await;
+                } else {
+                    crate::task::yield_now().await;
+                }
+                let now = handle.now_monotonic();
+                assert!(now >= last);
+                assert!(Instant::now() >= now);
+                last = now;
+            }
+        });
+        runtime.block_on(f).unwrap();
+    }
+
+    #[test]
+    fn kill_removes_timers() {
+        let runtime = Runtime::new();
+        let node = runtime.create_node().build();
+        runtime.block_on(async move {
+            let t0 = Instant::now();
+            node.spawn(async {
+                sleep(Duration::from_secs(10)).await;
+                unreachable!("killed node should not wake up");
+            });
+            let sleeping = node.spawn(async {
+                sleep(Duration::from_secs(10)).await;
+            });
+            sleep(Duration::from_secs(3)).await;
+            let handle = TimeHandle::current();
+            assert!(handle.timer.lock().next().is_some());
+
+            crate::runtime::Handle::current().kill(node.id());
+            assert_eq!(handle.timer.lock().next(), None);
+            assert!(sleeping.await.unwrap_err().is_cancelled());
+
+            // the clock jumps to the next alive timer
+            sleep(Duration::from_secs(1)).await;
+            let elapsed = t0.elapsed();
+            assert!(elapsed >= Duration::from_secs(4) && elapsed < Duration::from_secs(5));
+        });
+    }
+
+    #[test]
+    fn manual_advance() {
+        let runtime = Runtime::new();
+        runtime.set_auto_advance(false);
+        runtime.block_on(async {
+            let handle = TimeHandle::current();
+            let t0 = Instant::now();
+            let task = crate::task::spawn(sleep(Duration::from_secs(1)));
+            for _ in 0..10 {
+                crate::task::yield_now().await;
+            }
+            assert!(!task.is_finished());
+            assert_eq!(t0.elapsed(), Duration::ZERO);
+
+            handle.advance(Duration::from_millis(999));
+            crate::task::yield_now().await;
+            assert!(!task.is_finished());
+
+            handle.advance(Duration::from_millis(1));
+            task.await.unwrap();
+            assert_eq!(t0.elapsed(), Duration::from_secs(1));
+        });
+    }
+
+    #[test]
+    fn pause_resume() {
+        let runtime = Runtime::new();
+        runtime.block_on(async {
+            pause();
+            let t0 = Instant::now();
+            let mut sleep = Box::pin(sleep(Duration::from_secs(1)));
+            assert!(futures_util::poll!(sleep.as_mut()).is_pending());
+
+            advance(Duration::from_millis(400)).await;
+            assert!(futures_util::poll!(sleep.as_mut()).is_pending());
+
+            // advance exactly to the deadline
+            advance(Duration::from_millis(600)).await;
+            assert!(futures_util::poll!(sleep.as_mut()).is_ready());
+            assert_eq!(t0.elapsed(), Duration::from_secs(1));
+
+            // the clock stands still until the task is woken by `advance`
+            let task = crate::task::spawn(super::sleep(Duration::from_secs(1)));
+            crate::task::yield_now().await;
+            advance(Duration::from_secs(1)).await;
+            task.await.unwrap();
+            assert_eq!(t0.elapsed(), Duration::from_secs(2));
+
+            resume();
+            super::sleep(Duration::from_secs(1)).await;
+            assert!(t0.elapsed() > Duration::from_secs(3));
+        });
+    }
+
+    #[test]
+    fn pause_auto_advance() {
+        let runtime = Runtime::new();
+        runtime.block_on(async {
+            pause();
+            let t0 = Instant::now();
+            let near = |secs| {
+                let elapsed = t0.elapsed();
+                elapsed >= Duration::from_secs(secs)
+                    && elapsed < Duration::from_secs(secs) + Duration::from_micros(1)
+            };
+            // the clock jumps to the timer once all tasks are blocked
+            sleep(Duration::from_secs(1)).await;
+            assert!(near(1));
+            timeout(Duration::from_secs(1), std::future::pending::<()>())
+                .await
+                .unwrap_err();
+            assert!(near(2));
+        });
+    }
+
+    #[test]
+    #[should_panic(expected = "no events, all tasks will block forever")]
+    fn pause_without_timer() {
+        let runtime = Runtime::new();
+        runtime.block_on(async {
+            pause();
+            std::future::pending::<()>().await;
+        });
+    }
+
+    #[test]
+    fn instant_nanos() {
+        for seed in 0..10 {
+            let runtime = Runtime::with_seed_and_config(seed, crate::Config::default());
+            runtime.block_on(async {
+                let handle = TimeHandle::current();
+                let start = Instant::now();
+                assert_eq!(handle.instant_to_nanos(start), 0);
+                // instants before the start of the simulation map to 0
+                assert_eq!(handle.instant_to_nanos(start - Duration::from_secs(1)), 0);
+                sleep(Duration::from_secs(1)).await;
+                let now = Instant::now();
+                let nanos = handle.instant_to_nanos(now);
+                assert_eq!(nanos, handle.elapsed().as_nanos() as u64);
+                assert!((1_000_000_000..1_001_000_000).contains(&nanos));
+                assert_eq!(handle.nanos_to_instant(nanos), now);
+            });
+        }
+    }
+
+    #[test]
+    fn freeze() {
+        let runtime = Runtime::new();
+        runtime.block_on(async {
+            let handle = TimeHandle::current();
+            let t0 = Instant::now();
+            handle.freeze();
+            assert!(handle.is_frozen());
+
+            // ready tasks keep running while the clock stands still
+            let task = crate::task::spawn(async {
+                for _ in 0..10 {
+                    crate::task::yield_now().await;
+                }
+            });
+            for _ in 0..10 {
+                crate::task::yield_now().await;
+            }
+            task.await.unwrap();
+            assert_eq!(t0.elapsed(), Duration::ZERO);
+
+            handle.unfreeze();
+            assert!(!handle.is_frozen());
+            sleep(Duration::from_secs(1)).await;
+            assert!(t0.elapsed() >= Duration::from_secs(1));
+        });
+    }
+
+    #[test]
+    fn clock_modes() {
+        // (frozen, auto-advance, paused)
+        let check = |frozen: bool, auto_advance: bool, paused: bool| {
+            let runtime = Runtime::new();
+            let handle = runtime.handle().time.clone();
+            let (on_poll, manually) = runtime.block_on(async {
+                if paused {
+                    pause();
+                }
+                handle.set_auto_advance(auto_advance);
+                if frozen {
+                    handle.freeze();
+                }
+                let t0 = handle.elapsed();
+                for _ in 0..10 {
+                    crate::task::yield_now().await;
+                }
+                let t1 = handle.elapsed();
+                handle.advance(Duration::from_secs(1));
+                (t1 > t0, handle.elapsed() >= t1 + Duration::from_secs(1))
+            });
+            let mode = handle.mode();
+            let t0 = handle.elapsed();
+            let when_idle = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                runtime.block_on(async { sleep(Duration::from_secs(1)).await })
+            }))
+            .is_ok();
+            if when_idle {
+                assert!(handle.elapsed() >= t0 + Duration::from_secs(1));
+            }
+            assert_eq!(on_poll, mode.advances_on_poll());
+            assert_eq!(manually, mode.advances_manually());
+            assert_eq!(when_idle, mode.jumps_when_idle());
+            mode
+        };
+        for frozen in [false, true] {
+            for auto_advance in [false, true] {
+                for paused in [false, true] {
+                    let expected = if frozen {
+                        ClockMode::Frozen
+                    } else if !auto_advance {
+                        ClockMode::Manual
+                    } else if paused {
+                        ClockMode::Paused
+                    } else {
+                        ClockMode::Running
+                    };
+                    assert_eq!(check(frozen, auto_advance, paused), expected);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn clock_mode_transitions() {
+        let runtime = Runtime::new();
+        let handle = runtime.handle().time.clone();
+        runtime.block_on(async {
+            assert_eq!(handle.mode(), ClockMode::Running);
+            pause();
+            assert_eq!(handle.mode(), ClockMode::Paused);
+            handle.set_auto_advance(false);
+            assert_eq!(handle.mode(), ClockMode::Manual);
+            handle.freeze();
+            assert_eq!(handle.mode(), ClockMode::Frozen);
+            // lifting a flag falls back to the mode given by the others
+            handle.unfreeze();
+            assert_eq!(handle.mode(), ClockMode::Manual);
+            handle.set_auto_advance(true);
+            assert_eq!(handle.mode(), ClockMode::Paused);
+            handle.freeze();
+            resume();
+            assert_eq!(handle.mode(), ClockMode::Frozen);
+            handle.unfreeze();
+            assert_eq!(handle.mode(), ClockMode::Running);
+        });
+    }
+
+    #[test]
+    fn watcher() {
+        let runtime = Runtime::new();
+        runtime.block_on(async {
+            let handle = TimeHandle::current();
+            sleep(Duration::from_secs(1)).await;
+            let ticks = Arc::new(Mutex::new(vec![]));
+            let ticks0 = ticks.clone();
+            let handle0 = handle.clone();
+            handle.add_watcher(Duration::from_millis(300), move |t| {
+                // the watcher observes the clock at the tick
+                let elapsed = handle0.elapsed();
+                let tick = Duration::from_nanos(handle0.instant_to_nanos(t));
+                assert!(elapsed >= tick && elapsed < tick + Duration::from_micros(1));
+                ticks0.lock().push(tick.as_millis());
+            });
+            sleep(Duration::from_millis(1500)).await;
+            assert_eq!(*ticks.lock(), [1200, 1500, 1800, 2100, 2400]);
+        });
+    }
+
+    #[test]
+    #[should_panic(expected = "no events, all tasks will block forever")]
+    fn watcher_deadlock() {
+        let runtime = Runtime::new();
+        runtime.block_on(async {
+            TimeHandle::current().add_watcher(Duration::from_secs(1), |_| {});
+            std::future::pending::<()>().await;
+        });
+    }
 }
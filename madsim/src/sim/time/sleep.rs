@@ -32,7 +32,7 @@ impl Sleep {
     ///
     /// A `Sleep` instance is elapsed when the requested duration has elapsed.
     pub fn is_elapsed(&self) -> bool {
-        self.handle.clock.now_instant() >= self.deadline
+        self.handle.now_instant() >= self.deadline
     }
 
     /// Resets the `Sleep` instance to a new deadline.
@@ -46,7 +46,7 @@ impl Future for Sleep {
 
     fn poll(self: Pin<&mut Self>, cx: &mut std::task::Context<'_>) -> Poll<Self::Output> {
         if self.is_elapsed() {
-            return Poll::Ready(());
+            return crate::task::coop::poll_proceed(cx).map(|coop| coop.made_progress());
         }
         let waker = cx.waker().clone();
         self.handle.add_timer_at(self.deadline, || waker.wake());
@@ -55,7 +55,12 @@ unsafe extern "C" fn clock_gettime(
                 tv_nsec: dur.subsec_nanos() as _,
             });
         } else if clockid == 1 {
-            tp.write(std::mem::transmute(time.now_instant()));
+            // the layout of `Instant` is unspecified, so write its offset from the zeroed base
+            let dur = time.now_instant() - time.clock.base_instant();
+            tp.write(libc::timespec {
+                tv_sec: dur.as_secs() as _,
+                tv_nsec: dur.subsec_nanos() as _,
+            });
         } else {
             panic!("unsupported clockid: {}", clockid);
         };
@@ -136,4 +141,32 @@ mod tests {
         }
         assert_eq!(times.len(), 1);
     }
+
+    #[test]
+    fn std_instant_follows_simulated_clock() {
+        let runtime = Runtime::new();
+        runtime.block_on(async {
+            let handle = crate::runtime::Handle::current();
+            let (t0, e0) = (Instant::now(), handle.elapsed());
+            crate::time::sleep(Duration::from_secs(1)).await;
+            assert_eq!(t0.elapsed(), handle.elapsed() - e0);
+        });
+    }
+
+    #[test]
+    fn system_epoch() {
+        let now = |seed| {
+            let runtime = Runtime::with_seed_and_config(seed, crate::Config::default());
+            runtime.block_on(async {
+                crate::time::set_system_epoch(SystemTime::UNIX_EPOCH);
+                crate::time::sleep(Duration::from_secs(1)).await;
+                SystemTime::now()
+                    .duration_since(SystemTime::UNIX_EPOCH)
+                    .unwrap()
+                    .as_secs()
+            })
+        };
+        assert_eq!(now(1), 1);
+        assert_eq!(now(2), 1);
+    }
 }
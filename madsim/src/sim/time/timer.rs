@@ -0,0 +1,178 @@
+//! A timer that discards the events of killed nodes.
+
+use crate::task::NodeInfo;
+use std::{
+    cmp::Ordering,
+    collections::BinaryHeap,
+    sync::{Arc, Weak},
+    time::Duration,
+};
+
+/// A timer holding events ordered by their deadlines.
+///
+/// Unlike `naive_timer`, each event may be owned by a node. Once the node is killed,
+/// its events are fired immediately regardless of their deadlines, so that the executor
+/// can drop the woken tasks and the clock never advances to them.
+///
+/// Watchers are periodic events that do not keep the simulation going on their own:
+/// [`next`](Timer::next) ignores them if there is no other event.
+#[derive(Default)]
+pub(super) struct Timer {
+    events: BinaryHeap<Event>,
+    /// The number of events that are not watchers.
+    oneshots: usize,
+    /// The number of fired events.
+    fired: u64,
+}
+
+/// The type of callback function.
+pub(super) type Callback = Box<dyn FnOnce() + Send + Sync + 'static>;
+
+/// A function called periodically with the deadline.
+pub(super) type Watcher = Arc<dyn Fn(Duration) + Send + Sync + 'static>;
+
+impl Timer {
+    /// Add a timer owned by the `node`.
+    ///
+    /// The `callback` will be called on timer expired after `deadline`,
+    /// or as soon as the node is killed.
+    pub fn add(
+        &mut self,
+        deadline: Duration,
+        node: Option<&Arc<NodeInfo>>,
+        callback: impl FnOnce() + Send + Sync + 'static,
+    ) {
+        let event = Event {
+            deadline,
+            node: node.map(Arc::downgrade),
+            kind: Kind::Oneshot(Box::new(callback)),
+        };
+        // a task may still be running when its node is killed
+        if !event.is_alive() {
+            self.fired += 1;
+            event.fire();
+            return;
+        }
+        self.oneshots += 1;
+        self.events.push(event);
+    }
+
+    /// Add a watcher first fired at `deadline` and then every `interval`.
+    pub fn add_watcher(&mut self, deadline: Duration, interval: Duration, watcher: Watcher) {
+        assert!(!interval.is_zero(), "interval must be non-zero");
+        self.events.push(Event {
+            deadline,
+            node: None,
+            kind: Kind::Watcher(interval, watcher),
+        });
+    }
+
+    /// Expire timers.
+    ///
+    /// Given the current time `now`, remove all expired timers.
+    /// Returns their callbacks, which should be called after the timer is unlocked,
+    /// so that a callback can add timers or kill nodes.
+    #[must_use]
+    pub fn expire(&mut self, now: Duration) -> Vec<Callback> {
+        let mut callbacks: Vec<Callback> = vec![];
+        while let Some(t) = self.events.peek() {
+            if t.deadline > now {
+                break;
+            }
+            let event = self.events.pop().unwrap();
+            self.fired += 1;
+            match event.kind {
+                Kind::Oneshot(callback) => {
+                    self.oneshots -= 1;
+                    callbacks.push(callback);
+                }
+                Kind::Watcher(interval, watcher) => {
+                    let (deadline, w) = (event.deadline, watcher.clone());
+                    callbacks.push(Box::new(move || w(deadline)));
+                    self.events.push(Event {
+                        deadline: event.deadline + interval,
+                        node: None,
+                        kind: Kind::Watcher(interval, watcher),
+                    });
+                }
+            }
+        }
+        callbacks
+    }
+
+    /// Get next timer, or `None` if there are only watchers.
+    pub fn next(&self) -> Option<Duration> {
+        if self.oneshots == 0 {
+            return None;
+        }
+        self.events.peek().map(|e| e.deadline)
+    }
+
+    /// Get the number of fired events.
+    pub fn fired(&self) -> u64 {
+        self.fired
+    }
+
+    /// Fire and remove all timers owned by killed nodes.
+    pub fn remove_killed(&mut self) {
+        let (alive, killed) = std::mem::take(&mut self.events)
+            .into_iter()
+            .partition(Event::is_alive);
+        self.events = alive;
+        for event in Vec::from(killed) {
+            self.fired += 1;
+            self.oneshots -= 1;
+            event.fire();
+        }
+    }
+}
+
+struct Event {
+    deadline: Duration,
+    node: Option<Weak<NodeInfo>>,
+    kind: Kind,
+}
+
+enum Kind {
+    Oneshot(Callback),
+    /// A watcher and its interval.
+    Watcher(Duration, Watcher),
+}
+
+impl Event {
+    /// Calls the callback of a oneshot event.
+    fn fire(self) {
+        match self.kind {
+            Kind::Oneshot(callback) => callback(),
+            Kind::Watcher(..) => unreachable!("watchers are never owned by a node"),
+        }
+    }
+
+    fn is_alive(&self) -> bool {
+        match &self.node {
+            Some(node) => node.upgrade().is_some_and(|node| !node.is_killed()),
+            None => true,
+        }
+    }
+}
+
+impl PartialEq for Event {
+    fn eq(&self, other: &Self) -> bool {
+        self.deadline.eq(&other.deadline)
+    }
+}
+
+impl Eq for Event {}
+
+// BinaryHeap is a max-heap. So we need to reverse the order.
+impl PartialOrd for Event {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Event {
+    fn cmp(&self, other: &Event) -> Ordering {
+        other.deadline.cmp(&self.deadline)
+    }
+}
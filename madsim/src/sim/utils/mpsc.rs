@@ -4,12 +4,19 @@
 use crate::rand::GlobalRng;
 use rand::Rng;
 use spin::Mutex;
-use std::{fmt, sync::Arc};
+use std::{collections::VecDeque, fmt, sync::Arc};
 
 /// Creates a new asynchronous channel, returning the sender/receiver halves.
-pub fn channel<T>() -> (Sender<T>, Receiver<T>) {
+///
+/// Values with higher `priority` are received first. The priority of a value must not change
+/// while it is in the channel.
+pub fn channel<T>(priority: fn(&T) -> u8) -> (Sender<T>, Receiver<T>) {
     let inner = Arc::new(Inner {
-        queue: Mutex::new(Vec::new()),
+        queue: Mutex::new(Queue {
+            values: VecDeque::new(),
+            prioritized: 0,
+        }),
+        priority,
     });
     let sender = Sender {
         inner: Arc::clone(&inner),
@@ -30,7 +37,25 @@ pub struct Receiver<T> {
 }
 
 struct Inner<T> {
-    queue: Mutex<Vec<T>>,
+    queue: Mutex<Queue<T>>,
+    priority: fn(&T) -> u8,
+}
+
+struct Queue<T> {
+    values: VecDeque<T>,
+    /// The number of values with a non-zero priority.
+    ///
+    /// The receiver only scans the values for the highest priority if this is non-zero.
+    prioritized: usize,
+}
+
+impl<T> Inner<T> {
+    /// Updates the counter for a value removed from the queue.
+    fn removed(&self, queue: &mut Queue<T>, value: &T) {
+        if (self.priority)(value) != 0 {
+            queue.prioritized -= 1;
+        }
+    }
 }
 
 impl<T> Clone for Sender<T> {
@@ -54,11 +79,31 @@ impl<T> Sender<T> {
     /// Attempts to send a value on this channel, returning it back if it could not be sent.
     pub fn send(&self, value: T) -> Result<(), SendError<T>> {
         if let Some(mut queue) = self.inner.queue.try_lock() {
-            queue.push(value);
+            if (self.inner.priority)(&value) != 0 {
+                queue.prioritized += 1;
+            }
+            queue.values.push_back(value);
             return Ok(());
         }
         Err(SendError(value))
     }
+
+    /// Returns the number of pending values.
+    pub fn len(&self) -> usize {
+        self.inner.queue.lock().values.len()
+    }
+
+    /// Removes and returns all pending values that satisfy the predicate, in the order they
+    /// were sent.
+    pub fn remove_by(&self, f: impl Fn(&T) -> bool) -> Vec<T> {
+        let mut queue = self.inner.queue.lock();
+        let (removed, kept): (VecDeque<T>, _) = queue.values.drain(..).partition(|v| f(v));
+        queue.values = kept;
+        for value in &removed {
+            self.inner.removed(&mut queue, value);
+        }
+        removed.into()
+    }
 }
 
 /// This enumeration is the list of the possible reasons
@@ -69,16 +114,75 @@ pub enum TryRecvError {
 }
 
 impl<T> Receiver<T> {
-    /// Attempts to return a pending value on this receiver without blocking.
+    /// Attempts to return a random pending value with the highest priority without blocking.
     pub fn try_recv_random(&self, rng: &GlobalRng) -> Result<T, TryRecvError> {
         let mut queue = self.inner.queue.lock();
-        if !queue.is_empty() {
-            let idx = rng.with(|rng| rng.gen_range(0..queue.len()));
-            Ok(queue.swap_remove(idx))
-        } else if Arc::weak_count(&self.inner) == 0 {
-            Err(TryRecvError::Disconnected)
+        if queue.values.is_empty() {
+            return Err(self.empty_error());
+        }
+        let priority = self.inner.priority;
+        let idx = if queue.prioritized == 0 {
+            rng.with(|rng| rng.gen_range(0..queue.values.len()))
+        } else {
+            let max = queue.values.iter().map(priority).max().unwrap();
+            let count = queue.values.iter().filter(|v| priority(v) == max).count();
+            let nth = rng.with(|rng| rng.gen_range(0..count));
+            (0..queue.values.len())
+                .filter(|&i| priority(&queue.values[i]) == max)
+                .nth(nth)
+                .unwrap()
+        };
+        let value = queue.values.swap_remove_back(idx).unwrap();
+        self.inner.removed(&mut queue, &value);
+        Ok(value)
+    }
+
+    /// Attempts to return the earliest pending value with the highest priority without blocking.
+    pub fn try_recv(&self) -> Result<T, TryRecvError> {
+        let mut queue = self.inner.queue.lock();
+        if queue.values.is_empty() {
+            return Err(self.empty_error());
+        }
+        let priority = self.inner.priority;
+        let idx = if queue.prioritized == 0 {
+            0
+        } else {
+            let max = queue.values.iter().map(priority).max().unwrap();
+            queue
+                .values
+                .iter()
+                .position(|v| priority(v) == max)
+                .unwrap()
+        };
+        let value = queue.values.remove(idx).unwrap();
+        self.inner.removed(&mut queue, &value);
+        Ok(value)
+    }
+
+    fn empty_error(&self) -> TryRecvError {
+        if Arc::weak_count(&self.inner) == 0 {
+            TryRecvError::Disconnected
         } else {
-            Err(TryRecvError::Empty)
+            TryRecvError::Empty
         }
     }
+
+    /// Attempts to return the earliest pending value that satisfies the predicate.
+    pub fn try_recv_by(&self, f: impl Fn(&T) -> bool) -> Option<T> {
+        let mut queue = self.inner.queue.lock();
+        let idx = queue.values.iter().position(f)?;
+        let value = queue.values.remove(idx)?;
+        self.inner.removed(&mut queue, &value);
+        Some(value)
+    }
+
+    /// Returns `true` if there is no pending value.
+    pub fn is_empty(&self) -> bool {
+        self.inner.queue.lock().values.is_empty()
+    }
+
+    /// Calls a closure on each pending value in the order they were sent.
+    pub fn for_each(&self, f: impl FnMut(&T)) {
+        self.inner.queue.lock().values.iter().for_each(f);
+    }
 }
@@ -0,0 +1,95 @@
+//! Generation of random test inputs.
+//!
+//! Generators are closures drawing from the thread-local random number generator of `rand`,
+//! so the generated inputs differ on each run. Use the `madsim` cfg to derive them from the
+//! seed of the simulation.
+//!
+//! # Example
+//!
+//! ```
+//! use madsim::{generate, rand::Rng};
+//!
+//! #[derive(Debug, PartialEq)]
+//! enum Op {
+//!     Put(u32, u64),
+//!     Get(u32),
+//! }
+//!
+//! let mut kind = generate::weighted(&[3, 1]);
+//! let mut key = generate::range(0..10u32);
+//! let ops = generate::gen_ops(100, |rng| match kind(rng) {
+//!     0 => Op::Put(key(rng), rng.gen()),
+//!     _ => Op::Get(key(rng)),
+//! });
+//! assert_eq!(ops.len(), 100);
+//! ```
+
+use rand::{distributions::uniform::SampleUniform, rngs::ThreadRng, Rng};
+use std::ops::Range;
+
+/// Generates `count` values by calling the generator with the thread-local random number
+/// generator.
+pub fn gen_ops<T>(count: usize, mut generator: impl FnMut(&mut ThreadRng) -> T) -> Vec<T> {
+    let mut rng = rand::thread_rng();
+    (0..count).map(|_| generator(&mut rng)).collect()
+}
+
+/// Returns a generator of values uniformly drawn from the range.
+///
+/// # Panics
+///
+/// The generator panics if the range is empty.
+pub fn range<T>(range: Range<T>) -> impl FnMut(&mut ThreadRng) -> T
+where
+    T: SampleUniform + PartialOrd + Clone,
+{
+    move |rng| rng.gen_range(range.clone())
+}
+
+/// Returns a generator of values uniformly chosen from `choices`.
+///
+/// # Panics
+///
+/// This function panics if `choices` is empty.
+pub fn choose<T: Clone>(choices: &[T]) -> impl FnMut(&mut ThreadRng) -> T + '_ {
+    assert!(!choices.is_empty(), "no choice to choose from");
+    move |rng| choices[rng.gen_range(0..choices.len())].clone()
+}
+
+/// Returns a generator of indices into `weights`, each drawn with a probability
+/// proportional to its weight.
+///
+/// # Panics
+///
+/// This function panics if all weights are zero.
+pub fn weighted(weights: &[u32]) -> impl FnMut(&mut ThreadRng) -> usize + '_ {
+    let dist = rand::distributions::WeightedIndex::new(weights)
+        .expect("the total weight must be positive");
+    move |rng| rng.sample(&dist)
+}
+
+/// Returns a generator of `true` with probability `p`.
+///
+/// # Panics
+///
+/// This function panics if `p` is not in `[0, 1]`.
+pub fn with_probability(p: f64) -> impl FnMut(&mut ThreadRng) -> bool {
+    assert!((0.0..=1.0).contains(&p), "probability must be in [0, 1]");
+    move |rng| rng.gen_bool(p)
+}
+
+/// Returns a generator of vectors whose length is drawn from `len`, with elements
+/// produced by `element`.
+///
+/// # Panics
+///
+/// The generator panics if `len` is empty.
+pub fn vec_of<T>(
+    len: Range<usize>,
+    mut element: impl FnMut(&mut ThreadRng) -> T,
+) -> impl FnMut(&mut ThreadRng) -> Vec<T> {
+    move |rng| {
+        let len = rng.gen_range(len.clone());
+        (0..len).map(|_| element(rng)).collect()
+    }
+}
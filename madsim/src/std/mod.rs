@@ -1,7 +1,19 @@
 pub mod fs;
+pub mod generate;
 pub mod net;
+pub mod stream;
+pub mod sync;
+pub mod task;
 pub mod time;
 
+pub use futures_util::{join, select};
 pub use rand;
 pub use std::collections;
-pub use tokio::{main, task, test};
+pub use tokio::{main, task_local, test};
+
+/// Returns `true` if called within a madsim runtime.
+///
+/// Always returns `false` without the `madsim` cfg.
+pub fn is_simulated() -> bool {
+    false
+}
@@ -0,0 +1,85 @@
+//! Combinators for streams.
+//!
+//! These are the combinators of [`futures::stream`], which poll the constituent streams
+//! in a round-robin order. Use the `madsim` cfg to poll them in a random order drawn
+//! from the seed of the simulation.
+//!
+//! [`futures::stream`]: https://docs.rs/futures/0.3/futures/stream/index.html
+
+use futures_util::Stream;
+use std::{
+    fmt,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+pub use futures_util::stream::{select, Select};
+
+/// Merges a collection of streams into one, yielding items as they become available on any.
+///
+/// Unlike [`futures::stream::select_all`], the streams need not be `Unpin`.
+///
+/// [`futures::stream::select_all`]: https://docs.rs/futures/0.3/futures/stream/fn.select_all.html
+///
+/// # Example
+///
+/// ```
+/// use madsim::stream;
+/// use madsim::export::futures::{stream::iter, StreamExt};
+///
+/// let runtime = tokio::runtime::Runtime::new().unwrap();
+/// runtime.block_on(async {
+///     let streams = (0..3).map(|i| iter([i * 10, i * 10 + 1]));
+///     let mut items = stream::select_all(streams).collect::<Vec<_>>().await;
+///     items.sort();
+///     assert_eq!(items, [0, 1, 10, 11, 20, 21]);
+/// });
+/// ```
+pub fn select_all<I>(streams: I) -> SelectAll<I::Item>
+where
+    I: IntoIterator,
+    I::Item: Stream,
+{
+    SelectAll {
+        inner: futures_util::stream::select_all(streams.into_iter().map(Box::pin)),
+    }
+}
+
+/// Stream returned by [`select_all`].
+#[must_use = "streams do nothing unless polled"]
+pub struct SelectAll<St> {
+    inner: futures_util::stream::SelectAll<Pin<Box<St>>>,
+}
+
+impl<St: Stream> SelectAll<St> {
+    /// Adds a stream to the set.
+    pub fn push(&mut self, stream: St) {
+        self.inner.push(Box::pin(stream));
+    }
+
+    /// Returns the number of streams that have not ended.
+    pub fn len(&self) -> usize {
+        self.inner.len()
+    }
+
+    /// Returns `true` if all streams have ended.
+    pub fn is_empty(&self) -> bool {
+        self.inner.is_empty()
+    }
+}
+
+impl<St: Stream> Stream for SelectAll<St> {
+    type Item = St::Item;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        Pin::new(&mut self.inner).poll_next(cx)
+    }
+}
+
+impl<St: Stream> fmt::Debug for SelectAll<St> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SelectAll")
+            .field("len", &self.inner.len())
+            .finish()
+    }
+}
@@ -0,0 +1,126 @@
+//! Synchronization primitives.
+//!
+//! Most primitives are those of `tokio::sync` and `tokio_util::sync`.
+//! Fault injection and the options tuning the simulated wakeup order exist only in simulation.
+
+use spin::Mutex as SpinMutex;
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+
+pub use tokio::sync::futures::Notified;
+pub use tokio::sync::{
+    AcquireError, Barrier, BarrierWaitResult, Mutex, MutexGuard, Notify, RwLock, RwLockReadGuard,
+    RwLockWriteGuard, Semaphore, SemaphorePermit, TryAcquireError, TryLockError,
+};
+pub use tokio_util::sync::CancellationToken;
+
+/// A bounded multi-producer, single-consumer queue.
+pub mod mpsc {
+    pub use tokio::sync::mpsc::{channel, error, Receiver, Sender};
+}
+
+/// A one-shot channel is used for sending a single message between asynchronous tasks.
+pub mod oneshot {
+    pub use tokio::sync::oneshot::{channel, error, Receiver, Sender};
+}
+
+/// A set of named checkpoints for coordinating tasks around logical phases.
+///
+/// Tasks wait on a checkpoint by name until it is signaled. Once signaled, all waiters
+/// are released. Later waiters return immediately.
+///
+/// # Example
+///
+/// ```
+/// use madsim::sync::Checkpoint;
+///
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// let checkpoint = Checkpoint::new();
+/// checkpoint.register("after election");
+/// let cp = checkpoint.clone();
+/// let follower = tokio::spawn(async move { cp.wait("after election").await });
+/// checkpoint.signal("after election");
+/// follower.await.unwrap();
+/// # });
+/// ```
+#[derive(Clone, Default)]
+pub struct Checkpoint {
+    inner: Arc<SpinMutex<HashMap<String, Arc<State>>>>,
+}
+
+#[derive(Default)]
+struct State {
+    signaled: AtomicBool,
+    notify: Notify,
+}
+
+impl Checkpoint {
+    /// Creates a new set of checkpoints.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers a checkpoint named `name`. Does nothing if it is already registered.
+    pub fn register(&self, name: &str) {
+        self.inner.lock().entry(name.into()).or_default();
+    }
+
+    /// Waits until the checkpoint named `name` is signaled.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if the checkpoint is not registered.
+    pub async fn wait(&self, name: &str) {
+        let state = self.state(name);
+        loop {
+            let notified = state.notify.notified();
+            if state.signaled.load(Ordering::SeqCst) {
+                return;
+            }
+            notified.await;
+        }
+    }
+
+    /// Signals the checkpoint named `name`, releasing all waiters.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if the checkpoint is not registered.
+    pub fn signal(&self, name: &str) {
+        let state = self.state(name);
+        state.signaled.store(true, Ordering::SeqCst);
+        state.notify.notify_waiters();
+    }
+
+    /// Returns `true` if the checkpoint named `name` has been signaled.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if the checkpoint is not registered.
+    pub fn is_signaled(&self, name: &str) -> bool {
+        self.state(name).signaled.load(Ordering::SeqCst)
+    }
+
+    fn state(&self, name: &str) -> Arc<State> {
+        let inner = self.inner.lock();
+        let state = inner
+            .get(name)
+            .unwrap_or_else(|| panic!("checkpoint {name:?} is not registered"));
+        state.clone()
+    }
+}
+
+impl fmt::Debug for Checkpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let inner = self.inner.lock();
+        let mut names = inner.keys().collect::<Vec<_>>();
+        names.sort();
+        f.debug_struct("Checkpoint").field("names", &names).finish()
+    }
+}
@@ -0,0 +1,36 @@
+//! Asynchronous green-threads.
+//!
+//! Most items are those of `tokio::task`.
+
+use std::time::Duration;
+
+pub use futures_util::future::{join_all, JoinAll};
+pub use tokio::task::*;
+
+/// Yields execution back to the scheduler, and stays descheduled until `duration` has elapsed.
+///
+/// With a zero duration, it is the same as [`yield_now`].
+pub async fn yield_for(duration: Duration) {
+    if duration.is_zero() {
+        return yield_now().await;
+    }
+    tokio::time::sleep(duration).await
+}
+
+/// Waits for the first of the tasks to complete.
+///
+/// Returns the output of the completed task, its index in `handles`, and the remaining
+/// handles. This is `futures::future::select_all` on the join handles.
+///
+/// # Panics
+///
+/// This function panics if `handles` is empty.
+pub async fn select_any<T>(
+    handles: Vec<JoinHandle<T>>,
+) -> (Result<T, JoinError>, usize, Vec<JoinHandle<T>>) {
+    assert!(
+        !handles.is_empty(),
+        "select_any requires at least one handle"
+    );
+    futures_util::future::select_all(handles).await
+}
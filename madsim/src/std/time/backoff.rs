@@ -0,0 +1,88 @@
+use super::*;
+use rand::Rng;
+use std::future::Future;
+
+/// A policy of exponential backoff used by [`retry_with_backoff`].
+///
+/// The delay before the `n`-th retry is `min(base * 2^n, max)`, reduced by a random
+/// fraction of up to `jitter` drawn from the thread-local random number generator.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Backoff {
+    /// The delay before the first retry.
+    pub base: Duration,
+    /// The maximum delay between retries.
+    pub max: Duration,
+    /// The maximum fraction of the delay to be randomly subtracted, in `[0, 1]`.
+    pub jitter: f64,
+    /// The maximum number of retries.
+    pub max_retries: usize,
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Backoff {
+            base: Duration::from_millis(10),
+            max: Duration::from_secs(10),
+            jitter: 0.5,
+            max_retries: 10,
+        }
+    }
+}
+
+impl Backoff {
+    /// Returns the delay before the `n`-th retry, starting from 0.
+    fn delay(&self, n: usize) -> Duration {
+        let exp = 2u32.checked_pow(n as u32).unwrap_or(u32::MAX);
+        let delay = self.base.saturating_mul(exp).min(self.max);
+        if self.jitter == 0.0 {
+            return delay;
+        }
+        let fraction = rand::thread_rng().gen_range(0.0..self.jitter);
+        delay.mul_f64(1.0 - fraction)
+    }
+}
+
+/// Calls `op` until it succeeds, sleeping between attempts according to the `policy`.
+///
+/// Returns the first success, or the last error if all retries fail.
+///
+/// # Panics
+///
+/// This function panics if `policy.jitter` is not in `[0, 1]`.
+///
+/// # Example
+///
+/// ```
+/// use madsim::time::{retry_with_backoff, Backoff};
+///
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// let mut attempts = 0;
+/// let result = retry_with_backoff(Backoff::default(), || {
+///     attempts += 1;
+///     let ok = attempts == 3;
+///     async move { if ok { Ok(()) } else { Err("failed") } }
+/// })
+/// .await;
+/// assert_eq!(result, Ok(()));
+/// # });
+/// ```
+pub async fn retry_with_backoff<T, E, F, Fut>(policy: Backoff, mut op: F) -> Result<T, E>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+{
+    assert!(
+        (0.0..=1.0).contains(&policy.jitter),
+        "jitter must be in [0, 1]"
+    );
+    let mut retries = 0;
+    loop {
+        match op().await {
+            Ok(value) => return Ok(value),
+            Err(e) if retries >= policy.max_retries => return Err(e),
+            Err(_) => {}
+        }
+        sleep(policy.delay(retries)).await;
+        retries += 1;
+    }
+}
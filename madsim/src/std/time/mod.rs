@@ -0,0 +1,8 @@
+//! Utilities for tracking time.
+
+pub use tokio::time::{error, sleep, sleep_until, timeout, Duration, Instant};
+pub use tokio_util::time::{delay_queue, DelayQueue};
+
+mod backoff;
+
+pub use self::backoff::{retry_with_backoff, Backoff};